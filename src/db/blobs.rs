@@ -0,0 +1,217 @@
+use crate::errors::ApiError;
+use crate::models::schema::schema::{dbschema, dbschema_branch, service_envs};
+use diesel::prelude::*;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, DateTime, Document};
+use mongodb::options::{FindOptions, UpdateOptions};
+use mongodb::Database;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+const BLOB_COLLECTION: &str = "blobs";
+
+// What Postgres stores in place of a document that lives in the blob store
+const BLOB_REF_PREFIX: &str = "blob:sha256:";
+// Put in front of inline documents that would otherwise read as a reference
+const INLINE_ESCAPE_PREFIX: &str = "inline:";
+
+const COLLECT_BATCH_SIZE: usize = 500;
+
+// Documents up to this size stay inline even when Mongo is configured
+const INLINE_THRESHOLD: usize = 4096;
+
+pub fn content_hash(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+/// What a Postgres column holds for a document
+#[derive(Debug, PartialEq)]
+pub enum StoredDocument<'a> {
+    Blob(&'a str), // hash of the blob
+    Inline(&'a str),
+}
+
+pub fn classify(stored: &str) -> StoredDocument<'_> {
+    if let Some(hash) = stored.strip_prefix(BLOB_REF_PREFIX) {
+        return StoredDocument::Blob(hash);
+    }
+    StoredDocument::Inline(stored.strip_prefix(INLINE_ESCAPE_PREFIX).unwrap_or(stored))
+}
+
+/// Column value of a document kept inline
+pub fn inline_document(content: String) -> String {
+    if content.starts_with(BLOB_REF_PREFIX) || content.starts_with(INLINE_ESCAPE_PREFIX) {
+        format!("{}{}", INLINE_ESCAPE_PREFIX, content)
+    } else {
+        content
+    }
+}
+
+/// Hash of a document as stored in Postgres, without reading it back from the blob store
+pub fn stored_hash(stored: &str) -> String {
+    match classify(stored) {
+        StoredDocument::Blob(hash) => hash.to_string(),
+        StoredDocument::Inline(content) => content_hash(content),
+    }
+}
+
+/// Returns what should be written to the Postgres column: the document itself when it is small
+/// or no blob store is configured, otherwise a reference to its content addressed blob.
+/// `column_limit` is the max length of the Postgres column used when falling back to it.
+pub async fn store_document(
+    blobs: Option<&Database>,
+    content: Option<String>,
+    column_limit: usize,
+) -> Result<Option<String>, ApiError> {
+    let Some(content) = content else {
+        return Ok(None);
+    };
+
+    let blobs = match blobs {
+        Some(blobs) if content.len() > INLINE_THRESHOLD => blobs,
+        _ => {
+            let inline = inline_document(content);
+            if inline.chars().count() > column_limit {
+                return Err(ApiError::PayloadTooLarge(format!(
+                    "Document exceeds {} characters and no blob store is configured",
+                    column_limit
+                )));
+            }
+            return Ok(Some(inline));
+        }
+    };
+
+    let hash = content_hash(&content);
+
+    // Identical documents share a blob, the first write wins. `written_at` keeps the blob from
+    // being collected before the row referencing it is committed
+    blobs
+        .collection::<Document>(BLOB_COLLECTION)
+        .update_one(
+            doc! { "_id": &hash },
+            doc! {
+                "$setOnInsert": {
+                    "content": &content,
+                    "size": content.len() as i64,
+                    "created_at": DateTime::now(),
+                },
+                "$set": { "written_at": DateTime::now() },
+            },
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await
+        .map_err(|_| {
+            ApiError::ServiceUnavailable("Failed to write document to the blob store".to_string())
+        })?;
+
+    Ok(Some(format!("{}{}", BLOB_REF_PREFIX, hash)))
+}
+
+/// Inverse of `store_document`, inline documents are returned as they are
+pub async fn resolve_document(
+    blobs: Option<&Database>,
+    stored: Option<String>,
+) -> Result<Option<String>, ApiError> {
+    let Some(stored) = stored else {
+        return Ok(None);
+    };
+
+    let hash = match classify(&stored) {
+        StoredDocument::Blob(hash) => hash,
+        StoredDocument::Inline(content) => return Ok(Some(content.to_string())),
+    };
+
+    let blobs = blobs.ok_or_else(|| {
+        ApiError::ServiceUnavailable(
+            "Document is kept in the blob store which is not configured".to_string(),
+        )
+    })?;
+
+    let blob = blobs
+        .collection::<Document>(BLOB_COLLECTION)
+        .find_one(doc! { "_id": hash }, None)
+        .await
+        .map_err(|_| {
+            ApiError::ServiceUnavailable("Failed to read document from the blob store".to_string())
+        })?
+        .ok_or_else(|| {
+            ApiError::Internal(format!("Blob {} is missing from the blob store", hash))
+        })?;
+
+    blob.get_str("content")
+        .map(|content| Some(content.to_string()))
+        .map_err(|_| ApiError::Internal(format!("Blob {} is corrupted", hash)))
+}
+
+/// Hashes of the blobs the document columns refer to, soft deleted rows included
+pub fn referenced_blobs(conn: &mut PgConnection) -> QueryResult<HashSet<String>> {
+    let pattern = format!("{}%", BLOB_REF_PREFIX);
+    let mut stored = dbschema::table
+        .filter(dbschema::data.like(&pattern))
+        .select(dbschema::data)
+        .load::<Option<String>>(conn)?;
+    stored.extend(
+        dbschema_branch::table
+            .filter(dbschema_branch::data.like(&pattern))
+            .select(dbschema_branch::data)
+            .load::<Option<String>>(conn)?,
+    );
+    let specs = service_envs::table
+        .filter(service_envs::spec.like(&pattern))
+        .select(service_envs::spec)
+        .load::<String>(conn)?;
+
+    Ok(stored
+        .into_iter()
+        .flatten()
+        .chain(specs)
+        .filter_map(|stored| match classify(&stored) {
+            StoredDocument::Blob(hash) => Some(hash.to_string()),
+            StoredDocument::Inline(_) => None,
+        })
+        .collect())
+}
+
+/// Deletes the blobs no row refers to anymore, `referenced` being the hashes still stored in
+/// Postgres. Blobs written after `written_before` are kept, their row may not be committed yet
+pub async fn collect_unreferenced(
+    blobs: &Database,
+    referenced: &HashSet<String>,
+    written_before: DateTime,
+) -> mongodb::error::Result<u64> {
+    let collection = blobs.collection::<Document>(BLOB_COLLECTION);
+    // Blobs from before `written_at` was kept only have their creation time
+    let stale = doc! {
+        "$or": [
+            { "written_at": { "$lt": written_before } },
+            { "written_at": { "$exists": false }, "created_at": { "$lt": written_before } },
+        ]
+    };
+    let mut candidates = collection
+        .find(
+            stale.clone(),
+            FindOptions::builder().projection(doc! { "_id": 1 }).build(),
+        )
+        .await?;
+
+    let mut unreferenced = vec![];
+    while let Some(blob) = candidates.try_next().await? {
+        if let Ok(hash) = blob.get_str("_id") {
+            if !referenced.contains(hash) {
+                unreferenced.push(hash.to_string());
+            }
+        }
+    }
+
+    let mut deleted = 0;
+    for batch in unreferenced.chunks(COLLECT_BATCH_SIZE) {
+        deleted += collection
+            .delete_many(
+                doc! { "$and": [{ "_id": { "$in": batch } }, stale.clone()] },
+                None,
+            )
+            .await?
+            .deleted_count;
+    }
+    Ok(deleted)
+}
@@ -0,0 +1,101 @@
+use crate::db::blobs::content_hash;
+use crate::errors::ApiError;
+use crate::models::schema::{Idempotency_Key, Idempotency_KeyInsertable};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Keys older than this are forgotten and can be reused for a new request
+const IDEMPOTENCY_KEY_TTL_HOURS: i64 = 24;
+
+/// Fingerprint of a request body, a key replayed with a different body is rejected
+pub fn request_hash<T: Serialize>(request: &T) -> String {
+    content_hash(&serde_json::to_string(request).unwrap_or_default())
+}
+
+/// Claims `key` for this request, must run inside the transaction performing the write.
+/// Returns the stored response when the key was already used for the same request; a
+/// concurrent request with the same key blocks on the unique index until the first commits.
+pub fn claim<T: DeserializeOwned>(
+    conn: &mut PgConnection,
+    key: Option<&str>,
+    key_scope: &str,
+    hash: &str,
+) -> Result<Option<T>, ApiError> {
+    use crate::models::schema::schema::idempotency_key::dsl;
+
+    let Some(key) = key else {
+        return Ok(None);
+    };
+
+    diesel::delete(
+        dsl::idempotency_key
+            .filter(dsl::scope.eq(key_scope))
+            .filter(dsl::key.eq(key))
+            .filter(dsl::created_at.lt(Utc::now() - Duration::hours(IDEMPOTENCY_KEY_TTL_HOURS))),
+    )
+    .execute(conn)?;
+
+    let claimed = diesel::insert_into(dsl::idempotency_key)
+        .values(&Idempotency_KeyInsertable {
+            key: key.to_string(),
+            scope: key_scope.to_string(),
+            request_hash: hash.to_string(),
+            response_json: None,
+            created_at: Utc::now(),
+        })
+        .on_conflict((dsl::scope, dsl::key))
+        .do_nothing()
+        .execute(conn)?;
+
+    if claimed == 1 {
+        return Ok(None);
+    }
+
+    let existing = dsl::idempotency_key
+        .filter(dsl::scope.eq(key_scope))
+        .filter(dsl::key.eq(key))
+        .first::<Idempotency_Key>(conn)?;
+
+    if existing.request_hash != hash {
+        return Err(ApiError::UnprocessableEntity(
+            "Idempotency-Key was already used with a different request".to_string(),
+        ));
+    }
+
+    let response_json = existing.response_json.ok_or_else(|| {
+        ApiError::Conflict("A request with this Idempotency-Key is still in progress".to_string())
+    })?;
+
+    serde_json::from_str(&response_json)
+        .map(Some)
+        .map_err(|_| ApiError::Internal("Stored idempotent response is corrupted".to_string()))
+}
+
+/// Stores the response for a key claimed earlier in the same transaction
+pub fn complete<T: Serialize>(
+    conn: &mut PgConnection,
+    key: Option<&str>,
+    key_scope: &str,
+    response: &T,
+) -> Result<(), ApiError> {
+    use crate::models::schema::schema::idempotency_key::dsl;
+
+    let Some(key) = key else {
+        return Ok(());
+    };
+
+    let response_json = serde_json::to_string(response)
+        .map_err(|_| ApiError::Internal("Failed to serialize response".to_string()))?;
+
+    diesel::update(
+        dsl::idempotency_key
+            .filter(dsl::scope.eq(key_scope))
+            .filter(dsl::key.eq(key)),
+    )
+    .set(dsl::response_json.eq(Some(response_json)))
+    .execute(conn)?;
+
+    Ok(())
+}
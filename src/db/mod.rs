@@ -1,22 +1,45 @@
 use diesel::r2d2::{self, ConnectionManager};
-use diesel::PgConnection;
+use diesel::{PgConnection, RunQueryDsl};
 // use mongodb::bson::{doc, Document};
 use mongodb::options::ClientOptions;
 use mongodb::{Client, Database};
+use pool::PoolConfig;
 use rocket::fairing::AdHoc;
+use rocket::tokio::task;
 use std::env;
 
+pub mod blobs;
+pub mod idempotency;
+pub mod outbox;
+pub mod pipeline;
+pub mod pool;
+pub mod quick_links;
 pub mod redis;
+pub mod soft_delete;
+pub mod webhooks;
+
+// Upserts use `ON CONFLICT` on these. The tables are managed by ginger-db, whose
+// `database.toml` only lists table names and cannot declare constraints
+const UNIQUE_INDEXES: [&str; 6] = [
+    "CREATE UNIQUE INDEX IF NOT EXISTS service_identifier_key ON service (identifier)",
+    "CREATE UNIQUE INDEX IF NOT EXISTS service_envs_parent_id_env_key ON service_envs (parent_id, env)",
+    "CREATE UNIQUE INDEX IF NOT EXISTS package_identifier_key ON package (identifier)",
+    "CREATE UNIQUE INDEX IF NOT EXISTS package_env_parent_id_env_key ON package_env (parent_id, env)",
+    "CREATE UNIQUE INDEX IF NOT EXISTS dbschema_branch_parent_id_branch_name_key ON dbschema_branch (parent_id, branch_name)",
+    "CREATE UNIQUE INDEX IF NOT EXISTS idempotency_key_scope_key_key ON idempotency_key (scope, key)",
+];
 
 pub fn connect_mongo(mongo_uri: String, mongo_db_name: String) -> AdHoc {
     AdHoc::on_ignite("Connecting to MongoDB", |rocket| async {
         match connect(mongo_uri, mongo_db_name).await {
             Ok(database) => {
-                print!("Connected to mongo");
+                tracing::info!("Connected to mongo");
                 rocket.manage(database)
             }
+            // Blobs stay in their Postgres columns meanwhile, readiness reports the outage
             Err(error) => {
-                panic!("Cannot connect to instance:: {:?}", error)
+                tracing::error!(?error, "Cannot connect to MongoDB, running degraded");
+                rocket
             }
         }
     })
@@ -27,17 +50,82 @@ async fn connect(mongo_uri: String, mongo_db_name: String) -> mongodb::error::Re
     let client = Client::with_options(client_options)?;
     let database = client.database(mongo_db_name.as_str());
 
-    println!("MongoDB Connected!");
-
     Ok(database)
 }
 
 // Step 3: Create a function to establish a connection to the database
-pub fn connect_rdb() -> r2d2::Pool<ConnectionManager<PgConnection>> {
+pub fn connect_rdb(config: &PoolConfig) -> r2d2::Pool<ConnectionManager<PgConnection>> {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let manager = ConnectionManager::<PgConnection>::new(database_url);
     r2d2::Pool::builder()
-        .max_size(2)
+        .max_size(config.max_size)
+        .min_idle(config.min_idle)
+        .connection_timeout(config.connection_timeout())
+        .idle_timeout(config.idle_timeout())
+        .max_lifetime(config.max_lifetime())
         .build(manager)
         .expect("Failed to create pool.")
 }
+
+fn create_unique_indexes(pool: &r2d2::Pool<ConnectionManager<PgConnection>>) -> Result<(), String> {
+    let mut conn = pool
+        .get()
+        .map_err(|error| format!("failed to get DB connection: {}", error))?;
+    for statement in UNIQUE_INDEXES {
+        // Fails when duplicate rows already exist, they have to be cleaned up before starting
+        diesel::sql_query(statement)
+            .execute(&mut conn)
+            .map_err(|error| format!("{}: {}", statement, error))?;
+    }
+    Ok(())
+}
+
+/// Every upsert errors without these, so the service does not start when one is missing
+pub fn ensure_unique_indexes() -> AdHoc {
+    AdHoc::try_on_ignite("Ensuring unique indexes", |rocket| async move {
+        let Some(pool) = rocket
+            .state::<r2d2::Pool<ConnectionManager<PgConnection>>>()
+            .cloned()
+        else {
+            return Ok(rocket);
+        };
+
+        match task::spawn_blocking(move || create_unique_indexes(&pool)).await {
+            Ok(Ok(())) => Ok(rocket),
+            Ok(Err(error)) => {
+                tracing::error!(%error, "Failed to ensure unique indexes");
+                Err(rocket)
+            }
+            Err(error) => {
+                tracing::error!(?error, "Failed to ensure unique indexes");
+                Err(rocket)
+            }
+        }
+    })
+}
+
+pub fn migrate_quick_links() -> AdHoc {
+    AdHoc::on_liftoff("Migrating quick links", |rocket| {
+        Box::pin(async move {
+            let Some(pool) = rocket
+                .state::<r2d2::Pool<ConnectionManager<PgConnection>>>()
+                .cloned()
+            else {
+                return;
+            };
+
+            let migrated = task::spawn_blocking(move || {
+                let mut conn = pool.get().map_err(|error| error.to_string())?;
+                quick_links::normalize_all(&mut conn).map_err(|error| error.to_string())
+            })
+            .await;
+
+            match migrated {
+                Ok(Ok(0)) => {}
+                Ok(Ok(rewritten)) => tracing::info!(rewritten, "Migrated quick links"),
+                Ok(Err(error)) => tracing::error!(%error, "Failed to migrate quick links"),
+                Err(error) => tracing::error!(?error, "Failed to migrate quick links"),
+            }
+        })
+    })
+}
@@ -0,0 +1,183 @@
+use crate::models::schema::schema::{organization, outbox_event};
+use crate::models::schema::{Outbox_Event, Outbox_EventInsertable};
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use serde_json::{json, Value};
+
+pub const PIPELINE_UPDATE: &str = "pipeline-update";
+pub const CATALOG_UPDATE: &str = "catalog-update";
+pub const WORKSPACE_UPDATE: &str = "workspace-update";
+
+// Delivery attempts before an event is dead lettered
+pub const MAX_ATTEMPTS: i32 = 10;
+
+const FIRST_RETRY_SECS: i64 = 5;
+const MAX_RETRY_SECS: i64 = 60 * 60;
+
+// A claimed row is retried after this when the worker dies before recording the outcome
+const CLAIM_LEASE_SECS: i64 = 5 * 60;
+const MAX_ERROR_LENGTH: usize = 2000;
+
+/// Records an event for the members of the workspace, to be called in the transaction of the
+/// change it describes. The group is resolved now so renames and deletions do not lose it
+pub fn enqueue(
+    conn: &mut PgConnection,
+    org_id: &str,
+    topic: &str,
+    payload: Value,
+) -> QueryResult<()> {
+    let Some(group_id) = organization::table
+        .filter(organization::slug.eq(org_id))
+        .select(organization::group_id)
+        .first::<String>(conn)
+        .optional()?
+    else {
+        return Ok(());
+    };
+
+    let now = Utc::now();
+    diesel::insert_into(outbox_event::table)
+        .values(&Outbox_EventInsertable {
+            organization_id: org_id.to_string(),
+            group_id,
+            topic: topic.to_string(),
+            payload: payload.to_string(),
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            created_at: now,
+            delivered_at: None,
+            dead_lettered_at: None,
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// `kind` is one of service, package, dbschema, their environments and branches, or snapshot,
+/// `action` what happened to it (created, updated, published, deleted, restored)
+pub fn enqueue_catalog(
+    conn: &mut PgConnection,
+    org_id: &str,
+    kind: &str,
+    identifier: &str,
+    action: &str,
+) -> QueryResult<()> {
+    enqueue(
+        conn,
+        org_id,
+        CATALOG_UPDATE,
+        json!({"org_id": org_id, "kind": kind, "identifier": identifier, "action": action}),
+    )
+}
+
+pub fn enqueue_workspace(conn: &mut PgConnection, org_id: &str, action: &str) -> QueryResult<()> {
+    enqueue(
+        conn,
+        org_id,
+        WORKSPACE_UPDATE,
+        json!({"org_id": org_id, "action": action}),
+    )
+}
+
+/// Exponential backoff after the given number of failed attempts
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    Duration::seconds((FIRST_RETRY_SECS << exponent).min(MAX_RETRY_SECS))
+}
+
+/// When rows claimed at `now` are due again, should their outcome never be recorded. Webhook
+/// deliveries are claimed the same way
+pub fn lease_until(now: DateTime<Utc>) -> DateTime<Utc> {
+    now + Duration::seconds(CLAIM_LEASE_SECS)
+}
+
+/// What to record for a failed attempt, of an outbox event or a webhook delivery
+#[derive(Debug, PartialEq)]
+pub struct FailedAttempt {
+    pub attempts: i32,
+    pub error: String,
+    pub next_attempt_at: DateTime<Utc>,
+    pub given_up_at: Option<DateTime<Utc>>, // set once `max_attempts` is reached
+}
+
+impl FailedAttempt {
+    pub fn new(previous_attempts: i32, max_attempts: i32, error: &str, now: DateTime<Utc>) -> Self {
+        let attempts = previous_attempts + 1;
+        FailedAttempt {
+            attempts,
+            error: error.chars().take(MAX_ERROR_LENGTH).collect(),
+            next_attempt_at: now + retry_delay(attempts),
+            given_up_at: (attempts >= max_attempts).then_some(now),
+        }
+    }
+}
+
+/// Takes up to `limit` due events, other workers skip them until the lease runs out
+pub fn claim_due(conn: &mut PgConnection, limit: i64) -> QueryResult<Vec<Outbox_Event>> {
+    conn.transaction(|conn| {
+        let now = Utc::now();
+        let events = outbox_event::table
+            .filter(outbox_event::delivered_at.is_null())
+            .filter(outbox_event::dead_lettered_at.is_null())
+            .filter(outbox_event::next_attempt_at.le(now))
+            .order(outbox_event::id.asc())
+            .limit(limit)
+            .for_update()
+            .skip_locked()
+            .load::<Outbox_Event>(conn)?;
+
+        let ids: Vec<i64> = events.iter().map(|event| event.id).collect();
+        diesel::update(outbox_event::table.filter(outbox_event::id.eq_any(&ids)))
+            .set(outbox_event::next_attempt_at.eq(lease_until(now)))
+            .execute(conn)?;
+
+        Ok(events)
+    })
+}
+
+pub fn mark_delivered(conn: &mut PgConnection, event_id: i64) -> QueryResult<()> {
+    diesel::update(outbox_event::table.find(event_id))
+        .set((
+            outbox_event::delivered_at.eq(Some(Utc::now())),
+            outbox_event::attempts.eq(outbox_event::attempts + 1),
+            outbox_event::last_error.eq(None::<String>),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Schedules the next attempt, or dead letters the event once it ran out of attempts
+pub fn mark_failed(
+    conn: &mut PgConnection,
+    event: &Outbox_Event,
+    error: &str,
+    now: DateTime<Utc>,
+) -> QueryResult<bool> {
+    let failed = FailedAttempt::new(event.attempts, MAX_ATTEMPTS, error, now);
+
+    diesel::update(outbox_event::table.find(event.id))
+        .set((
+            outbox_event::attempts.eq(failed.attempts),
+            outbox_event::last_error.eq(Some(failed.error)),
+            outbox_event::next_attempt_at.eq(failed.next_attempt_at),
+            outbox_event::dead_lettered_at.eq(failed.given_up_at),
+        ))
+        .execute(conn)?;
+    Ok(failed.given_up_at.is_some())
+}
+
+/// Puts a dead lettered event back in the queue with a fresh set of attempts
+pub fn requeue(conn: &mut PgConnection, org_id: &str, event_id: i64) -> QueryResult<usize> {
+    diesel::update(
+        outbox_event::table
+            .find(event_id)
+            .filter(outbox_event::organization_id.eq(org_id))
+            .filter(outbox_event::dead_lettered_at.is_not_null()),
+    )
+    .set((
+        outbox_event::attempts.eq(0),
+        outbox_event::next_attempt_at.eq(Utc::now()),
+        outbox_event::dead_lettered_at.eq(None::<DateTime<Utc>>),
+    ))
+    .execute(conn)
+}
@@ -0,0 +1,239 @@
+use crate::models::schema::schema::{
+    dbschema, dbschema_branch, organization, package, package_env, service, service_envs,
+};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+
+pub const PASSING: &str = "passing";
+pub const FAILED: &str = "failed";
+
+/// When the pipeline of an environment last changed status and last passed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PipelineTimestamps {
+    pub changed_at: Option<DateTime<Utc>>,
+    pub last_passed_at: Option<DateTime<Utc>>,
+}
+
+impl PipelineTimestamps {
+    /// Timestamps once `status` is reported, repeating the current status keeps `changed_at`
+    pub fn after(self, previous: Option<&str>, status: &str, now: DateTime<Utc>) -> Self {
+        PipelineTimestamps {
+            changed_at: match self.changed_at {
+                Some(changed_at) if previous == Some(status) => Some(changed_at),
+                _ => Some(now),
+            },
+            last_passed_at: if status == PASSING {
+                Some(now)
+            } else {
+                self.last_passed_at
+            },
+        }
+    }
+}
+
+type PipelineRow = (Option<String>, Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+fn next_timestamps(row: Option<PipelineRow>, status: &str) -> PipelineTimestamps {
+    let (previous, changed_at, last_passed_at) = row.unwrap_or_default();
+    PipelineTimestamps {
+        changed_at,
+        last_passed_at,
+    }
+    .after(previous.as_deref(), status, Utc::now())
+}
+
+// A missing environment is left alone, as it was before timestamps were tracked
+pub fn set_service_pipeline(
+    conn: &mut PgConnection,
+    service_id: i64,
+    env: &str,
+    status: &str,
+) -> QueryResult<usize> {
+    let target = || {
+        service_envs::table
+            .filter(service_envs::parent_id.eq(service_id))
+            .filter(service_envs::env.eq(env))
+    };
+    let row = target()
+        .select((
+            service_envs::pipeline_status,
+            service_envs::pipeline_changed_at,
+            service_envs::last_passed_at,
+        ))
+        .first::<PipelineRow>(conn)
+        .optional()?;
+    let timestamps = next_timestamps(row, status);
+
+    diesel::update(target())
+        .set((
+            service_envs::pipeline_status.eq(status),
+            service_envs::pipeline_changed_at.eq(timestamps.changed_at),
+            service_envs::last_passed_at.eq(timestamps.last_passed_at),
+        ))
+        .execute(conn)
+}
+
+pub fn set_package_pipeline(
+    conn: &mut PgConnection,
+    package_id: i64,
+    env: &str,
+    status: &str,
+) -> QueryResult<usize> {
+    let target = || {
+        package_env::table
+            .filter(package_env::parent_id.eq(package_id))
+            .filter(package_env::env.eq(env))
+    };
+    let row = target()
+        .select((
+            package_env::pipeline_status,
+            package_env::pipeline_changed_at,
+            package_env::last_passed_at,
+        ))
+        .first::<PipelineRow>(conn)
+        .optional()?;
+    let timestamps = next_timestamps(row, status);
+
+    diesel::update(target())
+        .set((
+            package_env::pipeline_status.eq(status),
+            package_env::pipeline_changed_at.eq(timestamps.changed_at),
+            package_env::last_passed_at.eq(timestamps.last_passed_at),
+        ))
+        .execute(conn)
+}
+
+pub fn set_dbschema_pipeline(
+    conn: &mut PgConnection,
+    dbschema_id: i64,
+    branch_name: &str,
+    status: &str,
+) -> QueryResult<usize> {
+    let target = || {
+        dbschema_branch::table
+            .filter(dbschema_branch::parent_id.eq(dbschema_id))
+            .filter(dbschema_branch::branch_name.eq(branch_name))
+            .filter(dbschema_branch::deleted_at.is_null())
+    };
+    let row = target()
+        .select((
+            dbschema_branch::pipeline_status,
+            dbschema_branch::pipeline_changed_at,
+            dbschema_branch::last_passed_at,
+        ))
+        .first::<PipelineRow>(conn)
+        .optional()?;
+    let timestamps = next_timestamps(row, status);
+
+    diesel::update(target())
+        .set((
+            dbschema_branch::pipeline_status.eq(status),
+            dbschema_branch::pipeline_changed_at.eq(timestamps.changed_at),
+            dbschema_branch::last_passed_at.eq(timestamps.last_passed_at),
+        ))
+        .execute(conn)
+}
+
+/// Pipeline of one environment of a catalog entry
+#[derive(Debug, Clone)]
+pub struct CatalogPipeline {
+    pub kind: &'static str,
+    pub org_id: String,
+    pub identifier: String,
+    pub env: String,
+    pub status: Option<String>,
+    pub timestamps: PipelineTimestamps,
+}
+
+type CatalogRow = (
+    Option<String>,
+    Option<String>,
+    String,
+    Option<String>,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+);
+
+fn catalog_pipeline(kind: &'static str, row: CatalogRow) -> CatalogPipeline {
+    let (org_id, identifier, env, status, changed_at, last_passed_at) = row;
+    CatalogPipeline {
+        kind,
+        org_id: org_id.unwrap_or_default(),
+        identifier: identifier.unwrap_or_default(),
+        env,
+        status,
+        timestamps: PipelineTimestamps {
+            changed_at,
+            last_passed_at,
+        },
+    }
+}
+
+/// Every live environment of services, packages and schemas in live workspaces
+pub fn catalog_pipelines(conn: &mut PgConnection) -> QueryResult<Vec<CatalogPipeline>> {
+    let live_orgs = || {
+        organization::table
+            .filter(organization::deleted_at.is_null())
+            .select(organization::slug.nullable())
+    };
+
+    let services = service_envs::table
+        .inner_join(service::table)
+        .filter(service::deleted_at.is_null())
+        .filter(service_envs::deleted_at.is_null())
+        .filter(service::organization_id.eq_any(live_orgs()))
+        .select((
+            service::organization_id,
+            service::identifier.nullable(),
+            service_envs::env,
+            service_envs::pipeline_status,
+            service_envs::pipeline_changed_at,
+            service_envs::last_passed_at,
+        ))
+        .load::<CatalogRow>(conn)?;
+
+    let packages = package_env::table
+        .inner_join(package::table)
+        .filter(package::deleted_at.is_null())
+        .filter(package_env::deleted_at.is_null())
+        .filter(package::organization_id.eq_any(live_orgs()))
+        .select((
+            package::organization_id,
+            package::identifier.nullable(),
+            package_env::env,
+            package_env::pipeline_status,
+            package_env::pipeline_changed_at,
+            package_env::last_passed_at,
+        ))
+        .load::<CatalogRow>(conn)?;
+
+    let dbschemas = dbschema_branch::table
+        .inner_join(dbschema::table)
+        .filter(dbschema::deleted_at.is_null())
+        .filter(dbschema_branch::deleted_at.is_null())
+        .filter(dbschema::organization_id.eq_any(live_orgs()))
+        .select((
+            dbschema::organization_id,
+            dbschema::identifier,
+            dbschema_branch::branch_name,
+            dbschema_branch::pipeline_status,
+            dbschema_branch::pipeline_changed_at,
+            dbschema_branch::last_passed_at,
+        ))
+        .load::<CatalogRow>(conn)?;
+
+    Ok(services
+        .into_iter()
+        .map(|row| catalog_pipeline("service", row))
+        .chain(
+            packages
+                .into_iter()
+                .map(|row| catalog_pipeline("package", row)),
+        )
+        .chain(
+            dbschemas
+                .into_iter()
+                .map(|row| catalog_pipeline("schema", row)),
+        )
+        .collect())
+}
@@ -0,0 +1,97 @@
+use crate::errors::ApiError;
+use crate::telemetry;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::PgConnection;
+use rocket::figment::providers::Env;
+use rocket::figment::Figment;
+use rocket::tokio::runtime::{Handle, RuntimeFlavor};
+use rocket::tokio::task::block_in_place;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// Sizing of a connection pool, read from the `pools.<name>` table of `Rocket.toml` and
+/// overridable per key with `<NAME>_POOL_<KEY>` env vars, e.g. `POSTGRES_POOL_MAX_SIZE=20`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    pub max_size: u32,
+    pub min_idle: Option<u32>, // defaults to max_size, like r2d2
+    pub connection_timeout_secs: u64,
+    pub idle_timeout_secs: Option<u64>,
+    pub max_lifetime_secs: Option<u64>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 10,
+            min_idle: None,
+            connection_timeout_secs: 30,
+            idle_timeout_secs: Some(600),
+            max_lifetime_secs: Some(1800),
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn from_figment(figment: &Figment, name: &str) -> Self {
+        let table = format!("pools.{}", name);
+        let nested = table.clone();
+
+        figment
+            .clone()
+            .merge(
+                Env::prefixed(&format!("{}_POOL_", name.to_uppercase()))
+                    .map(move |key| format!("{}.{}", nested, key).into()),
+            )
+            .extract_inner::<PoolConfig>(&table)
+            .unwrap_or_default()
+    }
+
+    pub fn connection_timeout(&self) -> Duration {
+        Duration::from_secs(self.connection_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout_secs.map(Duration::from_secs)
+    }
+
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.max_lifetime_secs.map(Duration::from_secs)
+    }
+}
+
+/// Runs synchronous work (Diesel, Redis) without stalling the executor, the worker thread is
+/// handed to the blocking pool for the duration so other requests keep being served
+pub fn blocking<T>(work: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => block_in_place(work),
+        // The local test client drives Rocket on a current thread runtime, which cannot hand off
+        _ => work(),
+    }
+}
+
+/// Checks out a connection and runs `work` with it through `blocking`, the time spent (checkout
+/// included) is added to the DB time of the current request
+pub fn run_blocking<T>(
+    rdb: &Pool<ConnectionManager<PgConnection>>,
+    work: impl FnOnce(&mut PgConnection) -> Result<T, ApiError>,
+) -> Result<T, ApiError> {
+    let started = Instant::now();
+    let result = blocking(|| {
+        let mut conn = rdb
+            .get()
+            .map_err(|_| ApiError::ServiceUnavailable("Failed to get DB connection".to_string()))?;
+        work(&mut conn)
+    });
+
+    let elapsed = started.elapsed();
+    telemetry::add_db_time(elapsed);
+    tracing::debug!(
+        elapsed_ms = elapsed.as_millis() as u64,
+        ok = result.is_ok(),
+        "db"
+    );
+
+    result
+}
@@ -0,0 +1,45 @@
+use crate::models::quick_link::normalize_stored;
+use crate::models::schema::schema::{dbschema, organization, package, service};
+use diesel::prelude::*;
+
+// Rewrites the `quick_links` of every row of `$table` that is not in the canonical form yet
+macro_rules! normalize_table {
+    ($conn:expr, $table:ident) => {{
+        let rows = $table::table
+            .filter($table::quick_links.is_not_null())
+            .select(($table::id, $table::quick_links))
+            .load::<(i64, Option<String>)>($conn)?;
+
+        let mut rewritten = 0;
+        for (id, stored) in rows {
+            let Some(stored) = stored else { continue };
+            match normalize_stored(&stored) {
+                Some(normalized) if normalized.as_deref() != Some(stored.as_str()) => {
+                    diesel::update($table::table.find(id))
+                        .set($table::quick_links.eq(normalized))
+                        .execute($conn)?;
+                    rewritten += 1;
+                }
+                Some(_) => {}
+                // Left untouched so nothing is lost, it reads as no links
+                None => tracing::warn!(
+                    table = stringify!($table),
+                    id,
+                    "Quick links are not a list of links"
+                ),
+            }
+        }
+        rewritten
+    }};
+}
+
+/// Brings quick links stored as client encoded strings to the typed form, returns the number
+/// of rows rewritten. Rows already in that form are left alone so it can run on every start
+pub fn normalize_all(conn: &mut PgConnection) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        Ok(normalize_table!(conn, dbschema)
+            + normalize_table!(conn, service)
+            + normalize_table!(conn, package)
+            + normalize_table!(conn, organization))
+    })
+}
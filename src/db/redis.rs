@@ -1,19 +1,37 @@
-use r2d2_redis::{r2d2::Pool, RedisConnectionManager};
+use crate::db::pool::{blocking, PoolConfig};
+use r2d2_redis::r2d2::{Pool, PooledConnection};
+use r2d2_redis::redis::{self, Commands};
+use r2d2_redis::RedisConnectionManager;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::env;
+use std::future::Future;
 use std::ops::Deref;
+use std::time::Duration;
 
 // Define a type alias for your Redis connection pool
 type RedisPool = Pool<RedisConnectionManager>;
 
+const DEFAULT_CACHE_TTL_SECONDS: usize = 300;
+// Discovery lookups only change on publish, which invalidates them
+const DEFAULT_DISCOVERY_CACHE_TTL_SECONDS: usize = 24 * 60 * 60;
+// Caching and rate limiting are skipped rather than waited for
+const CHECKOUT_TIMEOUT: Duration = Duration::from_millis(100);
+
 // Function to create and return a Redis connection pool
-pub fn create_redis_pool(redis_url: &str) -> RedisPool {
+pub fn create_redis_pool(redis_url: &str, config: &PoolConfig) -> RedisPool {
     // Create a Redis connection manager
     let manager = RedisConnectionManager::new(redis_url).unwrap();
 
     // Create a pool with the manager
     Pool::builder()
-        .max_size(15) // Set the maximum number of connections in the pool
-        .build(manager)
-        .unwrap()
+        .max_size(config.max_size)
+        .min_idle(config.min_idle)
+        .connection_timeout(config.connection_timeout())
+        .idle_timeout(config.idle_timeout())
+        .max_lifetime(config.max_lifetime())
+        // Connections are opened on demand, an unreachable Redis only disables caching
+        .build_unchecked(manager)
 }
 
 // Rocket State to manage the Redis pool
@@ -28,3 +46,148 @@ impl Deref for RedisPoolState {
         &self.0
     }
 }
+
+fn ttl_from_env(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|ttl| ttl.parse().ok())
+        .unwrap_or(default)
+}
+
+fn cache_ttl() -> usize {
+    ttl_from_env("CACHE_TTL_SECONDS", DEFAULT_CACHE_TTL_SECONDS)
+}
+
+pub fn discovery_cache_ttl() -> usize {
+    ttl_from_env(
+        "DISCOVERY_CACHE_TTL_SECONDS",
+        DEFAULT_DISCOVERY_CACHE_TTL_SECONDS,
+    )
+}
+
+// Bumping the generation orphans every cached entry of the org, they expire on their own
+fn generation_key(org_id: &str) -> String {
+    format!("metadata:cache:{}:generation", org_id)
+}
+
+impl RedisPoolState {
+    /// A connection when one is available shortly, requests do not wait on an unreachable Redis
+    /// for the pool's `connection_timeout`
+    pub fn connection(&self) -> Option<PooledConnection<RedisConnectionManager>> {
+        self.0.get_timeout(CHECKOUT_TIMEOUT).ok()
+    }
+
+    fn entry_key(conn: &mut redis::Connection, org_id: &str, key: &str) -> Option<String> {
+        let generation: Option<u64> = conn.get(generation_key(org_id)).ok()?;
+        Some(format!(
+            "metadata:cache:{}:{}:{}",
+            org_id,
+            generation.unwrap_or(0),
+            key
+        ))
+    }
+
+    // The entry is resolved before the value is loaded, so an invalidation while loading
+    // orphans what gets stored instead of filing stale data under the new generation
+    fn lookup<T: DeserializeOwned>(&self, org_id: &str, key: &str) -> (Option<T>, Option<String>) {
+        blocking(|| {
+            let Some(mut conn) = self.connection() else {
+                return (None, None);
+            };
+            let Some(entry_key) = Self::entry_key(&mut conn, org_id, key) else {
+                return (None, None);
+            };
+            let value = conn.get::<_, Option<String>>(&entry_key).ok().flatten();
+            let hit = value.and_then(|value| serde_json::from_str(&value).ok());
+            (hit, Some(entry_key))
+        })
+    }
+
+    fn store<T: Serialize>(&self, entry_key: &str, value: &T, ttl: usize) {
+        blocking(|| {
+            let (Some(mut conn), Ok(value)) = (self.connection(), serde_json::to_string(value))
+            else {
+                return;
+            };
+            let _: Result<(), _> = conn.set_ex(entry_key, value, ttl);
+        })
+    }
+
+    pub fn invalidate(&self, org_id: &str) {
+        blocking(|| {
+            if let Some(mut conn) = self.connection() {
+                let _: Result<u64, _> = conn.incr(generation_key(org_id), 1);
+            }
+        })
+    }
+}
+
+/// Serves `key` from the cache when Redis is configured and reachable, otherwise runs `load`
+/// and stores its result. Any Redis failure falls back to `load`.
+pub fn cached<T, E, F>(
+    cache: Option<&RedisPoolState>,
+    org_id: &str,
+    key: &str,
+    load: F,
+) -> Result<T, E>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Result<T, E>,
+{
+    cached_for(cache, org_id, key, cache_ttl(), load)
+}
+
+/// Same as `cached` with entries kept for `ttl` seconds
+pub fn cached_for<T, E, F>(
+    cache: Option<&RedisPoolState>,
+    org_id: &str,
+    key: &str,
+    ttl: usize,
+    load: F,
+) -> Result<T, E>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Result<T, E>,
+{
+    let (hit, entry_key) = cache.map_or((None, None), |cache| cache.lookup(org_id, key));
+    if let Some(hit) = hit {
+        return Ok(hit);
+    }
+
+    // Loaders query the database
+    let value = blocking(load)?;
+    if let (Some(cache), Some(entry_key)) = (cache, entry_key) {
+        cache.store(&entry_key, &value, ttl);
+    }
+    Ok(value)
+}
+
+/// Same as `cached` for loaders that need to await, e.g. to resolve blobs
+pub async fn cached_async<T, E, F, Fut>(
+    cache: Option<&RedisPoolState>,
+    org_id: &str,
+    key: &str,
+    load: F,
+) -> Result<T, E>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let (hit, entry_key) = cache.map_or((None, None), |cache| cache.lookup(org_id, key));
+    if let Some(hit) = hit {
+        return Ok(hit);
+    }
+
+    let value = load().await?;
+    if let (Some(cache), Some(entry_key)) = (cache, entry_key) {
+        cache.store(&entry_key, &value, cache_ttl());
+    }
+    Ok(value)
+}
+
+pub fn invalidate(cache: Option<&RedisPoolState>, org_id: &str) {
+    if let Some(cache) = cache {
+        cache.invalidate(org_id);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::models::schema::schema::{
+    api_token, dbschema, dbschema_branch, organization, organization_slug_alias, outbox_event,
+    package, package_env, service, service_envs, snapshots, webhook, webhook_delivery,
+};
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use std::env;
+
+const DEFAULT_DELETED_RETENTION_DAYS: i64 = 30;
+
+/// How long soft deleted rows can be restored before they are purged
+pub fn retention() -> Duration {
+    Duration::days(
+        env::var("DELETED_RETENTION_DAYS")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(DEFAULT_DELETED_RETENTION_DAYS),
+    )
+}
+
+pub fn retention_cutoff() -> DateTime<Utc> {
+    Utc::now() - retention()
+}
+
+// Children are stamped with the same `deleted_at` as their parent, restoring the parent brings
+// back exactly the rows removed with it and leaves the ones deleted on their own
+pub fn delete_service(
+    conn: &mut PgConnection,
+    service_id: i64,
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    diesel::update(service::table.find(service_id))
+        .set(service::deleted_at.eq(Some(at)))
+        .execute(conn)?;
+    diesel::update(
+        service_envs::table
+            .filter(service_envs::parent_id.eq(service_id))
+            .filter(service_envs::deleted_at.is_null()),
+    )
+    .set(service_envs::deleted_at.eq(Some(at)))
+    .execute(conn)?;
+    Ok(())
+}
+
+pub fn restore_service(
+    conn: &mut PgConnection,
+    service_id: i64,
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    diesel::update(service::table.find(service_id))
+        .set(service::deleted_at.eq(None::<DateTime<Utc>>))
+        .execute(conn)?;
+    diesel::update(
+        service_envs::table
+            .filter(service_envs::parent_id.eq(service_id))
+            .filter(service_envs::deleted_at.eq(Some(at))),
+    )
+    .set(service_envs::deleted_at.eq(None::<DateTime<Utc>>))
+    .execute(conn)?;
+    Ok(())
+}
+
+pub fn delete_package(
+    conn: &mut PgConnection,
+    package_id: i64,
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    diesel::update(package::table.find(package_id))
+        .set(package::deleted_at.eq(Some(at)))
+        .execute(conn)?;
+    diesel::update(
+        package_env::table
+            .filter(package_env::parent_id.eq(package_id))
+            .filter(package_env::deleted_at.is_null()),
+    )
+    .set(package_env::deleted_at.eq(Some(at)))
+    .execute(conn)?;
+    Ok(())
+}
+
+pub fn restore_package(
+    conn: &mut PgConnection,
+    package_id: i64,
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    diesel::update(package::table.find(package_id))
+        .set(package::deleted_at.eq(None::<DateTime<Utc>>))
+        .execute(conn)?;
+    diesel::update(
+        package_env::table
+            .filter(package_env::parent_id.eq(package_id))
+            .filter(package_env::deleted_at.eq(Some(at))),
+    )
+    .set(package_env::deleted_at.eq(None::<DateTime<Utc>>))
+    .execute(conn)?;
+    Ok(())
+}
+
+pub fn delete_dbschema(
+    conn: &mut PgConnection,
+    dbschema_id: i64,
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    diesel::update(dbschema::table.find(dbschema_id))
+        .set(dbschema::deleted_at.eq(Some(at)))
+        .execute(conn)?;
+    diesel::update(
+        dbschema_branch::table
+            .filter(dbschema_branch::parent_id.eq(dbschema_id))
+            .filter(dbschema_branch::deleted_at.is_null()),
+    )
+    .set(dbschema_branch::deleted_at.eq(Some(at)))
+    .execute(conn)?;
+    Ok(())
+}
+
+pub fn restore_dbschema(
+    conn: &mut PgConnection,
+    dbschema_id: i64,
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    diesel::update(dbschema::table.find(dbschema_id))
+        .set(dbschema::deleted_at.eq(None::<DateTime<Utc>>))
+        .execute(conn)?;
+    diesel::update(
+        dbschema_branch::table
+            .filter(dbschema_branch::parent_id.eq(dbschema_id))
+            .filter(dbschema_branch::deleted_at.eq(Some(at))),
+    )
+    .set(dbschema_branch::deleted_at.eq(None::<DateTime<Utc>>))
+    .execute(conn)?;
+    Ok(())
+}
+
+/// Deletes the workspace with everything registered in it and revokes its API tokens
+pub fn delete_workspace(
+    conn: &mut PgConnection,
+    org_slug: &str,
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    let service_ids = service::table
+        .filter(service::organization_id.eq(org_slug))
+        .filter(service::deleted_at.is_null())
+        .select(service::id)
+        .load::<i64>(conn)?;
+    for service_id in service_ids {
+        delete_service(conn, service_id, at)?;
+    }
+
+    let package_ids = package::table
+        .filter(package::organization_id.eq(org_slug))
+        .filter(package::deleted_at.is_null())
+        .select(package::id)
+        .load::<i64>(conn)?;
+    for package_id in package_ids {
+        delete_package(conn, package_id, at)?;
+    }
+
+    let dbschema_ids = dbschema::table
+        .filter(dbschema::organization_id.eq(org_slug))
+        .filter(dbschema::deleted_at.is_null())
+        .select(dbschema::id)
+        .load::<i64>(conn)?;
+    for dbschema_id in dbschema_ids {
+        delete_dbschema(conn, dbschema_id, at)?;
+    }
+
+    diesel::update(
+        api_token::table
+            .filter(api_token::organization_id.eq(org_slug))
+            .filter(api_token::revoked_at.is_null()),
+    )
+    .set(api_token::revoked_at.eq(Some(at)))
+    .execute(conn)?;
+
+    diesel::update(organization::table.filter(organization::slug.eq(org_slug)))
+        .set(organization::deleted_at.eq(Some(at)))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Brings back the workspace and what was deleted along with it, revoked API tokens stay revoked
+pub fn restore_workspace(
+    conn: &mut PgConnection,
+    org_slug: &str,
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    let service_ids = service::table
+        .filter(service::organization_id.eq(org_slug))
+        .filter(service::deleted_at.eq(Some(at)))
+        .select(service::id)
+        .load::<i64>(conn)?;
+    for service_id in service_ids {
+        restore_service(conn, service_id, at)?;
+    }
+
+    let package_ids = package::table
+        .filter(package::organization_id.eq(org_slug))
+        .filter(package::deleted_at.eq(Some(at)))
+        .select(package::id)
+        .load::<i64>(conn)?;
+    for package_id in package_ids {
+        restore_package(conn, package_id, at)?;
+    }
+
+    let dbschema_ids = dbschema::table
+        .filter(dbschema::organization_id.eq(org_slug))
+        .filter(dbschema::deleted_at.eq(Some(at)))
+        .select(dbschema::id)
+        .load::<i64>(conn)?;
+    for dbschema_id in dbschema_ids {
+        restore_dbschema(conn, dbschema_id, at)?;
+    }
+
+    diesel::update(organization::table.filter(organization::slug.eq(org_slug)))
+        .set(organization::deleted_at.eq(None::<DateTime<Utc>>))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Workspaces due for purging, deleted before `cutoff` or deactivated past their `purge_after`.
+/// Returns the slug and the IAM group of each.
+pub fn expired_workspaces(
+    conn: &mut PgConnection,
+    cutoff: DateTime<Utc>,
+) -> QueryResult<Vec<(String, String)>> {
+    organization::table
+        .filter(
+            organization::deleted_at
+                .lt(cutoff)
+                .or(organization::purge_after.lt(Utc::now())),
+        )
+        .select((organization::slug, organization::group_id))
+        .load::<(String, String)>(conn)
+}
+
+/// Hard deletes the workspace with everything registered in it, snapshots and API tokens included
+pub fn purge_workspace(conn: &mut PgConnection, org_slug: &str) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let mut purged = 0;
+        purged += diesel::delete(
+            service_envs::table.filter(
+                service_envs::parent_id.eq_any(
+                    service::table
+                        .filter(service::organization_id.eq(org_slug))
+                        .select(service::id),
+                ),
+            ),
+        )
+        .execute(conn)?;
+        purged += diesel::delete(service::table.filter(service::organization_id.eq(org_slug)))
+            .execute(conn)?;
+        purged += diesel::delete(
+            package_env::table.filter(
+                package_env::parent_id.eq_any(
+                    package::table
+                        .filter(package::organization_id.eq(org_slug))
+                        .select(package::id),
+                ),
+            ),
+        )
+        .execute(conn)?;
+        purged += diesel::delete(package::table.filter(package::organization_id.eq(org_slug)))
+            .execute(conn)?;
+        purged += diesel::delete(
+            dbschema_branch::table.filter(
+                dbschema_branch::parent_id.eq_any(
+                    dbschema::table
+                        .filter(dbschema::organization_id.eq(org_slug))
+                        .select(dbschema::id),
+                ),
+            ),
+        )
+        .execute(conn)?;
+        purged += diesel::delete(dbschema::table.filter(dbschema::organization_id.eq(org_slug)))
+            .execute(conn)?;
+        purged += diesel::delete(snapshots::table.filter(snapshots::organization_id.eq(org_slug)))
+            .execute(conn)?;
+        purged += diesel::delete(api_token::table.filter(api_token::organization_id.eq(org_slug)))
+            .execute(conn)?;
+        purged +=
+            diesel::delete(outbox_event::table.filter(outbox_event::organization_id.eq(org_slug)))
+                .execute(conn)?;
+        purged += diesel::delete(
+            webhook_delivery::table.filter(webhook_delivery::organization_id.eq(org_slug)),
+        )
+        .execute(conn)?;
+        purged += diesel::delete(webhook::table.filter(webhook::organization_id.eq(org_slug)))
+            .execute(conn)?;
+        purged += diesel::delete(
+            organization_slug_alias::table.filter(
+                organization_slug_alias::organization_id.eq_any(
+                    organization::table
+                        .filter(organization::slug.eq(org_slug))
+                        .select(organization::id),
+                ),
+            ),
+        )
+        .execute(conn)?;
+        purged += diesel::delete(organization::table.filter(organization::slug.eq(org_slug)))
+            .execute(conn)?;
+        Ok(purged)
+    })
+}
+
+/// Hard deletes rows of live workspaces soft deleted before `cutoff`, children first
+pub fn purge_deleted(conn: &mut PgConnection, cutoff: DateTime<Utc>) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let mut purged = 0;
+
+        // Rows deleted on their own, or whose parent is deleted too
+        purged += diesel::delete(
+            service_envs::table.filter(
+                service_envs::deleted_at
+                    .lt(cutoff)
+                    .or(service_envs::parent_id.eq_any(
+                        service::table
+                            .filter(service::deleted_at.lt(cutoff))
+                            .select(service::id),
+                    )),
+            ),
+        )
+        .execute(conn)?;
+        purged +=
+            diesel::delete(service::table.filter(service::deleted_at.lt(cutoff))).execute(conn)?;
+        purged += diesel::delete(
+            package_env::table.filter(
+                package_env::deleted_at
+                    .lt(cutoff)
+                    .or(package_env::parent_id.eq_any(
+                        package::table
+                            .filter(package::deleted_at.lt(cutoff))
+                            .select(package::id),
+                    )),
+            ),
+        )
+        .execute(conn)?;
+        purged +=
+            diesel::delete(package::table.filter(package::deleted_at.lt(cutoff))).execute(conn)?;
+        purged += diesel::delete(
+            dbschema_branch::table.filter(
+                dbschema_branch::deleted_at
+                    .lt(cutoff)
+                    .or(dbschema_branch::parent_id.eq_any(
+                        dbschema::table
+                            .filter(dbschema::deleted_at.lt(cutoff))
+                            .select(dbschema::id),
+                    )),
+            ),
+        )
+        .execute(conn)?;
+        purged += diesel::delete(dbschema::table.filter(dbschema::deleted_at.lt(cutoff)))
+            .execute(conn)?;
+        // Delivered events are only kept for inspection, dead letters stay until retried
+        purged += diesel::delete(outbox_event::table.filter(outbox_event::delivered_at.lt(cutoff)))
+            .execute(conn)?;
+        // The delivery log keeps failures and recent deliveries of live webhooks
+        purged += diesel::delete(
+            webhook_delivery::table.filter(
+                webhook_delivery::delivered_at
+                    .lt(cutoff)
+                    .or(webhook_delivery::webhook_id.eq_any(
+                        webhook::table
+                            .filter(webhook::deleted_at.lt(cutoff))
+                            .select(webhook::id),
+                    )),
+            ),
+        )
+        .execute(conn)?;
+        purged +=
+            diesel::delete(webhook::table.filter(webhook::deleted_at.lt(cutoff))).execute(conn)?;
+
+        Ok(purged)
+    })
+}
@@ -0,0 +1,176 @@
+use crate::db::outbox::{lease_until, FailedAttempt};
+use crate::models::schema::schema::{webhook, webhook_delivery};
+use crate::models::schema::{Webhook, Webhook_Delivery, Webhook_DeliveryInsertable};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde_json::{json, Value};
+
+pub const SERVICE_SPEC_CHANGED: &str = "service.spec_changed";
+pub const PACKAGE_VERSION_PUBLISHED: &str = "package.version_published";
+pub const PIPELINE_FAILED: &str = "pipeline.failed";
+pub const SNAPSHOT_CREATED: &str = "snapshot.created";
+pub const SCHEMA_BRANCH_MERGED: &str = "schema.branch_merged";
+
+pub const EVENT_TYPES: [&str; 5] = [
+    SERVICE_SPEC_CHANGED,
+    PACKAGE_VERSION_PUBLISHED,
+    PIPELINE_FAILED,
+    SNAPSHOT_CREATED,
+    SCHEMA_BRANCH_MERGED,
+];
+
+// Delivery attempts before a delivery is marked as failed
+pub const MAX_ATTEMPTS: i32 = 8;
+
+pub fn subscribed_events(hook: &Webhook) -> Vec<String> {
+    serde_json::from_str(&hook.event_types_json).unwrap_or_default()
+}
+
+/// Queues a delivery for every webhook of the workspace subscribed to `event_type`, to be
+/// called in the transaction of the change it describes
+pub fn enqueue(
+    conn: &mut PgConnection,
+    org_id: &str,
+    event_type: &str,
+    data: Value,
+) -> QueryResult<()> {
+    let now = Utc::now();
+    let body = json!({
+        "event": event_type,
+        "org_id": org_id,
+        "occurred_at": now,
+        "data": data,
+    })
+    .to_string();
+
+    let deliveries: Vec<Webhook_DeliveryInsertable> = webhook::table
+        .filter(webhook::organization_id.eq(org_id))
+        .filter(webhook::deleted_at.is_null())
+        .load::<Webhook>(conn)?
+        .into_iter()
+        .filter(|hook| subscribed_events(hook).iter().any(|e| e == event_type))
+        .map(|hook| Webhook_DeliveryInsertable {
+            webhook_id: hook.id,
+            organization_id: org_id.to_string(),
+            event_type: event_type.to_string(),
+            payload: body.clone(),
+            attempts: 0,
+            next_attempt_at: now,
+            response_status: None,
+            last_error: None,
+            created_at: now,
+            delivered_at: None,
+            failed_at: None,
+        })
+        .collect();
+
+    if !deliveries.is_empty() {
+        diesel::insert_into(webhook_delivery::table)
+            .values(&deliveries)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Takes up to `limit` due deliveries of live webhooks along with the webhook to send them to
+pub fn claim_due(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> QueryResult<Vec<(Webhook_Delivery, Webhook)>> {
+    conn.transaction(|conn| {
+        let now = Utc::now();
+        let deliveries = webhook_delivery::table
+            .filter(webhook_delivery::delivered_at.is_null())
+            .filter(webhook_delivery::failed_at.is_null())
+            .filter(webhook_delivery::next_attempt_at.le(now))
+            .filter(
+                webhook_delivery::webhook_id.eq_any(
+                    webhook::table
+                        .filter(webhook::deleted_at.is_null())
+                        .select(webhook::id),
+                ),
+            )
+            .order(webhook_delivery::id.asc())
+            .limit(limit)
+            .for_update()
+            .skip_locked()
+            .load::<Webhook_Delivery>(conn)?;
+
+        let ids: Vec<i64> = deliveries.iter().map(|delivery| delivery.id).collect();
+        diesel::update(webhook_delivery::table.filter(webhook_delivery::id.eq_any(&ids)))
+            .set(webhook_delivery::next_attempt_at.eq(lease_until(now)))
+            .execute(conn)?;
+
+        let webhook_ids: Vec<i64> = deliveries
+            .iter()
+            .map(|delivery| delivery.webhook_id)
+            .collect();
+        let hooks = webhook::table
+            .filter(webhook::id.eq_any(&webhook_ids))
+            .load::<Webhook>(conn)?;
+
+        Ok(deliveries
+            .into_iter()
+            .filter_map(|delivery| {
+                let hook = hooks.iter().find(|hook| hook.id == delivery.webhook_id)?;
+                Some((delivery, hook.clone()))
+            })
+            .collect())
+    })
+}
+
+pub fn mark_delivered(conn: &mut PgConnection, delivery_id: i64, status: i32) -> QueryResult<()> {
+    diesel::update(webhook_delivery::table.find(delivery_id))
+        .set((
+            webhook_delivery::delivered_at.eq(Some(Utc::now())),
+            webhook_delivery::attempts.eq(webhook_delivery::attempts + 1),
+            webhook_delivery::response_status.eq(Some(status)),
+            webhook_delivery::last_error.eq(None::<String>),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Schedules the next attempt, or marks the delivery as failed once it ran out of attempts
+pub fn mark_failed(
+    conn: &mut PgConnection,
+    delivery: &Webhook_Delivery,
+    status: Option<i32>,
+    error: &str,
+    now: DateTime<Utc>,
+) -> QueryResult<bool> {
+    let failed = FailedAttempt::new(delivery.attempts, MAX_ATTEMPTS, error, now);
+
+    diesel::update(webhook_delivery::table.find(delivery.id))
+        .set((
+            webhook_delivery::attempts.eq(failed.attempts),
+            webhook_delivery::response_status.eq(status),
+            webhook_delivery::last_error.eq(Some(failed.error)),
+            webhook_delivery::next_attempt_at.eq(failed.next_attempt_at),
+            webhook_delivery::failed_at.eq(failed.given_up_at),
+        ))
+        .execute(conn)?;
+    Ok(failed.given_up_at.is_some())
+}
+
+/// Sends a delivery of the log again, whether it went through or failed
+pub fn redeliver(
+    conn: &mut PgConnection,
+    org_id: &str,
+    webhook_id: i64,
+    delivery_id: i64,
+) -> QueryResult<usize> {
+    diesel::update(
+        webhook_delivery::table
+            .find(delivery_id)
+            .filter(webhook_delivery::webhook_id.eq(webhook_id))
+            .filter(webhook_delivery::organization_id.eq(org_id)),
+    )
+    .set((
+        webhook_delivery::attempts.eq(0),
+        webhook_delivery::next_attempt_at.eq(Utc::now()),
+        webhook_delivery::delivered_at.eq(None::<DateTime<Utc>>),
+        webhook_delivery::failed_at.eq(None::<DateTime<Utc>>),
+    ))
+    .execute(conn)
+}
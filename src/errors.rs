@@ -0,0 +1,206 @@
+use okapi::openapi3::{MediaType, RefOr, Response as OpenApiResponse, Responses};
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder};
+use rocket::{Request, Response};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::response::OpenApiResponderInner;
+use rocket_okapi::JsonSchema;
+use serde::Serialize;
+use std::io::Cursor;
+
+/// Every handler error, rendered as an RFC 7807 `application/problem+json` document
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    PreconditionFailed(String),
+    PayloadTooLarge(String),
+    UnprocessableEntity(String),
+    PreconditionRequired(String),
+    TooManyRequests(String),
+    Internal(String),
+    Upstream(String),
+    ServiceUnavailable(String),
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub instance: Option<String>,
+    pub code: String, // machine readable, stable across releases
+}
+
+impl ApiError {
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::Unauthorized(_) => Status::Unauthorized,
+            ApiError::Forbidden(_) => Status::Forbidden,
+            ApiError::NotFound(_) => Status::NotFound,
+            ApiError::Conflict(_) => Status::Conflict,
+            ApiError::PreconditionFailed(_) => Status::PreconditionFailed,
+            ApiError::PayloadTooLarge(_) => Status::PayloadTooLarge,
+            ApiError::UnprocessableEntity(_) => Status::UnprocessableEntity,
+            ApiError::PreconditionRequired(_) => Status::PreconditionRequired,
+            ApiError::TooManyRequests(_) => Status::TooManyRequests,
+            ApiError::Internal(_) => Status::InternalServerError,
+            ApiError::Upstream(_) => Status::BadGateway,
+            ApiError::ServiceUnavailable(_) => Status::ServiceUnavailable,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PreconditionFailed(_) => "precondition_failed",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnprocessableEntity(_) => "unprocessable_entity",
+            ApiError::PreconditionRequired(_) => "precondition_required",
+            ApiError::TooManyRequests(_) => "too_many_requests",
+            ApiError::Internal(_) => "internal_error",
+            ApiError::Upstream(_) => "upstream_error",
+            ApiError::ServiceUnavailable(_) => "service_unavailable",
+        }
+    }
+
+    pub fn detail(&self) -> &str {
+        match self {
+            ApiError::BadRequest(detail)
+            | ApiError::Unauthorized(detail)
+            | ApiError::Forbidden(detail)
+            | ApiError::NotFound(detail)
+            | ApiError::Conflict(detail)
+            | ApiError::PreconditionFailed(detail)
+            | ApiError::PayloadTooLarge(detail)
+            | ApiError::UnprocessableEntity(detail)
+            | ApiError::PreconditionRequired(detail)
+            | ApiError::TooManyRequests(detail)
+            | ApiError::Internal(detail)
+            | ApiError::Upstream(detail)
+            | ApiError::ServiceUnavailable(detail) => detail,
+        }
+    }
+
+    pub fn problem(&self, instance: Option<String>) -> ProblemDetails {
+        let status = self.status();
+        ProblemDetails {
+            problem_type: format!("/metadata/problems/{}", self.code()),
+            title: status.reason_lossy().to_string(),
+            status: status.code,
+            detail: self.detail().to_string(),
+            instance,
+            code: self.code().to_string(),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(error: diesel::result::Error) -> Self {
+        match error {
+            diesel::result::Error::NotFound => ApiError::NotFound("Record not found".to_string()),
+            _ => ApiError::Internal("Database error".to_string()),
+        }
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for ApiError {
+    fn from(_: diesel::r2d2::PoolError) -> Self {
+        ApiError::ServiceUnavailable("Failed to get DB connection".to_string())
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let body = serde_json::to_string(&self.problem(Some(request.uri().path().to_string())))
+            .map_err(|_| Status::InternalServerError)?;
+
+        Response::build()
+            .status(self.status())
+            .header(ContentType::new("application", "problem+json"))
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+impl OpenApiResponderInner for ApiError {
+    fn responses(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let schema = gen.json_schema::<ProblemDetails>();
+        let mut responses = Responses::default();
+
+        for (status, description) in [
+            ("400", "The request is malformed"),
+            ("401", "Missing or invalid credentials"),
+            ("403", "The caller is not allowed to perform this action"),
+            ("404", "The resource does not exist"),
+            ("409", "The resource conflicts with an existing one"),
+            ("412", "The resource was modified since it was last read"),
+            ("413", "The document is too large to be stored"),
+            ("422", "The request body failed validation"),
+            ("428", "The write requires an If-Match header"),
+            ("429", "Rate limit exceeded"),
+            ("500", "Unexpected server error"),
+            ("502", "A dependent service failed"),
+            ("503", "A backing store is unavailable"),
+        ] {
+            let mut content = okapi::Map::new();
+            content.insert(
+                "application/problem+json".to_string(),
+                MediaType {
+                    schema: Some(schema.clone()),
+                    ..Default::default()
+                },
+            );
+            responses.responses.insert(
+                status.to_string(),
+                RefOr::Object(OpenApiResponse {
+                    description: description.to_string(),
+                    content,
+                    ..Default::default()
+                }),
+            );
+        }
+
+        Ok(responses)
+    }
+}
+
+#[catch(400)]
+pub fn bad_request() -> ApiError {
+    ApiError::BadRequest("The request is malformed".to_string())
+}
+
+#[catch(401)]
+pub fn unauthorized() -> ApiError {
+    ApiError::Unauthorized("Missing or invalid credentials".to_string())
+}
+
+#[catch(403)]
+pub fn forbidden() -> ApiError {
+    ApiError::Forbidden("You are not allowed to access this resource".to_string())
+}
+
+#[catch(404)]
+pub fn not_found(request: &Request<'_>) -> ApiError {
+    ApiError::NotFound(format!("No route matches {}", request.uri().path()))
+}
+
+#[catch(422)]
+pub fn unprocessable_entity() -> ApiError {
+    ApiError::UnprocessableEntity("The request body could not be parsed".to_string())
+}
+
+#[catch(500)]
+pub fn internal_error() -> ApiError {
+    ApiError::Internal("Unexpected server error".to_string())
+}
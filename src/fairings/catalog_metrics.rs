@@ -0,0 +1,56 @@
+use crate::db::pipeline::catalog_pipelines;
+use crate::metrics::metrics;
+use chrono::Utc;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::PgConnection;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::task::spawn_blocking;
+use rocket::tokio::time::{interval, Duration};
+use rocket::{Orbit, Rocket};
+
+// Alerts on pipeline age are only as fresh as this
+const REFRESH_INTERVAL_SECS: u64 = 60;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// Periodically recomputes the catalog gauges of `metrics::DomainMetrics` from the database
+pub struct CatalogMetrics;
+
+async fn refresh(pool: &DbPool) -> Result<(), String> {
+    let pool = pool.clone();
+    let pipelines = spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        catalog_pipelines(&mut conn).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    metrics().refresh(&pipelines, Utc::now());
+    Ok(())
+}
+
+#[rocket::async_trait]
+impl Fairing for CatalogMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Refresh catalog metrics",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(pool) = rocket.state::<DbPool>().cloned() else {
+            return;
+        };
+
+        rocket::tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(REFRESH_INTERVAL_SECS));
+            loop {
+                ticker.tick().await;
+                if let Err(error) = refresh(&pool).await {
+                    tracing::error!(%error, "Failed to refresh catalog metrics");
+                }
+            }
+        });
+    }
+}
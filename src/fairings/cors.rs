@@ -21,6 +21,11 @@ impl Fairing for CORS {
         ));
         response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
         response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        // Lets the portal read revisions for If-Match and the rate limit state
+        response.set_header(Header::new(
+            "Access-Control-Expose-Headers",
+            "ETag, RateLimit-Limit, RateLimit-Remaining, RateLimit-Reset, Retry-After",
+        ));
 
         if request.method() == rocket::http::Method::Options {
             response.set_status(rocket::http::Status::Ok);
@@ -1 +1,8 @@
+pub mod catalog_metrics;
 pub mod cors;
+pub mod outbox;
+pub mod purge;
+pub mod rate_limit;
+pub mod request_trace;
+pub mod slug_alias;
+pub mod webhooks;
@@ -0,0 +1,166 @@
+use crate::db::outbox::{claim_due, mark_delivered, mark_failed};
+use crate::metrics::metrics;
+use crate::middlewares::NotificationService_api_config::service_configuration;
+use crate::models::schema::Outbox_Event;
+use chrono::Utc;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::PgConnection;
+use ginger_shared_rs::rocket_models::RealtimeMessage;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::task::spawn_blocking;
+use rocket::tokio::time::{interval, Duration};
+use rocket::{Orbit, Rocket};
+use std::future::Future;
+use std::sync::Arc;
+use NotificationService::apis::configuration::Configuration;
+use NotificationService::apis::default_api::{
+    publish_message_to_group_api_land, PublishMessageToGroupApiLandParams,
+};
+use NotificationService::models::{PublishRequest, PublishType};
+
+const POLL_INTERVAL_SECS: u64 = 5;
+pub(crate) const BATCH_SIZE: i64 = 50;
+
+pub(crate) type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// Delivers the events of `outbox_event` to NotificationService, retrying failures with
+/// backoff until they are dead lettered
+pub struct OutboxRelay;
+
+async fn publish(configuration: &Configuration, event: &Outbox_Event) -> Result<(), String> {
+    let message = RealtimeMessage {
+        topic: event.topic.clone(),
+        payload: event.payload.clone(),
+    };
+
+    publish_message_to_group_api_land(
+        configuration,
+        PublishMessageToGroupApiLandParams {
+            group_id: event.group_id.clone(),
+            publish_request: PublishRequest {
+                message: message.to_string(),
+                prefix: "dev_portal".to_string(),
+                pub_type: PublishType::Members,
+            },
+        },
+    )
+    .await
+    .map(|_| ())
+    .map_err(|e| format!("{:?}", e))
+}
+
+/// Runs `work` on the blocking pool with a connection of `pool`
+pub(crate) async fn with_connection<T, W>(pool: &DbPool, work: W) -> Result<T, String>
+where
+    T: Send + 'static,
+    W: FnOnce(&mut PgConnection) -> Result<T, String> + Send + 'static,
+{
+    let pool = pool.clone();
+    spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        work(&mut conn)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Calls `drain` every `POLL_INTERVAL_SECS`, and again right away while it claims full batches.
+/// The webhook dispatcher polls its deliveries the same way
+pub(crate) fn spawn_poller<F, Fut>(task: &'static str, mut drain: F)
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<usize, String>> + Send,
+{
+    rocket::tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(POLL_INTERVAL_SECS));
+        loop {
+            ticker.tick().await;
+            // A full batch means more is due, keep going without waiting for the next tick
+            loop {
+                match drain().await {
+                    Ok(claimed) if claimed as i64 == BATCH_SIZE => continue,
+                    Ok(_) => break,
+                    Err(error) => {
+                        tracing::error!(%error, "Failed to {}", task);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+async fn relay(pool: &DbPool, configuration: &Configuration) -> Result<usize, String> {
+    let events = with_connection(pool, |conn| {
+        claim_due(conn, BATCH_SIZE).map_err(|e| e.to_string())
+    })
+    .await?;
+    let claimed = events.len();
+
+    for event in events {
+        let outcome = publish(configuration, &event).await;
+        if outcome.is_err() {
+            metrics()
+                .notification_failures
+                .with_label_values(&[event.topic.as_str()])
+                .inc();
+        }
+
+        with_connection(pool, move |conn| match outcome {
+            Ok(()) => mark_delivered(conn, event.id).map_err(|e| e.to_string()),
+            Err(error) => {
+                let dead =
+                    mark_failed(conn, &event, &error, Utc::now()).map_err(|e| e.to_string())?;
+                if dead {
+                    tracing::error!(
+                        event_id = event.id,
+                        topic = %event.topic,
+                        %error,
+                        "Outbox event dead lettered"
+                    );
+                } else {
+                    tracing::warn!(
+                        event_id = event.id,
+                        topic = %event.topic,
+                        %error,
+                        "Outbox delivery failed, will retry"
+                    );
+                }
+                Ok(())
+            }
+        })
+        .await?;
+    }
+
+    Ok(claimed)
+}
+
+#[rocket::async_trait]
+impl Fairing for OutboxRelay {
+    fn info(&self) -> Info {
+        Info {
+            name: "Relay outbox events to NotificationService",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(pool) = rocket.state::<DbPool>().cloned() else {
+            return;
+        };
+        // Events keep accumulating and go out once the key is configured
+        let Some(configuration) = service_configuration() else {
+            tracing::warn!(
+                "NOTIFICATION_SERVICE_API_KEY is not set, outbox events are not delivered"
+            );
+            return;
+        };
+
+        let configuration = Arc::new(configuration);
+        spawn_poller("relay outbox events", move || {
+            let pool = pool.clone();
+            let configuration = configuration.clone();
+            async move { relay(&pool, &configuration).await }
+        });
+    }
+}
@@ -0,0 +1,145 @@
+use crate::db::blobs::{collect_unreferenced, referenced_blobs};
+use crate::db::soft_delete::{
+    expired_workspaces, purge_deleted, purge_workspace, retention_cutoff,
+};
+use crate::metrics::iam_call;
+use crate::middlewares::IAMService_config::service_configuration;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::PgConnection;
+use mongodb::bson::DateTime as BsonDateTime;
+use mongodb::Database;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::task::spawn_blocking;
+use rocket::tokio::time::{interval, Duration};
+use rocket::{Orbit, Rocket};
+use IAMService::apis::default_api::{identity_delete_group, IdentityDeleteGroupParams};
+
+const PURGE_INTERVAL_SECS: u64 = 60 * 60;
+// Blobs are written before the row referring to them is committed
+const BLOB_GRACE_MILLIS: i64 = 60 * 60 * 1000;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// Hard deletes soft deleted rows once they are past the retention window, and workspaces
+/// that were deleted or deactivated long enough along with their IAM group. Blobs left without
+/// a row referring to them are collected afterwards
+pub struct DeletedPurger;
+
+async fn purge_expired_workspaces(pool: &DbPool) -> Result<(), String> {
+    let lookup_pool = pool.clone();
+    let expired = spawn_blocking(move || {
+        let mut conn = lookup_pool.get().map_err(|e| e.to_string())?;
+        expired_workspaces(&mut conn, retention_cutoff()).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let iam_config = service_configuration();
+    if !expired.is_empty() && iam_config.is_none() {
+        tracing::warn!("IAM_SERVICE_API_KEY is not set, IAM groups of purged workspaces are kept");
+    }
+
+    for (org_slug, group_id) in expired {
+        let purge_pool = pool.clone();
+        let slug = org_slug.clone();
+        let purged = spawn_blocking(move || {
+            let mut conn = purge_pool.get().map_err(|e| e.to_string())?;
+            purge_workspace(&mut conn, &slug).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        tracing::info!(org_id = %org_slug, rows = purged, "Purged workspace");
+
+        // The rows are gone at this point, a failure here only leaves an orphaned group behind
+        if let Some(config) = &iam_config {
+            if let Err(error) = iam_call(
+                "delete_group",
+                identity_delete_group(
+                    config,
+                    IdentityDeleteGroupParams {
+                        group_id: group_id.clone(),
+                    },
+                ),
+            )
+            .await
+            {
+                tracing::error!(
+                    group_id = %group_id,
+                    org_id = %org_slug,
+                    ?error,
+                    "Failed to delete IAM group of purged workspace"
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Blobs are shared by content, so they are collected once no row refers to them anymore
+async fn collect_blobs(pool: &DbPool, blobs: &Database) -> Result<u64, String> {
+    let written_before =
+        BsonDateTime::from_millis(BsonDateTime::now().timestamp_millis() - BLOB_GRACE_MILLIS);
+
+    let pool = pool.clone();
+    let referenced = spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        referenced_blobs(&mut conn).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    collect_unreferenced(blobs, &referenced, written_before)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn purge(pool: &DbPool, blobs: Option<&Database>) -> Result<usize, String> {
+    purge_expired_workspaces(pool).await?;
+
+    let purge_pool = pool.clone();
+    let purged = spawn_blocking(move || {
+        let mut conn = purge_pool.get().map_err(|e| e.to_string())?;
+        purge_deleted(&mut conn, retention_cutoff()).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    if let Some(blobs) = blobs {
+        match collect_blobs(pool, blobs).await {
+            Ok(0) => {}
+            Ok(collected) => tracing::info!(collected, "Collected unreferenced blobs"),
+            Err(error) => tracing::error!(%error, "Failed to collect unreferenced blobs"),
+        }
+    }
+    Ok(purged)
+}
+
+#[rocket::async_trait]
+impl Fairing for DeletedPurger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Purge soft deleted rows and expired workspaces",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(pool) = rocket.state::<DbPool>().cloned() else {
+            return;
+        };
+        let blobs = rocket.state::<Database>().cloned();
+
+        rocket::tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(PURGE_INTERVAL_SECS));
+            loop {
+                ticker.tick().await;
+                match purge(&pool, blobs.as_ref()).await {
+                    Ok(0) => {}
+                    Ok(rows) => tracing::info!(rows, "Purged soft deleted rows"),
+                    Err(error) => tracing::error!(%error, "Failed to purge soft deleted rows"),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,251 @@
+use crate::db::pool::blocking;
+use crate::db::redis::RedisPoolState;
+use crate::errors::ApiError;
+use chrono::Utc;
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use r2d2_redis::redis;
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::{Header, Method};
+use rocket::{Build, Data, Request, Response, Rocket};
+use serde::Deserialize;
+use std::env;
+use uuid::Uuid;
+
+// Requests over the limit are rerouted here so the original handler never runs
+const RATE_LIMITED_PATH: &str = "/metadata/rate-limited";
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RateLimit {
+    pub requests: u64,
+    pub window_secs: u64,
+}
+
+/// Limits per route group, read from the `rate_limits` table of `Rocket.toml`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub public: RateLimit,
+    pub api: RateLimit,
+    pub user: RateLimit,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            public: RateLimit {
+                requests: 60,
+                window_secs: 60,
+            },
+            api: RateLimit {
+                requests: 600,
+                window_secs: 60,
+            },
+            user: RateLimit {
+                requests: 300,
+                window_secs: 60,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RateLimitDecision {
+    limit: u64,
+    remaining: u64,
+    reset_secs: u64,
+    limited: bool,
+}
+
+#[derive(Deserialize)]
+struct Subject {
+    sub: String,
+}
+
+/// Subject of a token signed with `secret`, the same check `APIClaims` and `Claims` do. A
+/// forged or expired token has none
+pub(crate) fn token_subject(token: &str, secret: &[u8]) -> Option<String> {
+    decode::<Subject>(
+        token.trim_start_matches("Bearer ").trim(),
+        &DecodingKey::from_secret(secret),
+        &Validation::default(),
+    )
+    .ok()
+    .map(|data| data.claims.sub)
+}
+
+/// Used to pick the bucket and label traces, before the route guards run
+pub(crate) fn verified_subject(token: &str) -> Option<String> {
+    let secret = env::var("JWT_SECRET").ok()?;
+    token_subject(token, secret.as_bytes())
+}
+
+// Unverified tokens count against the client IP, otherwise a made up `sub` per request would
+// get a fresh quota each time
+pub(crate) fn bucket_for(
+    path: &str,
+    client_ip: &str,
+    api_token: Option<&str>,
+    user_token: Option<&str>,
+    secret: Option<&[u8]>,
+    config: &RateLimitConfig,
+) -> (String, RateLimit) {
+    if path.starts_with("/metadata/public/") {
+        return (format!("public:{}", client_ip), config.public);
+    }
+
+    let subject = |token: Option<&str>| token_subject(token?, secret?);
+    if let Some(sub) = subject(api_token) {
+        return (format!("api:{}", sub), config.api);
+    }
+    if let Some(sub) = subject(user_token) {
+        return (format!("user:{}", sub), config.user);
+    }
+
+    (format!("public:{}", client_ip), config.public)
+}
+
+fn bucket(request: &Request<'_>, config: &RateLimitConfig) -> (String, RateLimit) {
+    let client_ip = request
+        .client_ip()
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let secret = env::var("JWT_SECRET").ok();
+
+    bucket_for(
+        request.uri().path().as_str(),
+        &client_ip,
+        request.headers().get_one("X-API-Authorization"),
+        request.headers().get_one("Authorization"),
+        secret.as_deref().map(str::as_bytes),
+        config,
+    )
+}
+
+fn is_exempt(request: &Request<'_>) -> bool {
+    let path = request.uri().path();
+    request.method() == Method::Options
+        || path.starts_with("/metadata/health")
+        || path.starts_with("/metadata/metrics")
+        || path.starts_with("/metadata/api-docs")
+}
+
+/// Sliding window log kept in a sorted set, scored by the request time in milliseconds
+fn check(cache: &RedisPoolState, key: &str, rate_limit: RateLimit) -> Option<RateLimitDecision> {
+    let mut conn = cache.connection()?;
+
+    let now_ms = Utc::now().timestamp_millis();
+    let window_ms = (rate_limit.window_secs * 1000) as i64;
+    let redis_key = format!("metadata:ratelimit:{}", key);
+
+    let (count, oldest): (u64, Vec<(String, f64)>) = redis::pipe()
+        .atomic()
+        .cmd("ZREMRANGEBYSCORE")
+        .arg(&redis_key)
+        .arg(0)
+        .arg(now_ms - window_ms)
+        .ignore()
+        .cmd("ZADD")
+        .arg(&redis_key)
+        .arg(now_ms)
+        .arg(format!("{}-{}", now_ms, Uuid::new_v4()))
+        .ignore()
+        .cmd("ZCARD")
+        .arg(&redis_key)
+        .cmd("ZRANGE")
+        .arg(&redis_key)
+        .arg(0)
+        .arg(0)
+        .arg("WITHSCORES")
+        .cmd("PEXPIRE")
+        .arg(&redis_key)
+        .arg(window_ms)
+        .ignore()
+        .query(&mut *conn)
+        .ok()?;
+
+    let window_start = oldest
+        .first()
+        .map(|(_, score)| *score as i64)
+        .unwrap_or(now_ms);
+    let reset_ms = (window_start + window_ms - now_ms).max(0) as u64;
+
+    Some(RateLimitDecision {
+        limit: rate_limit.requests,
+        remaining: rate_limit.requests.saturating_sub(count),
+        reset_secs: (reset_ms + 999) / 1000,
+        limited: count > rate_limit.requests,
+    })
+}
+
+pub struct RateLimiter;
+
+#[rocket::async_trait]
+impl Fairing for RateLimiter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Redis sliding window rate limiter",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = rocket
+            .figment()
+            .extract_inner::<RateLimitConfig>("rate_limits")
+            .unwrap_or_default();
+
+        Ok(rocket
+            .manage(config)
+            .mount("/metadata", routes![rate_limited]))
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        if is_exempt(request) {
+            return;
+        }
+
+        // Without Redis there is nothing to count against, requests go through unlimited
+        let (Some(cache), Some(config)) = (
+            request.rocket().state::<RedisPoolState>(),
+            request.rocket().state::<RateLimitConfig>(),
+        ) else {
+            return;
+        };
+
+        let (key, rate_limit) = bucket(request, config);
+        let decision = blocking(|| check(cache, &key, rate_limit));
+
+        if let Some(RateLimitDecision { limited: true, .. }) = decision {
+            request.set_method(Method::Get);
+            request.set_uri(Origin::parse(RATE_LIMITED_PATH).unwrap());
+        }
+
+        request.local_cache(|| decision);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(decision) = request.local_cache(|| None::<RateLimitDecision>) else {
+            return;
+        };
+
+        response.set_header(Header::new("RateLimit-Limit", decision.limit.to_string()));
+        response.set_header(Header::new(
+            "RateLimit-Remaining",
+            decision.remaining.to_string(),
+        ));
+        response.set_header(Header::new(
+            "RateLimit-Reset",
+            decision.reset_secs.to_string(),
+        ));
+
+        if decision.limited {
+            response.set_header(Header::new("Retry-After", decision.reset_secs.to_string()));
+        }
+    }
+}
+
+#[get("/rate-limited")]
+fn rate_limited() -> ApiError {
+    ApiError::TooManyRequests("Too many requests".to_string())
+}
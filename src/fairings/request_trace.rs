@@ -0,0 +1,187 @@
+use crate::fairings::rate_limit::verified_subject;
+use crate::telemetry;
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, HeaderMap};
+use rocket::route::{Handler, Outcome, Route};
+use rocket::{Data, Request, Response};
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{info_span, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Span of the request being served, opened before any other fairing runs
+struct RequestSpan {
+    span: Span,
+    request_id: String,
+    started: Instant,
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap<'a>);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get_one(key)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.iter().map(|header| header.name().as_str()).collect()
+    }
+}
+
+// Ids from upstream proxies are kept so logs line up across services
+fn request_id(request: &Request<'_>) -> String {
+    request
+        .headers()
+        .get_one(REQUEST_ID_HEADER)
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+fn principal(request: &Request<'_>) -> String {
+    let subject = |header: &str, kind: &str| {
+        request
+            .headers()
+            .get_one(header)
+            .and_then(verified_subject)
+            .map(|sub| format!("{}:{}", kind, sub))
+    };
+
+    subject("X-API-Authorization", "api")
+        .or_else(|| subject("Authorization", "user"))
+        .unwrap_or_else(|| "anonymous".to_string())
+}
+
+// Value of the `<org_id>` segment of the matched route, if it has one
+fn org_id(request: &Request<'_>, route: &Route) -> Option<String> {
+    route
+        .uri
+        .path()
+        .as_str()
+        .split('/')
+        .zip(request.uri().path().as_str().split('/'))
+        .find(|(pattern, _)| *pattern == "<org_id>")
+        .map(|(_, value)| value.to_string())
+}
+
+fn request_span(request: &Request<'_>) -> &RequestSpan {
+    request.local_cache(|| RequestSpan {
+        span: Span::none(),
+        request_id: String::new(),
+        started: Instant::now(),
+    })
+}
+
+/// Opens a span per request carrying its id, principal, route, org, status and timings. The
+/// parent is taken from an incoming W3C `traceparent` header when there is one
+pub struct RequestTracer;
+
+#[rocket::async_trait]
+impl Fairing for RequestTracer {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request tracing",
+            kind: Kind::Request | Kind::Response | Kind::Shutdown,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let request_id = request_id(request);
+        let span = info_span!(
+            "request",
+            request_id = %request_id,
+            method = %request.method(),
+            path = %request.uri().path(),
+            principal = %principal(request),
+            route = Empty,
+            org_id = Empty,
+            status = Empty,
+            latency_ms = Empty,
+            db_ms = Empty,
+        );
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(request.headers()))
+        });
+        span.set_parent(parent);
+
+        request.local_cache(|| RequestSpan {
+            span,
+            request_id,
+            started: Instant::now(),
+        });
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let RequestSpan {
+            span,
+            request_id,
+            started,
+        } = request_span(request);
+
+        span.record("status", response.status().code);
+        span.record("latency_ms", started.elapsed().as_millis() as u64);
+        span.in_scope(|| {
+            if response.status().code >= 500 {
+                tracing::error!("request failed");
+            } else {
+                tracing::info!("request completed");
+            }
+        });
+
+        if !request_id.is_empty() {
+            response.set_header(Header::new(REQUEST_ID_HEADER, request_id.clone()));
+        }
+    }
+
+    async fn on_shutdown(&self, _: &rocket::Rocket<rocket::Orbit>) {
+        telemetry::shutdown();
+    }
+}
+
+/// Runs a route handler inside the request span, so its logs, DB calls and outgoing calls
+/// are attributed to the request
+#[derive(Clone)]
+struct Traced(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Traced {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let span = request_span(request).span.clone();
+        if let Some(route) = request.route() {
+            span.record("route", route.uri.as_str());
+            if let Some(org_id) = org_id(request, route) {
+                span.record("org_id", org_id.as_str());
+            }
+        }
+
+        let (outcome, db_time) = telemetry::with_db_timer(self.0.handle(request, data))
+            .instrument(span.clone())
+            .await;
+        span.record("db_ms", db_time.as_millis() as u64);
+
+        outcome
+    }
+}
+
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Traced(route.handler));
+            route
+        })
+        .collect()
+}
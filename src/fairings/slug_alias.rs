@@ -0,0 +1,152 @@
+use crate::db::pool::blocking;
+use crate::models::schema::schema::{organization, organization_slug_alias};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::{Data, Request};
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+const ORG_ID_PARAM: &str = "<org_id>";
+// Slug changes on this instance call `forget_aliases`, other instances pick them up once their
+// copy is this old
+const ALIASES_TTL: Duration = Duration::from_secs(60);
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+struct AliasTable {
+    loaded_at: Instant,
+    current: HashMap<String, String>, // old slug to the slug the workspace has now
+}
+
+// Requests are resolved against this copy rather than querying the database each time
+static ALIASES: RwLock<Option<AliasTable>> = RwLock::new(None);
+
+/// Drops the cached aliases, to be called once a slug change is committed
+pub fn forget_aliases() {
+    *ALIASES.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+fn load_aliases(rdb: &DbPool) -> Option<HashMap<String, String>> {
+    blocking(|| {
+        let mut conn = rdb.get().ok()?;
+        organization_slug_alias::table
+            .inner_join(organization::table)
+            .select((organization_slug_alias::old_slug, organization::slug))
+            .load::<(String, String)>(&mut conn)
+            .ok()
+            .map(|aliases| aliases.into_iter().collect())
+    })
+}
+
+// Runs `resolve` on the aliases, reloading them when they are stale. A failed reload keeps the
+// previous copy
+fn with_aliases<T>(rdb: &DbPool, resolve: impl FnOnce(&HashMap<String, String>) -> T) -> T {
+    {
+        let cached = ALIASES.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(table) = cached.as_ref() {
+            if table.loaded_at.elapsed() < ALIASES_TTL {
+                return resolve(&table.current);
+            }
+        }
+    }
+
+    let loaded = load_aliases(rdb);
+    let mut cached = ALIASES.write().unwrap_or_else(PoisonError::into_inner);
+    if let Some(current) = loaded {
+        *cached = Some(AliasTable {
+            loaded_at: Instant::now(),
+            current,
+        });
+    }
+    match cached.as_ref() {
+        Some(table) => resolve(&table.current),
+        None => resolve(&HashMap::new()),
+    }
+}
+
+/// Rewrites `<org_id>` path segments holding a slug the workspace had before a slug change,
+/// so bookmarked URLs and CI configs keep working after the rename
+pub struct SlugAliases;
+
+// Index of the `<org_id>` segment for every route the request could match
+fn org_segments(request: &Request<'_>, segments: &[&str]) -> Vec<usize> {
+    let mut indexes: Vec<usize> = request
+        .rocket()
+        .routes()
+        .filter(|route| route.method == request.method())
+        .filter_map(|route| {
+            let route_segments: Vec<&str> = route.uri.path().split('/').collect();
+            if route_segments.len() != segments.len() {
+                return None;
+            }
+
+            let mut org_index = None;
+            for (index, (expected, actual)) in route_segments.iter().zip(segments).enumerate() {
+                if *expected == ORG_ID_PARAM {
+                    org_index = Some(index);
+                } else if !expected.starts_with('<') && expected != actual {
+                    return None;
+                }
+            }
+            org_index
+        })
+        .collect();
+    indexes.sort_unstable();
+    indexes.dedup();
+    indexes
+}
+
+#[rocket::async_trait]
+impl Fairing for SlugAliases {
+    fn info(&self) -> Info {
+        Info {
+            name: "Resolve workspace slug aliases",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(rdb) = request.rocket().state::<DbPool>() else {
+            return;
+        };
+        let path = request.uri().path().to_string();
+        let mut segments: Vec<&str> = path.split('/').collect();
+
+        // Most requests carry no old slug, the routes are only matched for those that do
+        let aliased: Vec<(usize, String)> = with_aliases(rdb, |aliases| {
+            segments
+                .iter()
+                .enumerate()
+                .filter_map(|(index, segment)| Some((index, aliases.get(*segment)?.clone())))
+                .collect()
+        });
+        if aliased.is_empty() {
+            return;
+        }
+
+        let indexes = org_segments(request, &segments);
+        let resolved: Vec<(usize, String)> = aliased
+            .into_iter()
+            .filter(|(index, _)| indexes.contains(index))
+            .collect();
+        if resolved.is_empty() {
+            return;
+        }
+
+        for (index, slug) in &resolved {
+            segments[*index] = slug;
+        }
+
+        let mut rewritten = segments.join("/");
+        if let Some(query) = request.uri().query() {
+            rewritten = format!("{}?{}", rewritten, query);
+        }
+
+        if let Ok(uri) = Origin::parse_owned(rewritten) {
+            request.set_uri(uri);
+        }
+    }
+}
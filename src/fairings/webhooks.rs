@@ -0,0 +1,228 @@
+use crate::db::webhooks::{claim_due, mark_delivered, mark_failed};
+use crate::fairings::outbox::{spawn_poller, with_connection, DbPool, BATCH_SIZE};
+use crate::models::schema::{Webhook, Webhook_Delivery};
+use chrono::Utc;
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::net::lookup_host;
+use rocket::tokio::time::Duration;
+use rocket::{Orbit, Rocket};
+use sha2::Sha256;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// Sends queued webhook deliveries, retrying failures with backoff until they are marked failed
+pub struct WebhookDispatcher;
+
+/// `t=<unix seconds>,v1=<hex HMAC-SHA256 of "<t>.<body>">`, receivers recompute it with the
+/// secret returned when the webhook was registered and reject stale timestamps
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+/// Loopback and private targets are only reachable with `WEBHOOKS_ALLOW_PRIVATE_TARGETS=true`,
+/// for a local stand-in during development
+pub fn private_targets_allowed() -> bool {
+    cfg!(test) || env::var("WEBHOOKS_ALLOW_PRIVATE_TARGETS").map_or(false, |v| v == "true")
+}
+
+/// Whether a workspace may have the server send requests to `ip`
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || first == 0
+                || (first == 100 && (64..128).contains(&second)) // carrier-grade NAT
+                || (first == 198 && (second == 18 || second == 19))) // benchmarking
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_address(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00 // unique local
+                || (first & 0xffc0) == 0xfe80) // link local
+        }
+    }
+}
+
+/// Addresses of `host`, refused when any of them is internal and `allow_private` is not set
+pub async fn resolve_target(
+    host: &str,
+    port: u16,
+    allow_private: bool,
+) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Cannot resolve {}: {}", host, e))?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(format!("Cannot resolve {}", host));
+    }
+    if !allow_private && addrs.iter().any(|addr| !is_public_address(addr.ip())) {
+        return Err(format!("{} resolves to an internal address", host));
+    }
+    Ok(addrs)
+}
+
+/// Checked when a webhook is registered and again before each delivery
+pub async fn check_target(url: &str) -> Result<(), String> {
+    let parsed = Url::parse(url).map_err(|e| e.to_string())?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| "URL has no host".to_string())?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = parsed.port_or_known_default().unwrap_or(443);
+
+    resolve_target(host, port, private_targets_allowed())
+        .await
+        .map(|_| ())
+}
+
+// Hosts are resolved again when connecting, this keeps one from being rebound to an internal
+// address after `check_target`
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = resolve_target(name.as_str(), 0, false).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Posts the delivery, a 2xx answer is a success. Failures carry the status when there was one
+pub async fn deliver(
+    client: &reqwest::Client,
+    hook: &Webhook,
+    delivery: &Webhook_Delivery,
+) -> Result<i32, (Option<i32>, String)> {
+    check_target(&hook.url).await.map_err(|e| (None, e))?;
+
+    let response = client
+        .post(&hook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event_type)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(
+            SIGNATURE_HEADER,
+            signature(&hook.secret, Utc::now().timestamp(), &delivery.payload),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|e| (None, e.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16() as i32)
+    } else {
+        Err((Some(status.as_u16() as i32), format!("HTTP {}", status)))
+    }
+}
+
+async fn dispatch(pool: &DbPool, client: &reqwest::Client) -> Result<usize, String> {
+    let claimed = with_connection(pool, |conn| {
+        claim_due(conn, BATCH_SIZE).map_err(|e| e.to_string())
+    })
+    .await?;
+    let count = claimed.len();
+
+    // Endpoints are independent, a slow one does not hold up the rest of the batch
+    let outcomes = join_all(
+        claimed
+            .iter()
+            .map(|(delivery, hook)| deliver(client, hook, delivery)),
+    )
+    .await;
+
+    with_connection(pool, move |conn| {
+        for ((delivery, hook), outcome) in claimed.into_iter().zip(outcomes) {
+            match outcome {
+                Ok(status) => mark_delivered(conn, delivery.id, status),
+                Err((status, error)) => mark_failed(conn, &delivery, status, &error, Utc::now())
+                    .map(|failed| {
+                        if failed {
+                            tracing::error!(
+                                delivery_id = delivery.id,
+                                webhook_id = hook.id,
+                                %error,
+                                "Webhook delivery failed for good"
+                            );
+                        } else {
+                            tracing::warn!(
+                                delivery_id = delivery.id,
+                                webhook_id = hook.id,
+                                %error,
+                                "Webhook delivery failed, will retry"
+                            );
+                        }
+                    }),
+            }
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    })
+    .await?;
+
+    Ok(count)
+}
+
+#[rocket::async_trait]
+impl Fairing for WebhookDispatcher {
+    fn info(&self) -> Info {
+        Info {
+            name: "Deliver workspace webhooks",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(pool) = rocket.state::<DbPool>().cloned() else {
+            return;
+        };
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .redirect(reqwest::redirect::Policy::none());
+        if !private_targets_allowed() {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = builder.build().unwrap_or_default();
+
+        spawn_poller("dispatch webhook deliveries", move || {
+            let pool = pool.clone();
+            let client = client.clone();
+            async move { dispatch(&pool, &client).await }
+        });
+    }
+}
@@ -2,33 +2,58 @@
 extern crate rocket;
 use rocket::Rocket;
 
-use crate::routes::metadata;
-use db::redis::create_redis_pool;
+use crate::routes::{
+    api_tokens, canvas, deletions, discovery, health, manifests, metadata, outbox, quick_links,
+    webhooks, workspaces,
+};
+use db::pool::PoolConfig;
+use db::redis::{create_redis_pool, RedisPoolState};
 use dotenv::dotenv;
+use fairings::request_trace::traced;
 use rocket::Build;
 use rocket_okapi::openapi_get_routes;
 use rocket_okapi::swagger_ui::{make_swagger_ui, SwaggerUIConfig};
 use rocket_prometheus::PrometheusMetrics;
 use std::env;
 mod db;
+mod errors;
 mod fairings;
+mod metrics;
 mod middlewares;
 mod models;
 mod routes;
+mod telemetry;
 
 #[launch]
 fn rocket() -> Rocket<Build> {
     dotenv().ok();
+    telemetry::init();
     let prometheus = PrometheusMetrics::new();
+    metrics::register(prometheus.registry());
 
-    let mut server = rocket::build()
-        .manage(db::connect_rdb())
+    let server = rocket::build();
+    let postgres_pool = PoolConfig::from_figment(server.figment(), "postgres");
+    let redis_pool = PoolConfig::from_figment(server.figment(), "redis");
+
+    let mut server = server
+        .manage(db::connect_rdb(&postgres_pool))
+        .attach(fairings::request_trace::RequestTracer)
+        .attach(db::ensure_unique_indexes())
+        .attach(db::migrate_quick_links())
         .attach(fairings::cors::CORS)
+        .attach(fairings::rate_limit::RateLimiter)
+        .attach(fairings::slug_alias::SlugAliases)
+        .attach(fairings::purge::DeletedPurger)
+        .attach(fairings::catalog_metrics::CatalogMetrics)
+        .attach(fairings::outbox::OutboxRelay)
+        .attach(fairings::webhooks::WebhookDispatcher)
         .attach(prometheus.clone())
         .mount(
             "/metadata/",
-            openapi_get_routes![
+            traced(openapi_get_routes![
                 routes::index,
+                health::live,
+                health::ready,
                 metadata::create_dbschema,
                 metadata::get_dbschemas,
                 metadata::update_dbschema,
@@ -50,6 +75,11 @@ fn rocket() -> Rocket<Build> {
                 metadata::get_workspace,
                 metadata::get_workspace_details,
                 metadata::delete_workspace,
+                metadata::deactivate_workspace,
+                metadata::reactivate_workspace,
+                workspaces::rename_workspace,
+                workspaces::change_workspace_slug,
+                workspaces::transfer_workspace,
                 metadata::get_services_and_envs_user_land,
                 metadata::get_service_and_env_by_id_user_land,
                 metadata::get_current_workspace,
@@ -66,8 +96,37 @@ fn rocket() -> Rocket<Build> {
                 metadata::get_dbschemas_and_tables_user_land,
                 metadata::create_snapshot,
                 metadata::get_snapshots,
-                metadata::get_package_version
-            ],
+                metadata::get_package_version,
+                api_tokens::create_api_token,
+                api_tokens::get_api_tokens,
+                api_tokens::rotate_api_token,
+                api_tokens::revoke_api_token,
+                deletions::delete_service,
+                deletions::delete_service_env,
+                deletions::delete_package,
+                deletions::delete_package_env,
+                deletions::delete_dbschema,
+                deletions::delete_dbschema_branch,
+                deletions::get_deleted_items,
+                deletions::restore_deleted_item,
+                deletions::restore_workspace,
+                outbox::get_dead_letters,
+                outbox::retry_dead_letter,
+                webhooks::create_webhook,
+                webhooks::get_webhooks,
+                webhooks::delete_webhook,
+                webhooks::get_webhook_deliveries,
+                webhooks::redeliver_webhook_delivery,
+                manifests::ingest_manifests,
+                manifests::get_services_toml,
+                discovery::get_service_location,
+                discovery::resolve_services,
+                quick_links::get_quick_links,
+                quick_links::add_quick_link,
+                quick_links::update_quick_link,
+                quick_links::delete_quick_link,
+                canvas::get_auto_layout
+            ]),
         )
         .mount(
             "/metadata/api-docs",
@@ -76,27 +135,38 @@ fn rocket() -> Rocket<Build> {
                 ..Default::default()
             }),
         )
-        .mount("/metadata/metrics", prometheus);
+        .mount("/metadata/metrics", prometheus)
+        .register(
+            "/",
+            catchers![
+                errors::bad_request,
+                errors::unauthorized,
+                errors::forbidden,
+                errors::not_found,
+                errors::unprocessable_entity,
+                errors::internal_error
+            ],
+        );
 
     match env::var("MONGO_URI") {
         Ok(mongo_uri) => match env::var("MONGO_DB_NAME") {
             Ok(mongo_db_name) => {
-                println!("Attempting to connect to mongo");
-                server = server.manage(db::connect_mongo(mongo_uri, mongo_db_name))
+                tracing::info!("Attempting to connect to mongo");
+                server = server.attach(db::connect_mongo(mongo_uri, mongo_db_name))
             }
             Err(_) => {
-                println!("Not connecting to mongo, missing MONGO_DB_NAME")
+                tracing::warn!("Not connecting to mongo, missing MONGO_DB_NAME")
             }
         },
-        Err(_) => println!("Not connecting to mongo, missing MONGO_URI"),
+        Err(_) => tracing::warn!("Not connecting to mongo, missing MONGO_URI"),
     };
 
     match env::var("REDIS_URI") {
         Ok(redis_uri) => {
-            println!("Attempting to connect to redis");
-            server = server.manage(create_redis_pool(&redis_uri))
+            tracing::info!("Attempting to connect to redis");
+            server = server.manage(RedisPoolState(create_redis_pool(&redis_uri, &redis_pool)))
         }
-        Err(_) => println!("Not connecting to redis"),
+        Err(_) => tracing::warn!("Not connecting to redis"),
     }
 
     server
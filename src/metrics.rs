@@ -0,0 +1,185 @@
+use crate::db::pipeline::{CatalogPipeline, FAILED};
+use chrono::{DateTime, Utc};
+use rocket_prometheus::prometheus::{
+    exponential_buckets, histogram_opts, opts, GaugeVec, HistogramVec, IntCounterVec, IntGaugeVec,
+    Registry,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Catalog metrics exposed next to the HTTP ones at `/metadata/metrics`. The catalog gauges are
+/// recomputed by `fairings::catalog_metrics`, the rest is recorded where it happens.
+///
+/// A prod pipeline failing for more than an hour:
+/// `metadata_pipeline_failing_seconds{env="prod"} > 3600`
+pub struct DomainMetrics {
+    pub entities: IntGaugeVec,
+    pub pipelines: IntGaugeVec,
+    pub seconds_since_pass: GaugeVec,
+    pub failing_seconds: GaugeVec,
+    pub spec_size_bytes: HistogramVec,
+    pub notification_failures: IntCounterVec,
+    pub iam_duration: HistogramVec,
+}
+
+static METRICS: OnceLock<DomainMetrics> = OnceLock::new();
+
+pub fn metrics() -> &'static DomainMetrics {
+    METRICS.get_or_init(|| DomainMetrics {
+        entities: IntGaugeVec::new(
+            opts!(
+                "metadata_catalog_entities",
+                "Environments of services, packages and schemas"
+            ),
+            &["kind", "org_id", "env"],
+        )
+        .unwrap(),
+        pipelines: IntGaugeVec::new(
+            opts!("metadata_pipelines", "Environment pipelines by status"),
+            &["kind", "env", "status"],
+        )
+        .unwrap(),
+        seconds_since_pass: GaugeVec::new(
+            opts!(
+                "metadata_pipeline_seconds_since_pass",
+                "Seconds since the pipeline of an environment last passed"
+            ),
+            &["kind", "org_id", "identifier", "env"],
+        )
+        .unwrap(),
+        failing_seconds: GaugeVec::new(
+            opts!(
+                "metadata_pipeline_failing_seconds",
+                "Seconds since a failed pipeline started failing"
+            ),
+            &["kind", "org_id", "identifier", "env"],
+        )
+        .unwrap(),
+        spec_size_bytes: HistogramVec::new(
+            histogram_opts!(
+                "metadata_spec_size_bytes",
+                "Size of published specs and schemas",
+                exponential_buckets(256.0, 4.0, 8).unwrap()
+            ),
+            &["kind"],
+        )
+        .unwrap(),
+        notification_failures: IntCounterVec::new(
+            opts!(
+                "metadata_notification_publish_failures_total",
+                "Messages NotificationService failed to publish"
+            ),
+            &["topic"],
+        )
+        .unwrap(),
+        iam_duration: HistogramVec::new(
+            histogram_opts!(
+                "metadata_iam_request_duration_seconds",
+                "Latency of calls to IAMService"
+            ),
+            &["operation", "outcome"],
+        )
+        .unwrap(),
+    })
+}
+
+/// Adds the domain metrics to the registry served by `rocket_prometheus`
+pub fn register(registry: &Registry) {
+    let metrics = metrics();
+    let collectors: [Box<dyn rocket_prometheus::prometheus::core::Collector>; 7] = [
+        Box::new(metrics.entities.clone()),
+        Box::new(metrics.pipelines.clone()),
+        Box::new(metrics.seconds_since_pass.clone()),
+        Box::new(metrics.failing_seconds.clone()),
+        Box::new(metrics.spec_size_bytes.clone()),
+        Box::new(metrics.notification_failures.clone()),
+        Box::new(metrics.iam_duration.clone()),
+    ];
+
+    for collector in collectors {
+        if let Err(error) = registry.register(collector) {
+            tracing::error!(%error, "Failed to register metric");
+        }
+    }
+}
+
+/// Times a call to IAMService, labelled with the operation and whether it succeeded
+pub async fn iam_call<T, E>(
+    operation: &str,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let started = Instant::now();
+    let result = call.await;
+    metrics()
+        .iam_duration
+        .with_label_values(&[operation, if result.is_ok() { "ok" } else { "error" }])
+        .observe(started.elapsed().as_secs_f64());
+    result
+}
+
+/// Records the size of a published document, before it is moved out to blob storage
+pub fn observe_spec(kind: &str, document: Option<&str>) {
+    if let Some(document) = document {
+        metrics()
+            .spec_size_bytes
+            .with_label_values(&[kind])
+            .observe(document.len() as f64);
+    }
+}
+
+fn seconds_since(at: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+    (now - at).num_seconds().max(0) as f64
+}
+
+impl DomainMetrics {
+    /// Replaces the catalog gauges, entries gone from the catalog drop out of the series
+    pub fn refresh(&self, pipelines: &[CatalogPipeline], now: DateTime<Utc>) {
+        let mut entities: HashMap<[&str; 3], i64> = HashMap::new();
+        let mut statuses: HashMap<[&str; 3], i64> = HashMap::new();
+
+        self.seconds_since_pass.reset();
+        self.failing_seconds.reset();
+
+        for pipeline in pipelines {
+            let status = pipeline.status.as_deref().unwrap_or("unknown");
+            *entities
+                .entry([
+                    pipeline.kind,
+                    pipeline.org_id.as_str(),
+                    pipeline.env.as_str(),
+                ])
+                .or_default() += 1;
+            *statuses
+                .entry([pipeline.kind, pipeline.env.as_str(), status])
+                .or_default() += 1;
+
+            let labels = [
+                pipeline.kind,
+                pipeline.org_id.as_str(),
+                pipeline.identifier.as_str(),
+                pipeline.env.as_str(),
+            ];
+            if let Some(last_passed_at) = pipeline.timestamps.last_passed_at {
+                self.seconds_since_pass
+                    .with_label_values(&labels)
+                    .set(seconds_since(last_passed_at, now));
+            }
+            if let (FAILED, Some(changed_at)) = (status, pipeline.timestamps.changed_at) {
+                self.failing_seconds
+                    .with_label_values(&labels)
+                    .set(seconds_since(changed_at, now));
+            }
+        }
+
+        self.entities.reset();
+        for (labels, count) in entities {
+            self.entities.with_label_values(&labels).set(count);
+        }
+        self.pipelines.reset();
+        for (labels, count) in statuses {
+            self.pipelines.with_label_values(&labels).set(count);
+        }
+    }
+}
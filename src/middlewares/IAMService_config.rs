@@ -1,5 +1,6 @@
 
                         use okapi::openapi3::{Object, SecurityRequirement, SecurityScheme, SecuritySchemeData};
+use crate::telemetry;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket_okapi::gen::OpenApiGenerator;
@@ -10,6 +11,14 @@ use IAMService::get_configuration; // Assuming get_configuration exists and retu
 #[derive(Debug)]
 pub struct IAMService_config(pub Configuration); // Wrapper struct for Configuration
 
+/// Configuration for calls made outside of a request, authenticated with IAM_SERVICE_API_KEY
+pub fn service_configuration() -> Option<Configuration> {
+    let key = std::env::var("IAM_SERVICE_API_KEY").ok()?;
+    let mut configuration = get_configuration();
+    configuration.api_key = Some(ApiKey { key, prefix: None });
+    Some(configuration)
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for IAMService_config {
     type Error = ();
@@ -28,6 +37,7 @@ impl<'r> FromRequest<'r> for IAMService_config {
             key: token_str,
             prefix: None,
         });
+        configuration.client = telemetry::propagating_client();
 
         Outcome::Success(IAMService_config(configuration))
     }
@@ -1,3 +1,4 @@
+use crate::telemetry;
 use okapi::openapi3::{Object, SecurityRequirement, SecurityScheme, SecuritySchemeData};
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
@@ -9,6 +10,14 @@ use NotificationService::get_configuration; // Assuming get_configuration exists
 #[derive(Debug)]
 pub struct NotificationService_api_config(pub Configuration); // Wrapper struct for Configuration
 
+/// Configuration for calls made outside of a request, authenticated with NOTIFICATION_SERVICE_API_KEY
+pub fn service_configuration() -> Option<Configuration> {
+    let key = std::env::var("NOTIFICATION_SERVICE_API_KEY").ok()?;
+    let mut configuration = get_configuration();
+    configuration.api_key = Some(ApiKey { key, prefix: None });
+    Some(configuration)
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for NotificationService_api_config {
     type Error = ();
@@ -27,6 +36,7 @@ impl<'r> FromRequest<'r> for NotificationService_api_config {
             key: token_str,
             prefix: None,
         });
+        configuration.client = telemetry::propagating_client();
 
         Outcome::Success(NotificationService_api_config(configuration))
     }
@@ -1,5 +1,6 @@
 
                         use okapi::openapi3::{Object, SecurityRequirement, SecurityScheme, SecuritySchemeData};
+use crate::telemetry;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket_okapi::gen::OpenApiGenerator;
@@ -28,6 +29,7 @@ impl<'r> FromRequest<'r> for NotificationService_config {
             key: token_str,
             prefix: None,
         });
+        configuration.client = telemetry::propagating_client();
 
         Outcome::Success(NotificationService_config(configuration))
     }
@@ -0,0 +1,194 @@
+use crate::db::pool::blocking;
+use crate::errors::ApiError;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use ginger_shared_rs::rocket_utils::APIClaims;
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+use serde::Deserialize;
+use std::env;
+use std::ops::Deref;
+
+/// Lets a token read the workspace metadata
+pub const SCOPE_READ: &str = "metadata:read";
+/// Lets a token register services, packages, schemas and snapshots, implies `metadata:read`
+pub const SCOPE_WRITE: &str = "metadata:write";
+/// Lets a token report pipeline results
+pub const SCOPE_PIPELINE: &str = "pipeline:write";
+
+pub const KNOWN_SCOPES: [&str; 3] = [SCOPE_READ, SCOPE_WRITE, SCOPE_PIPELINE];
+
+// Only tokens minted by this service carry a `jti`, older tokens have nothing to revoke
+#[derive(Deserialize)]
+struct TokenId {
+    jti: Option<String>,
+}
+
+/// `APIClaims` that have additionally been checked against the api token revocation store. The
+/// flag marks legacy tokens, those without a `jti`
+pub struct ActiveAPIClaims(pub APIClaims, bool);
+
+impl Deref for ActiveAPIClaims {
+    type Target = APIClaims;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Tokens minted outside this service predate scopes and carry none. Deprecated: they keep
+/// acting on their workspace without scope checks until `API_TOKENS_ALLOW_LEGACY=false`, which
+/// is meant to be set once the pipelines using them have moved to tokens from `/api-tokens`
+pub fn legacy_tokens_allowed() -> bool {
+    env::var("API_TOKENS_ALLOW_LEGACY").map_or(true, |v| v != "false")
+}
+
+/// Tokens are bound to the workspace whose group is their `sub`, they may only act on it and
+/// only within their scopes, unless `unscoped` lets a legacy token skip the scope check
+pub fn check_token_access(
+    token_sub: &str,
+    token_scopes: &[String],
+    unscoped: bool,
+    org_group_id: &str,
+    scope: &str,
+) -> Result<(), ApiError> {
+    let granted = unscoped
+        || token_scopes
+            .iter()
+            .any(|granted| granted == scope || (granted == SCOPE_WRITE && scope == SCOPE_READ));
+    if !granted {
+        return Err(ApiError::Forbidden(format!(
+            "Permission Denied: The token lacks the {} scope.",
+            scope
+        )));
+    }
+    if token_sub != org_group_id {
+        return Err(ApiError::Forbidden(
+            "Permission Denied: The token belongs to another workspace.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+impl ActiveAPIClaims {
+    fn unscoped(&self) -> bool {
+        self.1 && legacy_tokens_allowed()
+    }
+
+    /// For routes that are not tied to a workspace
+    pub fn require_scope(&self, scope: &str) -> Result<(), ApiError> {
+        check_token_access(&self.sub, &self.scopes, self.unscoped(), &self.sub, scope)
+    }
+
+    /// Checks the token may act on `org_id` with `scope`
+    pub fn authorize(
+        &self,
+        conn: &mut PgConnection,
+        org_id: &str,
+        scope: &str,
+    ) -> Result<(), ApiError> {
+        use crate::models::schema::schema::organization;
+
+        let org_group_id = organization::table
+            .filter(organization::slug.eq(org_id))
+            .filter(organization::deleted_at.is_null())
+            .select(organization::group_id)
+            .first::<String>(conn)
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound("Workspace not found".to_string()))?;
+
+        check_token_access(
+            &self.sub,
+            &self.scopes,
+            self.unscoped(),
+            &org_group_id,
+            scope,
+        )
+    }
+}
+
+fn token_id(request: &Request<'_>) -> Option<String> {
+    let token = request
+        .headers()
+        .get_one("X-API-Authorization")?
+        .trim_start_matches("Bearer ")
+        .trim();
+    token_jti(token)
+}
+
+/// The `jti` of a token, `None` for legacy tokens
+pub fn token_jti(token: &str) -> Option<String> {
+    // The signature and expiry have already been verified by the `APIClaims` guard
+    let mut validation = Validation::default();
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.required_spec_claims.clear();
+
+    decode::<TokenId>(token, &DecodingKey::from_secret(&[]), &validation)
+        .ok()
+        .and_then(|data| data.claims.jti)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ActiveAPIClaims {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let claims = match request.guard::<APIClaims>().await {
+            Outcome::Success(claims) => claims,
+            Outcome::Error((status, _)) => return Outcome::Error((status, ())),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        let token_jti = match token_id(request) {
+            Some(token_jti) => token_jti,
+            None => return Outcome::Success(ActiveAPIClaims(claims, true)),
+        };
+
+        let rdb = match request
+            .rocket()
+            .state::<Pool<ConnectionManager<PgConnection>>>()
+        {
+            Some(rdb) => rdb,
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        use crate::models::schema::schema::api_token::dsl::*;
+
+        let revoked = blocking(|| {
+            let mut conn = rdb.get().ok()?;
+            Some(
+                api_token
+                    .filter(jti.eq(&token_jti))
+                    .select(revoked_at)
+                    .first::<Option<DateTime<Utc>>>(&mut conn)
+                    .optional(),
+            )
+        });
+
+        let Some(revoked) = revoked else {
+            return Outcome::Error((Status::ServiceUnavailable, ()));
+        };
+
+        match revoked {
+            Ok(Some(None)) => Outcome::Success(ActiveAPIClaims(claims, false)),
+            // Either revoked or removed from the store
+            Ok(_) => Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+impl<'a> OpenApiFromRequest<'a> for ActiveAPIClaims {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        name: String,
+        required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        APIClaims::from_request_input(gen, name, required)
+    }
+}
@@ -0,0 +1,174 @@
+use crate::db::blobs::content_hash;
+use crate::errors::ApiError;
+use okapi::openapi3::{
+    Object, Parameter, ParameterValue, RefOr, Response as OpenApiResponse, Responses,
+};
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder};
+use rocket::Response;
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+use rocket_okapi::response::OpenApiResponderInner;
+use rocket_okapi::OpenApiError;
+use serde::Serialize;
+
+/// Revision of a representation, quoted as it goes on the wire
+pub fn etag<T: Serialize>(value: &T) -> String {
+    format!(
+        "\"{}\"",
+        content_hash(&serde_json::to_string(value).unwrap_or_default())
+    )
+}
+
+// `*` or a comma separated list of entity tags, weak tags compare equal to their strong form
+fn header_matches(header: &str, current: &str) -> bool {
+    header
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == current)
+}
+
+fn header_parameter(
+    gen: &mut OpenApiGenerator,
+    name: &str,
+    description: &str,
+    required: bool,
+) -> RequestHeaderInput {
+    RequestHeaderInput::Parameter(Parameter {
+        name: name.to_string(),
+        location: "header".to_string(),
+        description: Some(description.to_string()),
+        required,
+        deprecated: false,
+        allow_empty_value: false,
+        value: ParameterValue::Schema {
+            style: None,
+            explode: None,
+            allow_reserved: false,
+            schema: gen.json_schema::<String>(),
+            example: None,
+            examples: None,
+        },
+        extensions: Object::default(),
+    })
+}
+
+/// `If-Match` header, writes are rejected unless it matches the current revision
+#[derive(Debug)]
+pub struct IfMatch(pub Option<String>);
+
+impl IfMatch {
+    pub fn verify(&self, current: &str) -> Result<(), ApiError> {
+        match &self.0 {
+            None => Err(ApiError::PreconditionRequired(
+                "If-Match header with the ETag of the last read is required".to_string(),
+            )),
+            Some(header) if header_matches(header, current) => Ok(()),
+            Some(_) => Err(ApiError::PreconditionFailed(
+                "The resource was modified since it was last read".to_string(),
+            )),
+        }
+    }
+}
+
+/// `If-None-Match` header, reads matching the current revision answer 304
+#[derive(Debug)]
+pub struct IfNoneMatch(pub Option<String>);
+
+impl IfNoneMatch {
+    pub fn matches(&self, current: &str) -> bool {
+        self.0
+            .as_deref()
+            .map_or(false, |header| header_matches(header, current))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfMatch(
+            request.headers().get_one("If-Match").map(str::to_string),
+        ))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch(
+            request
+                .headers()
+                .get_one("If-None-Match")
+                .map(str::to_string),
+        ))
+    }
+}
+
+impl<'a> OpenApiFromRequest<'a> for IfMatch {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> Result<RequestHeaderInput, OpenApiError> {
+        Ok(header_parameter(
+            gen,
+            "If-Match",
+            "ETag of the last read, the write fails with 412 when the resource changed since",
+            true,
+        ))
+    }
+}
+
+impl<'a> OpenApiFromRequest<'a> for IfNoneMatch {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> Result<RequestHeaderInput, OpenApiError> {
+        Ok(header_parameter(
+            gen,
+            "If-None-Match",
+            "ETag of a cached copy, answered with 304 when it is still current",
+            false,
+        ))
+    }
+}
+
+/// Response carrying an `ETag` header, or an empty 304 when the caller's copy is current
+pub enum ETagged<R> {
+    Body(String, R),
+    NotModified(String),
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for ETagged<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            ETagged::Body(etag, body) => Response::build_from(body.respond_to(request)?)
+                .header(Header::new("ETag", etag))
+                .ok(),
+            ETagged::NotModified(etag) => Response::build()
+                .status(Status::NotModified)
+                .header(Header::new("ETag", etag))
+                .ok(),
+        }
+    }
+}
+
+impl<R: OpenApiResponderInner> OpenApiResponderInner for ETagged<R> {
+    fn responses(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = R::responses(gen)?;
+        responses.responses.insert(
+            "304".to_string(),
+            RefOr::Object(OpenApiResponse {
+                description: "The copy matching If-None-Match is still current".to_string(),
+                ..Default::default()
+            }),
+        );
+        Ok(responses)
+    }
+}
@@ -1,4 +1,5 @@
 use super::IAMService_config::IAMService_config;
+use crate::metrics::iam_call;
 use ginger_shared_rs::rocket_utils::Claims;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
@@ -30,7 +31,12 @@ impl<'r> FromRequest<'r> for GroupOwnerships {
             (claims, iam_service_config)
         {
             // Here you would proxy to the IAM service
-            match identity_get_group_ownserships(&openapi_config.0).await {
+            match iam_call(
+                "get_group_ownerships",
+                identity_get_group_ownserships(&openapi_config.0),
+            )
+            .await
+            {
                 Ok(groups) => Outcome::Success(GroupOwnerships::new(groups)),
                 Err(_) => Outcome::Error((Status::InternalServerError, ())),
             }
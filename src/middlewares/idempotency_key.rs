@@ -0,0 +1,62 @@
+use okapi::openapi3::{Object, Parameter, ParameterValue};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::request::OpenApiFromRequest;
+use rocket_okapi::request::RequestHeaderInput;
+use rocket_okapi::OpenApiError;
+
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const IDEMPOTENCY_KEY_MAX_LENGTH: usize = 255;
+
+/// Optional `Idempotency-Key` header, retried requests carrying the same key replay the first response
+#[derive(Debug)]
+pub struct IdempotencyKey(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.headers().get_one(IDEMPOTENCY_KEY_HEADER) {
+            None => Outcome::Success(IdempotencyKey(None)),
+            Some(key) => {
+                let key = key.trim();
+                if key.is_empty() || key.len() > IDEMPOTENCY_KEY_MAX_LENGTH {
+                    Outcome::Error((Status::BadRequest, ()))
+                } else {
+                    Outcome::Success(IdempotencyKey(Some(key.to_string())))
+                }
+            }
+        }
+    }
+}
+
+impl<'a> OpenApiFromRequest<'a> for IdempotencyKey {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> Result<RequestHeaderInput, OpenApiError> {
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: IDEMPOTENCY_KEY_HEADER.to_string(),
+            location: "header".to_string(),
+            description: Some(
+                "Unique key per logical write, retries with the same key return the original response"
+                    .to_string(),
+            ),
+            required: false,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema: gen.json_schema::<String>(),
+                example: None,
+                examples: None,
+            },
+            extensions: Object::default(),
+        }))
+    }
+}
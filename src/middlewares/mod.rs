@@ -4,5 +4,8 @@
 pub mod IAMService_config;
 pub mod NotificationService_api_config;
 pub mod NotificationService_config;
+pub mod api_token_claims;
+pub mod conditional;
 pub mod groups;
 pub mod groups_owned;
+pub mod idempotency_key;
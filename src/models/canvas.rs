@@ -0,0 +1,112 @@
+use rocket_okapi::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Layout of the workspace canvas, stored in `organization.blocks_positions`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CanvasLayout {
+    #[serde(default)]
+    pub blocks: BTreeMap<String, BlockLayout>, // keyed by identifier of a service, package or schema
+    #[serde(default)]
+    pub groups: Vec<CanvasGroup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BlockLayout {
+    pub x: f64,
+    pub y: f64,
+    pub width: Option<f64>, // the portal sizes the block to its content when absent
+    pub height: Option<f64>,
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+/// A group or swimlane drawn behind the blocks it holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CanvasGroup {
+    pub id: String,
+    pub label: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(default)]
+    pub collapsed: bool,
+    #[serde(default)]
+    pub blocks: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct LegacyPosition {
+    x: f64,
+    y: f64,
+}
+
+// The portal used to store `{"<id>": {"x": .., "y": ..}}` or a list of React Flow nodes
+fn decode_legacy(value: Value) -> Option<CanvasLayout> {
+    let positions: Vec<(String, LegacyPosition)> = match value {
+        Value::Object(positions) => positions
+            .into_iter()
+            .filter_map(|(id, position)| Some((id, serde_json::from_value(position).ok()?)))
+            .collect(),
+        Value::Array(nodes) => nodes
+            .into_iter()
+            .filter_map(|mut node| {
+                let id = node.get("id")?.as_str()?.to_string();
+                let position = node.get_mut("position").map(Value::take).unwrap_or(node);
+                Some((id, serde_json::from_value(position).ok()?))
+            })
+            .collect(),
+        _ => return None,
+    };
+
+    Some(CanvasLayout {
+        blocks: positions
+            .into_iter()
+            .map(|(id, position)| {
+                let block = BlockLayout {
+                    x: position.x,
+                    y: position.y,
+                    width: None,
+                    height: None,
+                    collapsed: false,
+                };
+                (id, block)
+            })
+            .collect(),
+        groups: vec![],
+    })
+}
+
+/// Layout held by `blocks_positions`, empty when there is none or it cannot be read
+pub fn parse_layout(stored: Option<&str>) -> CanvasLayout {
+    let Some(value) = stored.and_then(|stored| serde_json::from_str::<Value>(stored).ok()) else {
+        return CanvasLayout::default();
+    };
+
+    let typed = value.get("blocks").is_some() || value.get("groups").is_some();
+    if typed {
+        serde_json::from_value(value).unwrap_or_default()
+    } else {
+        decode_legacy(value).unwrap_or_default()
+    }
+}
+
+/// What goes into `blocks_positions`, nothing for an empty layout
+pub fn stored_layout(layout: &CanvasLayout) -> Option<String> {
+    if layout.blocks.is_empty() && layout.groups.is_empty() {
+        return None;
+    }
+    serde_json::to_string(layout).ok()
+}
+
+impl CanvasLayout {
+    /// Drops the blocks of entities that no longer exist, groups only keep the remaining ones
+    pub fn prune(&mut self, live: &BTreeSet<String>) {
+        self.blocks.retain(|id, _| live.contains(id));
+        for group in &mut self.groups {
+            group.blocks.retain(|id| live.contains(id));
+        }
+    }
+}
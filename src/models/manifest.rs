@@ -0,0 +1,76 @@
+use crate::models::quick_link::QuickLink;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// `metadata.toml` of a repo
+#[derive(Debug, Deserialize)]
+pub struct MetadataManifest {
+    pub lang: Option<String>,
+    pub package_type: Option<String>,
+    #[serde(default)]
+    pub links: Vec<QuickLink>, // stored as the quick links of the service or package
+}
+
+/// URL of something per env (`dev`, `stage`, `prod`, `stage_k8`, `prod_k8`)
+pub type EnvUrls = BTreeMap<String, String>;
+
+/// `services.toml` of a service, dependencies are keyed by identifier
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServicesManifest {
+    pub lang: Option<String>,
+    pub organization_id: Option<String>,
+    pub dir: Option<String>,
+    pub spec_url: Option<String>,
+    pub service_type: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub services: BTreeMap<String, EnvUrls>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub portals_refs: BTreeMap<String, EnvUrls>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ws_refs: BTreeMap<String, EnvUrls>,
+    #[serde(default)]
+    pub urls: EnvUrls,
+    #[serde(default)]
+    pub urls_ws: EnvUrls,
+}
+
+impl ServicesManifest {
+    /// Identifiers of the services and portals the service calls, sorted
+    pub fn dependencies(&self) -> Vec<String> {
+        self.services
+            .keys()
+            .chain(self.portals_refs.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// `releaser.toml`, only the version is of interest here
+#[derive(Debug, Deserialize)]
+pub struct ReleaserManifest {
+    pub version: ReleaserVersion,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaserVersion {
+    pub channel: String,
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    #[serde(default)]
+    pub revision: u32,
+}
+
+// Written the way the releaser stamps it into Cargo.toml, `0.6.0-nightly.0`
+impl fmt::Display for ReleaserVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if self.channel.eq_ignore_ascii_case("final") {
+            return Ok(());
+        }
+        write!(f, "-{}.{}", self.channel.to_lowercase(), self.revision)
+    }
+}
@@ -1,3 +1,6 @@
+pub mod canvas;
+pub mod manifest;
+pub mod quick_link;
 pub mod request;
 pub mod response;
 pub mod schema;
@@ -0,0 +1,69 @@
+use rocket_okapi::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+/// A link shown on a schema, service, package or workspace, same shape as the `[[links]]` of
+/// `metadata.toml`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct QuickLink {
+    pub icon: String,
+    #[serde(default)]
+    pub internal: bool,
+    pub label: String,
+    pub link: String,
+}
+
+// Clients used to JSON-encode the links themselves, some did it twice. Entries that are not
+// links are dropped, `None` when it is not a list of links at all
+fn decode(stored: &str) -> Option<Vec<QuickLink>> {
+    match serde_json::from_str::<Value>(stored).ok()? {
+        Value::String(inner) => decode(&inner),
+        Value::Array(items) => Some(
+            items
+                .into_iter()
+                .filter_map(|item| serde_json::from_value(item).ok())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Links held by a `quick_links` column
+pub fn parse_quick_links(stored: Option<&str>) -> Vec<QuickLink> {
+    stored.and_then(decode).unwrap_or_default()
+}
+
+/// What goes into a `quick_links` column, nothing when there are no links
+pub fn stored_quick_links(links: &[QuickLink]) -> Option<String> {
+    if links.is_empty() {
+        return None;
+    }
+    serde_json::to_string(links).ok()
+}
+
+/// Canonical form of a column written before links were typed, `None` when it cannot be read
+pub fn normalize_stored(stored: &str) -> Option<Option<String>> {
+    decode(stored).map(|links| stored_quick_links(&links))
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LinksOrEncoded {
+    Links(Vec<QuickLink>),
+    Encoded(String),
+}
+
+/// Accepts the links, or the JSON-encoded string clients sent before links were typed
+pub fn deserialize_quick_links<'de, D>(deserializer: D) -> Result<Option<Vec<QuickLink>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<LinksOrEncoded>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(LinksOrEncoded::Links(links)) => Ok(Some(links)),
+        Some(LinksOrEncoded::Encoded(encoded)) if encoded.trim().is_empty() => Ok(None),
+        Some(LinksOrEncoded::Encoded(encoded)) => decode(&encoded)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom("quick_links must be a list of links")),
+    }
+}
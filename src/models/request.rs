@@ -1,3 +1,5 @@
+use crate::models::quick_link::{deserialize_quick_links, QuickLink};
+use rocket::FromForm;
 use rocket_okapi::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -10,8 +12,9 @@ pub struct CreateDbschemaRequest {
     pub db_type: String,
     pub repo_origin: String,
     pub version: String,
-    pub quick_links: Option<String>,
-    pub schema: Option<String> // if we are creating it then we will use this schema to populate the schema
+    #[serde(default, deserialize_with = "deserialize_quick_links")]
+    pub quick_links: Option<Vec<QuickLink>>,
+    pub schema: Option<String>, // if we are creating it then we will use this schema to populate the schema
 }
 
 #[derive(Deserialize, JsonSchema, Serialize)]
@@ -21,7 +24,8 @@ pub struct UpdateDbschemaRequest {
     pub organisation_id: String,
     pub repo_origin: String,
     pub version: String,
-    pub quick_links: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_quick_links")]
+    pub quick_links: Option<Vec<QuickLink>>,
 }
 
 #[derive(Deserialize, JsonSchema, Serialize)]
@@ -60,7 +64,8 @@ pub struct UpdateServiceRequest {
     pub description: String,
     pub organization_id: String,
     pub repo_origin: Option<String>,
-    pub quick_links: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_quick_links")]
+    pub quick_links: Option<Vec<QuickLink>>,
 }
 
 #[derive(Deserialize, Serialize, JsonSchema)]
@@ -74,7 +79,8 @@ pub struct CreateOrUpdatePackageRequest {
     pub dependencies: Vec<String>,
     pub env: String,
     pub repo_origin: Option<String>,
-    pub quick_links: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_quick_links")]
+    pub quick_links: Option<Vec<QuickLink>>,
 }
 
 #[derive(Deserialize, JsonSchema)]
@@ -96,5 +102,82 @@ pub struct CreateSnapshotRequest {
     pub version: String,
     pub org_id: String,
     pub infra_repo_origin: String,
-    pub quick_links: String,
+    #[serde(default, deserialize_with = "deserialize_quick_links")]
+    pub quick_links: Option<Vec<QuickLink>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<i64>, // defaults to 90, at most 365
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RotateApiTokenRequest {
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CreateWebhookRequest {
+    pub url: String,              // https, plain http is only accepted for loopback hosts
+    pub event_types: Vec<String>, // e.g. service.spec_changed, pipeline.failed
+}
+
+/// The manifests of a repo as they are checked in, a service is published when `services_toml`
+/// is given, a package otherwise
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct IngestManifestsRequest {
+    pub identifier: String,
+    pub env: String,
+    pub metadata_toml: String,
+    pub services_toml: Option<String>,
+    pub releaser_toml: Option<String>,
+    pub organization_id: Option<String>, // defaults to the one of services.toml
+    pub spec: Option<String>,            // the registered spec is kept when absent
+    pub description: Option<String>,
+    pub repo_origin: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RenameWorkspaceRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ChangeWorkspaceSlugRequest {
+    pub slug: String, // normalized with the same rules as the slug derived at creation
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TransferWorkspaceRequest {
+    pub group_id: String, // IAM group taking over, the caller must own it
+}
+
+/// Query parameters of the services listings, filters are combined with AND
+#[derive(Debug, Serialize, Deserialize, JsonSchema, FromForm)]
+pub struct ServiceListQuery {
+    pub service_type: Option<String>,
+    pub lang: Option<String>,
+    pub env: Option<String>, // services with a live environment of that name
+    pub pipeline_status: Option<String>, // of any environment, or of `env` when both are given
+    pub db_schema_id: Option<String>,
+    pub dependency: Option<String>, // services listing this identifier among their dependencies
+    pub sort: Option<String>,       // identifier or updated_at, prefixed with - for descending
+    pub fields: Option<String>,     // comma separated top level fields to return
+}
+
+/// Whose quick links, the workspace itself when `kind` is absent
+#[derive(Debug, Serialize, Deserialize, JsonSchema, FromForm)]
+pub struct QuickLinkOwnerQuery {
+    pub kind: Option<String>, // workspace, dbschema, service or package
+    pub identifier: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, FromForm)]
+pub struct PackageListQuery {
+    pub package_type: Option<String>,
+    pub lang: Option<String>,
+    pub sort: Option<String>,
+    pub fields: Option<String>,
 }
@@ -1,8 +1,10 @@
+use crate::models::canvas::CanvasLayout;
+use crate::models::quick_link::QuickLink;
 use chrono::{DateTime, Utc};
 use rocket_okapi::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CreateDbschemaResponse {
     pub message: String,
     pub id: i64,
@@ -19,7 +21,7 @@ pub struct GetDbschemaResponse {
     pub organization_id: String,
 }
 
-#[derive(Serialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetDbschemaAndTablesResponse {
     pub id: i64,
     pub name: String,
@@ -32,7 +34,7 @@ pub struct GetDbschemaAndTablesResponse {
     pub tables: Vec<String>,
     pub pipeline_status: Option<String>,
     pub repo_origin: Option<String>,
-    pub quick_links: Option<String>,
+    pub quick_links: Vec<QuickLink>,
 }
 
 #[derive(Serialize, JsonSchema)]
@@ -61,7 +63,7 @@ pub struct UpdateDbschemaBranchResponse {
     pub id: i64,
 }
 
-#[derive(Debug, Serialize, JsonSchema)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct UpdateServiceResponse {
     pub message: String,
     pub service_id: i64,
@@ -71,8 +73,9 @@ pub struct UpdateServiceResponse {
 pub struct ServicesEnvResponse {
     pub spec: String,
     pub base_url: String,
+    pub base_url_ws: Option<String>,
 }
-#[derive(Debug, Serialize, JsonSchema)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ServicesEnvTrimmedResponse {
     pub env_key: String,
     pub base_url: String,
@@ -82,7 +85,7 @@ pub struct ServicesEnvTrimmedResponse {
     pub pipeline_status: Option<String>,
 }
 
-#[derive(Debug, Serialize, JsonSchema)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ServicesTrimmedResponse {
     pub identifier: String,
     pub envs: Vec<ServicesEnvTrimmedResponse>,
@@ -96,7 +99,8 @@ pub struct ServicesTrimmedResponse {
     pub description: String,
     pub organization_id: String,
     pub repo_origin: Option<String>,
-    pub quick_links: Option<String>,
+    pub quick_links: Vec<QuickLink>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -121,13 +125,13 @@ pub struct ServiceResponse {
     pub repo_origin: Option<String>,
 }
 
-#[derive(Serialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct CreateOrUpdatePackageResponse {
     pub message: String,
     pub package_id: i64,
 }
 
-#[derive(Serialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct PackageResponse {
     pub identifier: String,
     pub package_type: String,
@@ -139,7 +143,7 @@ pub struct PackageResponse {
     pub dependencies: Vec<String>,
     pub pipeline_status: Option<String>,
     pub repo_origin: Option<String>,
-    pub quick_links: Option<String>,
+    pub quick_links: Vec<QuickLink>,
 }
 
 #[derive(Debug, Serialize, JsonSchema)]
@@ -156,8 +160,9 @@ pub struct WorkspaceSummaryResponse {
     pub is_admin: bool,
     pub group_id: String,
     pub infra_repo_origin: Option<String>,
-    pub quick_links: Option<String>,
+    pub quick_links: Vec<QuickLink>,
     pub version: Option<String>,
+    pub purge_after: Option<DateTime<Utc>>, // set while the workspace is deactivated
 }
 
 #[derive(Debug, Serialize, JsonSchema)]
@@ -171,10 +176,151 @@ pub struct SnapshotsResponse {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct WorkspaceDetailResponse {
     pub name: Option<String>,
-    pub block_positions: Option<String>,
+    pub block_positions: CanvasLayout, // blocks of deleted entities are left out
     pub is_active: bool,
     pub is_admin: bool,
 }
+
+#[derive(Serialize, JsonSchema)]
+pub struct ApiTokenResponse {
+    pub id: i64,
+    pub name: String,
+    pub org_id: String,
+    pub scopes: Vec<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct IssuedApiTokenResponse {
+    pub token: String, // only returned once, at creation or rotation
+    pub details: ApiTokenResponse,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct WebhookResponse {
+    pub id: i64,
+    pub org_id: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct RegisteredWebhookResponse {
+    pub secret: String, // signs the deliveries, only returned at registration
+    pub details: WebhookResponse,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct WebhookDeliveryResponse {
+    pub id: i64,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub status: String, // pending, delivered or failed
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Where a service of the workspace answers in an env
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ServiceLocationResponse {
+    pub identifier: String,
+    pub env: String,
+    pub base_url: String,
+    pub base_url_ws: Option<String>,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ResolvedServicesResponse {
+    pub env: String,
+    pub services: Vec<ServiceLocationResponse>,
+    pub unresolved: Vec<String>, // not registered in the workspace or not deployed in the env
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ManifestChangeResponse {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>, // specs are compared by content hash
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct IngestManifestsResponse {
+    pub kind: String, // service or package
+    pub identifier: String,
+    pub env: String,
+    pub id: Option<i64>, // absent when a dry run would create it
+    pub created: bool,
+    pub dry_run: bool,
+    pub changes: Vec<ManifestChangeResponse>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct DeletedItemResponse {
+    pub kind: String, // service, service_env, package, package_env, dbschema or dbschema_branch
+    pub id: i64,
+    pub name: String,
+    pub parent: Option<String>,
+    pub deleted_at: DateTime<Utc>,
+    pub restorable_until: DateTime<Utc>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct OutboxEventResponse {
+    pub id: i64,
+    pub topic: String,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub dead_lettered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct WorkspaceLifecycleResponse {
+    pub message: String,
+    pub is_active: bool,
+    pub purge_after: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct WorkspaceIdentityResponse {
+    pub slug: String,
+    pub name: Option<String>,
+    pub group_id: String,
+    pub previous_slugs: Vec<String>, // still resolve to this workspace
+}
+
+/// Envelope of every list endpoint, `next_cursor` is absent on the last page
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub total: i64, // items matching the filters across all pages
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct DependencyStatusResponse {
+    pub name: String,
+    pub status: String, // up, down or not_configured
+    pub required: bool, // the service cannot serve requests without it
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ReadinessResponse {
+    pub status: String, // ready, degraded or unavailable
+    pub dependencies: Vec<DependencyStatusResponse>,
+}
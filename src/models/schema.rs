@@ -35,6 +35,7 @@ pub mod schema {
             db_type ->Varchar,
             #[max_length = 10000]
             quick_links ->Nullable<Varchar>,
+            deleted_at ->Nullable<Timestamptz>,
             id ->BigInt,
             
         }
@@ -52,6 +53,9 @@ pub mod schema {
             #[max_length = 50]
             version ->Nullable<Varchar>,
             pipeline_status ->Nullable<Varchar>,
+            pipeline_changed_at ->Nullable<Timestamptz>,
+            last_passed_at ->Nullable<Timestamptz>,
+            deleted_at ->Nullable<Timestamptz>,
             id ->BigInt,
             
         }
@@ -99,6 +103,8 @@ pub mod schema {
             quick_links ->Nullable<Varchar>,
             #[max_length = 150]
             message_queue_schema_id ->Nullable<Varchar>,
+            updated_at ->Timestamptz,
+            deleted_at ->Nullable<Timestamptz>,
             id ->BigInt,
             
         }
@@ -118,6 +124,9 @@ pub mod schema {
             pipeline_status ->Nullable<Varchar>,
             #[max_length = 150]
             base_url_ws ->Nullable<Varchar>,
+            pipeline_changed_at ->Nullable<Timestamptz>,
+            last_passed_at ->Nullable<Timestamptz>,
+            deleted_at ->Nullable<Timestamptz>,
             id ->BigInt,
             
         }
@@ -144,6 +153,7 @@ pub mod schema {
             repo_origin ->Nullable<Varchar>,
             #[max_length = 10000]
             quick_links ->Nullable<Varchar>,
+            deleted_at ->Nullable<Timestamptz>,
             id ->BigInt,
             
         }
@@ -156,6 +166,9 @@ pub mod schema {
             env ->Varchar,
             parent_id ->BigInt,
             pipeline_status ->Nullable<Varchar>,
+            pipeline_changed_at ->Nullable<Timestamptz>,
+            last_passed_at ->Nullable<Timestamptz>,
+            deleted_at ->Nullable<Timestamptz>,
             id ->BigInt,
             
         }
@@ -179,6 +192,8 @@ pub mod schema {
             quick_links ->Nullable<Varchar>,
             #[max_length = 25]
             version ->Nullable<Varchar>,
+            purge_after ->Nullable<Timestamptz>,
+            deleted_at ->Nullable<Timestamptz>,
             id ->BigInt,
             
         }
@@ -197,6 +212,116 @@ pub mod schema {
         }
     }
     
+    table! {
+        api_token (id) {
+            #[max_length = 100]
+            name ->Varchar,
+            #[max_length = 100]
+            organization_id ->Varchar,
+            #[max_length = 50]
+            jti ->Varchar,
+            #[max_length = 1000]
+            scopes_json ->Varchar,
+            #[max_length = 100]
+            created_by ->Nullable<Varchar>,
+            created_at ->Timestamptz,
+            expires_at ->Timestamptz,
+            revoked_at ->Nullable<Timestamptz>,
+            id ->BigInt,
+            
+        }
+    }
+    
+    table! {
+        idempotency_key (id) {
+            #[max_length = 255]
+            key ->Varchar,
+            #[max_length = 200]
+            scope ->Varchar,
+            #[max_length = 64]
+            request_hash ->Varchar,
+            #[max_length = 40000]
+            response_json ->Nullable<Varchar>,
+            created_at ->Timestamptz,
+            id ->BigInt,
+            
+        }
+    }
+    
+    table! {
+        organization_slug_alias (id) {
+            #[max_length = 100]
+            old_slug ->Varchar,
+            organization_id ->BigInt,
+            created_at ->Timestamptz,
+            id ->BigInt,
+            
+        }
+    }
+    
+    table! {
+        outbox_event (id) {
+            #[max_length = 100]
+            organization_id ->Varchar,
+            #[max_length = 100]
+            group_id ->Varchar,
+            #[max_length = 100]
+            topic ->Varchar,
+            #[max_length = 10000]
+            payload ->Varchar,
+            attempts ->Integer,
+            next_attempt_at ->Timestamptz,
+            #[max_length = 2000]
+            last_error ->Nullable<Varchar>,
+            created_at ->Timestamptz,
+            delivered_at ->Nullable<Timestamptz>,
+            dead_lettered_at ->Nullable<Timestamptz>,
+            id ->BigInt,
+            
+        }
+    }
+    
+    table! {
+        webhook (id) {
+            #[max_length = 100]
+            organization_id ->Varchar,
+            #[max_length = 2000]
+            url ->Varchar,
+            #[max_length = 100]
+            secret ->Varchar,
+            #[max_length = 1000]
+            event_types_json ->Varchar,
+            #[max_length = 100]
+            created_by ->Nullable<Varchar>,
+            created_at ->Timestamptz,
+            deleted_at ->Nullable<Timestamptz>,
+            id ->BigInt,
+            
+        }
+    }
+    
+    table! {
+        webhook_delivery (id) {
+            webhook_id ->BigInt,
+            #[max_length = 100]
+            organization_id ->Varchar,
+            #[max_length = 100]
+            event_type ->Varchar,
+            #[max_length = 10000]
+            payload ->Varchar,
+            attempts ->Integer,
+            next_attempt_at ->Timestamptz,
+            response_status ->Nullable<Integer>,
+            #[max_length = 2000]
+            last_error ->Nullable<Varchar>,
+            created_at ->Timestamptz,
+            delivered_at ->Nullable<Timestamptz>,
+            failed_at ->Nullable<Timestamptz>,
+            id ->BigInt,
+            
+        }
+    }
+    
     
         
     
@@ -216,6 +341,12 @@ pub mod schema {
     
         
     
+        diesel::joinable!(organization_slug_alias -> organization (organization_id));
+    
+        
+    
+        diesel::joinable!(webhook_delivery -> webhook (webhook_id));
+    
 
     diesel::allow_tables_to_appear_in_same_query!(
         dbschema,
@@ -227,11 +358,17 @@ pub mod schema {
         package_env,
         organization,
         snapshots,
+        api_token,
+        idempotency_key,
+        organization_slug_alias,
+        outbox_event,
+        webhook,
+        webhook_delivery,
         
     );
 }
 
-use schema::{ dbschema,dbschema_branch,templates,service,service_envs,package,package_env,organization,snapshots, };
+use schema::{ dbschema,dbschema_branch,templates,service,service_envs,package,package_env,organization,snapshots,api_token,idempotency_key,organization_slug_alias,outbox_event,webhook,webhook_delivery, };
 
 
 
@@ -251,6 +388,7 @@ pub struct Dbschema {
     pub repo_origin:Option<String>,
     pub db_type:String,
     pub quick_links:Option<String>,
+    pub deleted_at:Option<DateTime<Utc>>,
     pub id:i64,
     
 }
@@ -268,6 +406,9 @@ pub struct Dbschema_Branch {
     pub updated_at:DateTime<Utc>,
     pub version:Option<String>,
     pub pipeline_status:Option<String>,
+    pub pipeline_changed_at:Option<DateTime<Utc>>,
+    pub last_passed_at:Option<DateTime<Utc>>,
+    pub deleted_at:Option<DateTime<Utc>>,
     pub id:i64,
     
 }
@@ -305,6 +446,8 @@ pub struct Service {
     pub cache_schema_id:Option<String>,
     pub quick_links:Option<String>,
     pub message_queue_schema_id:Option<String>,
+    pub updated_at:DateTime<Utc>,
+    pub deleted_at:Option<DateTime<Utc>>,
     pub id:i64,
     
 }
@@ -323,6 +466,9 @@ pub struct Service_Envs {
     pub version:String,
     pub pipeline_status:Option<String>,
     pub base_url_ws:Option<String>,
+    pub pipeline_changed_at:Option<DateTime<Utc>>,
+    pub last_passed_at:Option<DateTime<Utc>>,
+    pub deleted_at:Option<DateTime<Utc>>,
     pub id:i64,
     
 }
@@ -344,6 +490,7 @@ pub struct Package {
     pub dependencies_json:Option<String>,
     pub repo_origin:Option<String>,
     pub quick_links:Option<String>,
+    pub deleted_at:Option<DateTime<Utc>>,
     pub id:i64,
     
 }
@@ -358,6 +505,9 @@ pub struct Package_Env {
     pub env:String,
     pub parent_id:i64,
     pub pipeline_status:Option<String>,
+    pub pipeline_changed_at:Option<DateTime<Utc>>,
+    pub last_passed_at:Option<DateTime<Utc>>,
+    pub deleted_at:Option<DateTime<Utc>>,
     pub id:i64,
     
 }
@@ -377,6 +527,8 @@ pub struct Organization {
     pub infra_repo_origin:Option<String>,
     pub quick_links:Option<String>,
     pub version:Option<String>,
+    pub purge_after:Option<DateTime<Utc>>,
+    pub deleted_at:Option<DateTime<Utc>>,
     pub id:i64,
     
 }
@@ -396,6 +548,110 @@ pub struct Snapshots {
 }
 
 
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, JsonSchema,Identifiable)]
+
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = api_token)]
+pub struct Api_Token {
+    pub name:String,
+    pub organization_id:String,
+    pub jti:String,
+    pub scopes_json:String,
+    pub created_by:Option<String>,
+    pub created_at:DateTime<Utc>,
+    pub expires_at:DateTime<Utc>,
+    pub revoked_at:Option<DateTime<Utc>>,
+    pub id:i64,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, JsonSchema,Identifiable)]
+
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = idempotency_key)]
+pub struct Idempotency_Key {
+    pub key:String,
+    pub scope:String,
+    pub request_hash:String,
+    pub response_json:Option<String>,
+    pub created_at:DateTime<Utc>,
+    pub id:i64,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, JsonSchema,Identifiable,Associations)]
+#[diesel(belongs_to(Organization, foreign_key = organization_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = organization_slug_alias)]
+pub struct Organization_Slug_Alias {
+    pub old_slug:String,
+    pub organization_id:i64,
+    pub created_at:DateTime<Utc>,
+    pub id:i64,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, JsonSchema,Identifiable)]
+
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = outbox_event)]
+pub struct Outbox_Event {
+    pub organization_id:String,
+    pub group_id:String,
+    pub topic:String,
+    pub payload:String,
+    pub attempts:i32,
+    pub next_attempt_at:DateTime<Utc>,
+    pub last_error:Option<String>,
+    pub created_at:DateTime<Utc>,
+    pub delivered_at:Option<DateTime<Utc>>,
+    pub dead_lettered_at:Option<DateTime<Utc>>,
+    pub id:i64,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, JsonSchema,Identifiable)]
+
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = webhook)]
+pub struct Webhook {
+    pub organization_id:String,
+    pub url:String,
+    pub secret:String,
+    pub event_types_json:String,
+    pub created_by:Option<String>,
+    pub created_at:DateTime<Utc>,
+    pub deleted_at:Option<DateTime<Utc>>,
+    pub id:i64,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, JsonSchema,Identifiable,Associations)]
+#[diesel(belongs_to(Webhook, foreign_key = webhook_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = webhook_delivery)]
+pub struct Webhook_Delivery {
+    pub webhook_id:i64,
+    pub organization_id:String,
+    pub event_type:String,
+    pub payload:String,
+    pub attempts:i32,
+    pub next_attempt_at:DateTime<Utc>,
+    pub response_status:Option<i32>,
+    pub last_error:Option<String>,
+    pub created_at:DateTime<Utc>,
+    pub delivered_at:Option<DateTime<Utc>>,
+    pub failed_at:Option<DateTime<Utc>>,
+    pub id:i64,
+    
+}
+
+
 
 
 #[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, Insertable, JsonSchema)]
@@ -430,6 +686,8 @@ pub struct Dbschema_BranchInsertable {
     pub updated_at:DateTime<Utc>,
     pub version:Option<String>,
     pub pipeline_status:Option<String>,
+    pub pipeline_changed_at:Option<DateTime<Utc>>,
+    pub last_passed_at:Option<DateTime<Utc>>,
     
 }
 
@@ -465,6 +723,7 @@ pub struct ServiceInsertable {
     pub cache_schema_id:Option<String>,
     pub quick_links:Option<String>,
     pub message_queue_schema_id:Option<String>,
+    pub updated_at:DateTime<Utc>,
     
 }
 
@@ -482,6 +741,8 @@ pub struct Service_EnvsInsertable {
     pub version:String,
     pub pipeline_status:Option<String>,
     pub base_url_ws:Option<String>,
+    pub pipeline_changed_at:Option<DateTime<Utc>>,
+    pub last_passed_at:Option<DateTime<Utc>>,
     
 }
 
@@ -515,6 +776,8 @@ pub struct Package_EnvInsertable {
     pub env:String,
     pub parent_id:i64,
     pub pipeline_status:Option<String>,
+    pub pipeline_changed_at:Option<DateTime<Utc>>,
+    pub last_passed_at:Option<DateTime<Utc>>,
     
 }
 
@@ -548,3 +811,101 @@ pub struct SnapshotsInsertable {
     pub organization_id:String,
     
 }
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, Insertable, JsonSchema)]
+
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = api_token)]
+pub struct Api_TokenInsertable {
+    pub name:String,
+    pub organization_id:String,
+    pub jti:String,
+    pub scopes_json:String,
+    pub created_by:Option<String>,
+    pub created_at:DateTime<Utc>,
+    pub expires_at:DateTime<Utc>,
+    pub revoked_at:Option<DateTime<Utc>>,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, Insertable, JsonSchema)]
+
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = idempotency_key)]
+pub struct Idempotency_KeyInsertable {
+    pub key:String,
+    pub scope:String,
+    pub request_hash:String,
+    pub response_json:Option<String>,
+    pub created_at:DateTime<Utc>,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, Insertable, JsonSchema,Associations)]
+#[diesel(belongs_to(Organization, foreign_key = organization_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = organization_slug_alias)]
+pub struct Organization_Slug_AliasInsertable {
+    pub old_slug:String,
+    pub organization_id:i64,
+    pub created_at:DateTime<Utc>,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, Insertable, JsonSchema)]
+
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = outbox_event)]
+pub struct Outbox_EventInsertable {
+    pub organization_id:String,
+    pub group_id:String,
+    pub topic:String,
+    pub payload:String,
+    pub attempts:i32,
+    pub next_attempt_at:DateTime<Utc>,
+    pub last_error:Option<String>,
+    pub created_at:DateTime<Utc>,
+    pub delivered_at:Option<DateTime<Utc>>,
+    pub dead_lettered_at:Option<DateTime<Utc>>,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, Insertable, JsonSchema)]
+
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = webhook)]
+pub struct WebhookInsertable {
+    pub organization_id:String,
+    pub url:String,
+    pub secret:String,
+    pub event_types_json:String,
+    pub created_by:Option<String>,
+    pub created_at:DateTime<Utc>,
+    pub deleted_at:Option<DateTime<Utc>>,
+    
+}
+
+
+#[derive(Queryable, Debug, Clone, Selectable, Serialize, Deserialize, Insertable, JsonSchema,Associations)]
+#[diesel(belongs_to(Webhook, foreign_key = webhook_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = webhook_delivery)]
+pub struct Webhook_DeliveryInsertable {
+    pub webhook_id:i64,
+    pub organization_id:String,
+    pub event_type:String,
+    pub payload:String,
+    pub attempts:i32,
+    pub next_attempt_at:DateTime<Utc>,
+    pub response_status:Option<i32>,
+    pub last_error:Option<String>,
+    pub created_at:DateTime<Utc>,
+    pub delivered_at:Option<DateTime<Utc>>,
+    pub failed_at:Option<DateTime<Utc>>,
+    
+}
@@ -0,0 +1,315 @@
+use crate::db::outbox;
+use crate::db::pool::run_blocking;
+use crate::errors::ApiError;
+use crate::middlewares::api_token_claims::KNOWN_SCOPES;
+use crate::middlewares::groups_owned::GroupOwnerships;
+use crate::models::request::{CreateApiTokenRequest, RotateApiTokenRequest};
+use crate::models::response::{ApiTokenResponse, IssuedApiTokenResponse, Page};
+use crate::models::schema::{Api_Token, Api_TokenInsertable, Organization};
+use crate::routes::pagination::PageRequest;
+use crate::routes::{ensure_active, owned_organization};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use ginger_shared_rs::rocket_models::MessageResponse;
+use ginger_shared_rs::rocket_utils::Claims;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+use serde::Serialize;
+use std::env;
+use uuid::Uuid;
+
+const DEFAULT_TOKEN_VALIDITY_DAYS: i64 = 90;
+const MAX_TOKEN_VALIDITY_DAYS: i64 = 365;
+
+// Mirrors `APIClaims` so the minted tokens are accepted by the existing guards
+#[derive(Serialize)]
+struct IssuedTokenClaims {
+    sub: String,
+    exp: usize,
+    scopes: Vec<String>,
+    group_id: i64,
+    jti: String,
+}
+
+fn to_response(token: Api_Token) -> ApiTokenResponse {
+    ApiTokenResponse {
+        id: token.id,
+        name: token.name,
+        org_id: token.organization_id,
+        scopes: serde_json::from_str(&token.scopes_json).unwrap_or_default(),
+        created_by: token.created_by,
+        created_at: token.created_at,
+        expires_at: token.expires_at,
+        revoked_at: token.revoked_at,
+    }
+}
+
+fn validity(expires_in_days: Option<i64>) -> Result<Duration, ApiError> {
+    let days = expires_in_days.unwrap_or(DEFAULT_TOKEN_VALIDITY_DAYS);
+    if days < 1 || days > MAX_TOKEN_VALIDITY_DAYS {
+        return Err(ApiError::BadRequest(format!(
+            "expires_in_days must be between 1 and {}",
+            MAX_TOKEN_VALIDITY_DAYS
+        )));
+    }
+    Ok(Duration::days(days))
+}
+
+pub fn normalize_scopes(scopes: &[String]) -> Result<Vec<String>, ApiError> {
+    let mut normalized: Vec<String> = scopes
+        .iter()
+        .map(|scope| scope.trim().to_string())
+        .filter(|scope| !scope.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+
+    if normalized.is_empty() {
+        return Err(ApiError::BadRequest(
+            "At least one scope is required".to_string(),
+        ));
+    }
+    if let Some(unknown) = normalized
+        .iter()
+        .find(|scope| !KNOWN_SCOPES.contains(&scope.as_str()))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown scope {}, expected one of {}",
+            unknown,
+            KNOWN_SCOPES.join(", ")
+        )));
+    }
+    Ok(normalized)
+}
+
+fn issue_token(
+    conn: &mut PgConnection,
+    org: &Organization,
+    token_name: String,
+    token_scopes: Vec<String>,
+    valid_for: Duration,
+    creator: Option<String>,
+) -> Result<IssuedApiTokenResponse, ApiError> {
+    use crate::models::schema::schema::api_token::dsl::*;
+
+    let secret = env::var("JWT_SECRET")
+        .map_err(|_| ApiError::Internal("Token signing is not configured".to_string()))?;
+
+    let now = Utc::now();
+    let token_jti = Uuid::new_v4().to_string();
+
+    let new_token = Api_TokenInsertable {
+        name: token_name,
+        organization_id: org.slug.clone(),
+        jti: token_jti.clone(),
+        scopes_json: serde_json::to_string(&token_scopes).unwrap(),
+        created_by: creator,
+        created_at: now,
+        expires_at: now + valid_for,
+        revoked_at: None,
+    };
+
+    let created_token = diesel::insert_into(api_token)
+        .values(&new_token)
+        .get_result::<Api_Token>(conn)
+        .map_err(|_| ApiError::Internal("Error storing API token".to_string()))?;
+
+    // `sub` is the workspace group, which is how `get_current_workspace` resolves the org
+    let token_claims = IssuedTokenClaims {
+        sub: org.group_id.clone(),
+        exp: created_token.expires_at.timestamp() as usize,
+        scopes: token_scopes,
+        group_id: org.id,
+        jti: token_jti,
+    };
+
+    let token = encode(
+        &Header::default(),
+        &token_claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .map_err(|_| ApiError::Internal("Failed to sign API token".to_string()))?;
+
+    Ok(IssuedApiTokenResponse {
+        token,
+        details: to_response(created_token),
+    })
+}
+
+#[openapi()]
+#[post("/workspace/<org_id>/api-tokens", data = "<create_request>")]
+pub async fn create_api_token(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    create_request: Json<CreateApiTokenRequest>,
+    claims: Claims,
+    groups_owned: GroupOwnerships,
+) -> Result<status::Created<Json<IssuedApiTokenResponse>>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        let token_name = create_request.name.trim().to_string();
+        if token_name.is_empty() {
+            return Err(ApiError::BadRequest("Token name is required".to_string()));
+        }
+
+        let token_scopes = normalize_scopes(&create_request.scopes)?;
+        let valid_for = validity(create_request.expires_in_days)?;
+
+        let issued = conn.transaction::<_, ApiError, _>(|conn| {
+            let issued = issue_token(
+                conn,
+                &org,
+                token_name,
+                token_scopes,
+                valid_for,
+                Some(claims.sub),
+            )?;
+            outbox::enqueue_workspace(conn, &org.slug, "api_token_created")?;
+            Ok(issued)
+        })?;
+
+        Ok(status::Created::new("/api-tokens").body(Json(issued)))
+    })
+}
+
+#[openapi()]
+#[get("/workspace/<org_id>/api-tokens?<cursor>&<limit>")]
+pub async fn get_api_tokens(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Json<Page<ApiTokenResponse>>, ApiError> {
+    use crate::models::schema::schema::api_token::dsl::*;
+
+    let page_request = PageRequest::parse(cursor.as_deref(), limit)?;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+
+        // Newest first, ids grow with creation time
+        let mut query = api_token.filter(organization_id.eq(&org.slug)).into_boxed();
+        if let Some(after_id) = page_request.after_id {
+            query = query.filter(id.lt(after_id));
+        }
+
+        let tokens = query
+            .order(id.desc())
+            .limit(page_request.fetch_limit())
+            .load::<Api_Token>(conn)
+            .map_err(|_| ApiError::Internal("Error retrieving API tokens".to_string()))?;
+
+        let total = api_token
+            .filter(organization_id.eq(&org.slug))
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|_| ApiError::Internal("Error counting API tokens".to_string()))?;
+
+        Ok(Json(
+            page_request
+                .page(tokens, total, |token| (None, token.id))
+                .map(to_response),
+        ))
+    })
+}
+
+#[openapi()]
+#[post(
+    "/workspace/<org_id>/api-tokens/<token_id>/rotate",
+    data = "<rotate_request>"
+)]
+pub async fn rotate_api_token(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    token_id: i64,
+    rotate_request: Json<RotateApiTokenRequest>,
+    claims: Claims,
+    groups_owned: GroupOwnerships,
+) -> Result<Json<IssuedApiTokenResponse>, ApiError> {
+    use crate::models::schema::schema::api_token::dsl::*;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        let existing_token = api_token
+            .filter(id.eq(token_id))
+            .filter(organization_id.eq(&org.slug))
+            .filter(revoked_at.is_null())
+            .first::<Api_Token>(conn)
+            .optional()
+            .map_err(|_| ApiError::Internal("Error retrieving API token".to_string()))?
+            .ok_or_else(|| ApiError::NotFound("API token not found".to_string()))?;
+
+        let valid_for = validity(rotate_request.expires_in_days)?;
+
+        let issued = conn.transaction::<_, ApiError, _>(|conn| {
+            // Issue the replacement first so a failure never leaves the caller without a token
+            let issued = issue_token(
+                conn,
+                &org,
+                existing_token.name.clone(),
+                serde_json::from_str(&existing_token.scopes_json).unwrap_or_default(),
+                valid_for,
+                Some(claims.sub),
+            )?;
+
+            diesel::update(api_token.filter(id.eq(existing_token.id)))
+                .set(revoked_at.eq(Some(Utc::now())))
+                .execute(conn)
+                .map_err(|_| ApiError::Internal("Error revoking API token".to_string()))?;
+
+            outbox::enqueue_workspace(conn, &org.slug, "api_token_rotated")?;
+            Ok(issued)
+        })?;
+
+        Ok(Json(issued))
+    })
+}
+
+#[openapi()]
+#[delete("/workspace/<org_id>/api-tokens/<token_id>")]
+pub async fn revoke_api_token(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    token_id: i64,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+) -> Result<Json<MessageResponse>, ApiError> {
+    use crate::models::schema::schema::api_token::dsl::*;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            let updated_rows = diesel::update(
+                api_token
+                    .filter(id.eq(token_id))
+                    .filter(organization_id.eq(&org.slug))
+                    .filter(revoked_at.is_null()),
+            )
+            .set(revoked_at.eq(Some(Utc::now())))
+            .execute(conn)
+            .map_err(|_| ApiError::Internal("Error revoking API token".to_string()))?;
+
+            if updated_rows == 0 {
+                return Err(ApiError::NotFound("API token not found".to_string()));
+            }
+
+            outbox::enqueue_workspace(conn, &org.slug, "api_token_revoked")?;
+            Ok(())
+        })?;
+
+        Ok(Json(MessageResponse {
+            message: "API token revoked".to_string(),
+        }))
+    })
+}
@@ -0,0 +1,440 @@
+use crate::db::pool::run_blocking;
+use crate::errors::ApiError;
+use crate::middlewares::groups::GroupMemberships;
+use crate::middlewares::groups_owned::GroupOwnerships;
+use crate::models::canvas::{parse_layout, stored_layout, BlockLayout, CanvasLayout};
+use crate::models::schema::schema::{dbschema, package, service};
+use crate::routes::member_organization;
+use crate::routes::metadata::parse_identifiers;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use ginger_shared_rs::rocket_utils::Claims;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+use std::collections::{BTreeMap, BTreeSet};
+
+const MAX_CANVAS_BLOCKS: usize = 2000;
+const MAX_CANVAS_GROUPS: usize = 200;
+const CANVAS_ID_MAX_LENGTH: usize = 200;
+const CANVAS_LABEL_MAX_LENGTH: usize = 100;
+const CANVAS_COORDINATE_LIMIT: f64 = 1_000_000.0;
+const CANVAS_SIZE_LIMIT: f64 = 100_000.0;
+// Size of `organization.blocks_positions`
+const CANVAS_LAYOUT_MAX_LENGTH: usize = 40000;
+
+/// Identifiers blocks can refer to, the live services, packages and schemas of the workspace
+pub fn canvas_block_ids(conn: &mut PgConnection, org_id: &str) -> QueryResult<BTreeSet<String>> {
+    let services = service::table
+        .filter(service::organization_id.eq(org_id))
+        .filter(service::deleted_at.is_null())
+        .select(service::identifier)
+        .load::<String>(conn)?;
+    let packages = package::table
+        .filter(package::organization_id.eq(org_id))
+        .filter(package::deleted_at.is_null())
+        .select(package::identifier)
+        .load::<String>(conn)?;
+    let dbschemas = dbschema::table
+        .filter(dbschema::organization_id.eq(org_id))
+        .filter(dbschema::deleted_at.is_null())
+        .select(dbschema::identifier)
+        .load::<Option<String>>(conn)?;
+
+    Ok(services
+        .into_iter()
+        .chain(packages)
+        .chain(dbschemas.into_iter().flatten())
+        .collect())
+}
+
+fn check_position(what: &str, x: f64, y: f64) -> Result<(), ApiError> {
+    let valid = |v: f64| v.is_finite() && v.abs() <= CANVAS_COORDINATE_LIMIT;
+    if valid(x) && valid(y) {
+        return Ok(());
+    }
+    Err(ApiError::BadRequest(format!(
+        "Position of {} must be within ±{}",
+        what, CANVAS_COORDINATE_LIMIT
+    )))
+}
+
+fn check_size(what: &str, size: f64) -> Result<(), ApiError> {
+    if size.is_finite() && size > 0.0 && size <= CANVAS_SIZE_LIMIT {
+        return Ok(());
+    }
+    Err(ApiError::BadRequest(format!(
+        "Width and height of {} must be positive and at most {}",
+        what, CANVAS_SIZE_LIMIT
+    )))
+}
+
+fn check_id(id: &str) -> Result<(), ApiError> {
+    if id.is_empty() || id.len() > CANVAS_ID_MAX_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "Canvas ids must be between 1 and {} characters",
+            CANVAS_ID_MAX_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Checks a layout before it is stored
+pub fn validate_layout(layout: &CanvasLayout) -> Result<(), ApiError> {
+    if layout.blocks.len() > MAX_CANVAS_BLOCKS || layout.groups.len() > MAX_CANVAS_GROUPS {
+        return Err(ApiError::BadRequest(format!(
+            "A canvas holds at most {} blocks and {} groups",
+            MAX_CANVAS_BLOCKS, MAX_CANVAS_GROUPS
+        )));
+    }
+
+    for (id, block) in &layout.blocks {
+        check_id(id)?;
+        check_position(id, block.x, block.y)?;
+        for size in block.width.iter().chain(block.height.iter()) {
+            check_size(id, *size)?;
+        }
+    }
+
+    let mut group_ids = BTreeSet::new();
+    for group in &layout.groups {
+        check_id(&group.id)?;
+        if !group_ids.insert(group.id.as_str()) {
+            return Err(ApiError::BadRequest(format!(
+                "Group id {} is used more than once",
+                group.id
+            )));
+        }
+        if group.label.chars().count() > CANVAS_LABEL_MAX_LENGTH {
+            return Err(ApiError::BadRequest(format!(
+                "Group labels must be at most {} characters",
+                CANVAS_LABEL_MAX_LENGTH
+            )));
+        }
+        check_position(&group.id, group.x, group.y)?;
+        check_size(&group.id, group.width)?;
+        check_size(&group.id, group.height)?;
+    }
+
+    if stored_layout(layout).map_or(0, |stored| stored.len()) > CANVAS_LAYOUT_MAX_LENGTH {
+        return Err(ApiError::BadRequest(
+            "Canvas layout is too large".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Room the layout leaves for a block the portal sizes itself
+const BLOCK_WIDTH: f64 = 280.0;
+const BLOCK_HEIGHT: f64 = 160.0;
+const COLUMN_SPACING: f64 = 360.0;
+const LAYER_SPACING: f64 = 260.0;
+const ORDERING_SWEEPS: usize = 8;
+
+/// Blocks of a workspace and what they depend on
+#[derive(Debug, Default)]
+pub struct CanvasGraph {
+    pub blocks: Vec<String>,          // services, then schemas, then packages
+    pub edges: Vec<(String, String)>, // from the dependent block to its dependency
+}
+
+/// Builds the graph of the live blocks, dependencies on unknown identifiers are left out
+pub fn canvas_graph(conn: &mut PgConnection, org_id: &str) -> Result<CanvasGraph, ApiError> {
+    let services = service::table
+        .filter(service::organization_id.eq(org_id))
+        .filter(service::deleted_at.is_null())
+        .select((
+            service::identifier,
+            service::dependencies_json,
+            service::db_schema_id,
+            service::cache_schema_id,
+            service::message_queue_schema_id,
+        ))
+        .order(service::identifier)
+        .load::<(
+            String,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+        )>(conn)?;
+    let dbschemas = dbschema::table
+        .filter(dbschema::organization_id.eq(org_id))
+        .filter(dbschema::deleted_at.is_null())
+        .select(dbschema::identifier)
+        .order(dbschema::identifier)
+        .load::<Option<String>>(conn)?;
+    let packages = package::table
+        .filter(package::organization_id.eq(org_id))
+        .filter(package::deleted_at.is_null())
+        .select((package::identifier, package::dependencies_json))
+        .order(package::identifier)
+        .load::<(String, Option<String>)>(conn)?;
+
+    let mut graph = CanvasGraph::default();
+    for (identifier, dependencies, db_schema, cache_schema, mq_schema) in services {
+        for dependency in parse_identifiers(dependencies)?
+            .into_iter()
+            .chain(db_schema)
+            .chain(cache_schema)
+            .chain(mq_schema)
+        {
+            graph.edges.push((identifier.clone(), dependency));
+        }
+        graph.blocks.push(identifier);
+    }
+    graph.blocks.extend(dbschemas.into_iter().flatten());
+    for (identifier, dependencies) in packages {
+        for dependency in parse_identifiers(dependencies)? {
+            graph.edges.push((identifier.clone(), dependency));
+        }
+        graph.blocks.push(identifier);
+    }
+
+    let live: BTreeSet<&String> = graph.blocks.iter().collect();
+    let edges = std::mem::take(&mut graph.edges);
+    graph.edges = edges
+        .into_iter()
+        .filter(|(from, to)| from != to && live.contains(to))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    Ok(graph)
+}
+
+// Reverses the edges closing a cycle so every edge points to a deeper layer
+fn acyclic_edges(count: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut outgoing = vec![vec![]; count];
+    for &(from, to) in edges {
+        outgoing[from].push(to);
+    }
+
+    // 0 unvisited, 1 on the DFS path, 2 done
+    let mut state = vec![0u8; count];
+    let mut back_edges = BTreeSet::new();
+    for root in 0..count {
+        if state[root] != 0 {
+            continue;
+        }
+        state[root] = 1;
+        let mut path = vec![(root, 0)];
+        while let Some((node, next)) = path.last_mut() {
+            let node = *node;
+            let Some(&child) = outgoing[node].get(*next) else {
+                state[node] = 2;
+                path.pop();
+                continue;
+            };
+            *next += 1;
+            match state[child] {
+                0 => {
+                    state[child] = 1;
+                    path.push((child, 0));
+                }
+                1 => {
+                    back_edges.insert((node, child));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    edges
+        .iter()
+        .map(|&(from, to)| {
+            if back_edges.contains(&(from, to)) {
+                (to, from)
+            } else {
+                (from, to)
+            }
+        })
+        .collect()
+}
+
+// Longest path layering, blocks without dependents sit on the first layer
+fn assign_layers(count: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut incoming = vec![0; count];
+    let mut outgoing = vec![vec![]; count];
+    for &(from, to) in edges {
+        incoming[to] += 1;
+        outgoing[from].push(to);
+    }
+
+    let mut layers = vec![0; count];
+    let mut ready: Vec<usize> = (0..count).filter(|&node| incoming[node] == 0).collect();
+    while let Some(node) = ready.pop() {
+        for &child in &outgoing[node] {
+            layers[child] = layers[child].max(layers[node] + 1);
+            incoming[child] -= 1;
+            if incoming[child] == 0 {
+                ready.push(child);
+            }
+        }
+    }
+    layers
+}
+
+fn footprint(block: &BlockLayout) -> (f64, f64) {
+    (
+        block.width.unwrap_or(BLOCK_WIDTH),
+        block.height.unwrap_or(BLOCK_HEIGHT),
+    )
+}
+
+fn overlaps(a: &BlockLayout, b: &BlockLayout) -> bool {
+    let (a_width, a_height) = footprint(a);
+    let (b_width, b_height) = footprint(b);
+    a.x < b.x + b_width && b.x < a.x + a_width && a.y < b.y + b_height && b.y < a.y + a_height
+}
+
+/// Lays the blocks out in layers following their dependencies, Sugiyama style. Blocks already
+/// in `pinned` keep their place and order their layer, the others are moved right until they
+/// clear them. Groups are kept as they are
+pub fn auto_layout(graph: &CanvasGraph, pinned: &CanvasLayout) -> CanvasLayout {
+    let index: BTreeMap<&str, usize> = graph
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(position, id)| (id.as_str(), position))
+        .collect();
+    let edges: Vec<(usize, usize)> = graph
+        .edges
+        .iter()
+        .filter_map(|(from, to)| Some((*index.get(from.as_str())?, *index.get(to.as_str())?)))
+        .filter(|(from, to)| from != to)
+        .collect();
+    let edges = acyclic_edges(graph.blocks.len(), &edges);
+    let mut layers = assign_layers(graph.blocks.len(), &edges);
+
+    // Edges spanning several layers go through a virtual node on each layer they cross
+    let mut upper = vec![vec![]; graph.blocks.len()];
+    let mut lower = vec![vec![]; graph.blocks.len()];
+    for (from, to) in edges {
+        let mut previous = from;
+        for layer in layers[from] + 1..layers[to] {
+            let virtual_node = layers.len();
+            layers.push(layer);
+            upper.push(vec![previous]);
+            lower.push(vec![]);
+            lower[previous].push(virtual_node);
+            previous = virtual_node;
+        }
+        upper[to].push(previous);
+        lower[previous].push(to);
+    }
+
+    let layer_count = layers.iter().max().map_or(0, |deepest| deepest + 1);
+    let mut rows: Vec<Vec<usize>> = vec![vec![]; layer_count];
+    for (node, &layer) in layers.iter().enumerate() {
+        rows[layer].push(node);
+    }
+    // Pinned blocks seed the order so the result reads like the canvas the user arranged
+    let seed = |node: usize| {
+        graph
+            .blocks
+            .get(node)
+            .and_then(|id| pinned.blocks.get(id))
+            .map_or(f64::INFINITY, |block| block.x)
+    };
+    for row in &mut rows {
+        row.sort_by(|a, b| seed(*a).total_cmp(&seed(*b)).then(a.cmp(b)));
+    }
+
+    // Barycenter ordering, sweeping down then up to reduce crossings
+    let mut position = vec![0.0; layers.len()];
+    for row in &rows {
+        for (slot, &node) in row.iter().enumerate() {
+            position[node] = slot as f64;
+        }
+    }
+    for sweep in 0..ORDERING_SWEEPS {
+        let downward = sweep % 2 == 0;
+        let order: Vec<usize> = if downward {
+            (1..layer_count).collect()
+        } else {
+            (0..layer_count.saturating_sub(1)).rev().collect()
+        };
+        for layer in order {
+            let neighbours = if downward { &upper } else { &lower };
+            let barycenter = |node: usize| {
+                let linked = &neighbours[node];
+                if linked.is_empty() {
+                    return position[node];
+                }
+                linked.iter().map(|&other| position[other]).sum::<f64>() / linked.len() as f64
+            };
+            let mut keyed: Vec<(f64, usize)> = rows[layer]
+                .iter()
+                .map(|&node| (barycenter(node), node))
+                .collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            rows[layer] = keyed.into_iter().map(|(_, node)| node).collect();
+            for (slot, &node) in rows[layer].iter().enumerate() {
+                position[node] = slot as f64;
+            }
+        }
+    }
+
+    // Rows are centered on the widest one
+    let widest = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut layout = CanvasLayout {
+        blocks: BTreeMap::new(),
+        groups: pinned.groups.clone(),
+    };
+    let mut placed: Vec<BlockLayout> = vec![];
+    for (layer, row) in rows.iter().enumerate() {
+        let offset = (widest - row.len()) as f64 / 2.0;
+        for (slot, &node) in row.iter().enumerate() {
+            let Some(id) = graph.blocks.get(node) else {
+                continue;
+            };
+            if let Some(block) = pinned.blocks.get(id) {
+                layout.blocks.insert(id.clone(), block.clone());
+                continue;
+            }
+            let mut block = BlockLayout {
+                x: (offset + slot as f64) * COLUMN_SPACING,
+                y: layer as f64 * LAYER_SPACING,
+                width: None,
+                height: None,
+                collapsed: false,
+            };
+            while pinned
+                .blocks
+                .values()
+                .chain(&placed)
+                .any(|other| overlaps(&block, other))
+            {
+                block.x += COLUMN_SPACING;
+            }
+            placed.push(block.clone());
+            layout.blocks.insert(id.clone(), block);
+        }
+    }
+    layout
+}
+
+/// Computes a layout from the dependency graph, saving it is left to `update_block_positions`.
+/// Blocks already placed stay where they are unless `reset` is set
+#[openapi()]
+#[get("/workspace/<org_id>/canvas/auto-layout?<reset>")]
+pub async fn get_auto_layout(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    reset: Option<bool>,
+    _claims: Claims,
+    groups: GroupMemberships,
+    groups_owned: GroupOwnerships,
+) -> Result<Json<CanvasLayout>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = member_organization(conn, &org_id, &groups.0, &groups_owned.0)?;
+        let graph = canvas_graph(conn, &org.slug)?;
+
+        let mut pinned = parse_layout(org.blocks_positions.as_deref());
+        pinned.prune(&graph.blocks.iter().cloned().collect());
+        if reset.unwrap_or(false) {
+            pinned.blocks.clear();
+        }
+        Ok(Json(auto_layout(&graph, &pinned)))
+    })
+}
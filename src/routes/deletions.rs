@@ -0,0 +1,643 @@
+use crate::db::outbox;
+use crate::db::pool::run_blocking;
+use crate::db::redis::{invalidate, RedisPoolState};
+use crate::db::soft_delete;
+use crate::errors::ApiError;
+use crate::middlewares::groups_owned::GroupOwnerships;
+use crate::models::response::{DeletedItemResponse, Page};
+use crate::models::schema::schema::{
+    dbschema, dbschema_branch, organization, package, package_env, service, service_envs,
+};
+use crate::routes::pagination::PageRequest;
+use crate::routes::{ensure_active, owned_organization};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use ginger_shared_rs::rocket_models::MessageResponse;
+use ginger_shared_rs::rocket_utils::Claims;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+use std::cmp::Reverse;
+
+// `deleted_at` of the row when it exists in the workspace, it must still be within retention
+pub fn restorable(
+    deleted: Option<Option<DateTime<Utc>>>,
+    cutoff: DateTime<Utc>,
+) -> Result<DateTime<Utc>, ApiError> {
+    match deleted {
+        Some(Some(at)) if at >= cutoff => Ok(at),
+        Some(Some(_)) => Err(ApiError::NotFound(
+            "The item is past the retention window and can no longer be restored".to_string(),
+        )),
+        _ => Err(ApiError::NotFound("Deleted item not found".to_string())),
+    }
+}
+
+/// Schemas registered before identifiers were required have none, their id stands in for it
+pub fn schema_identifier(identifier: Option<String>, id: i64) -> String {
+    identifier.unwrap_or_else(|| id.to_string())
+}
+
+fn deleted(message: &str) -> Json<MessageResponse> {
+    Json(MessageResponse {
+        message: message.to_string(),
+    })
+}
+
+#[openapi()]
+#[delete("/workspace/<org_id>/services/<service_identifier>")]
+pub async fn delete_service(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    service_identifier: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            let service_id = service::table
+                .filter(service::organization_id.eq(&org.slug))
+                .filter(service::identifier.eq(&service_identifier))
+                .filter(service::deleted_at.is_null())
+                .select(service::id)
+                .for_update()
+                .first::<i64>(conn)
+                .optional()?
+                .ok_or_else(|| ApiError::NotFound("Service not found".to_string()))?;
+
+            soft_delete::delete_service(conn, service_id, Utc::now())?;
+            outbox::enqueue_catalog(conn, &org.slug, "service", &service_identifier, "deleted")?;
+            Ok(())
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &org.slug);
+
+        Ok(deleted("Service and its environments deleted"))
+    })
+}
+
+#[openapi()]
+#[delete("/workspace/<org_id>/services/<service_identifier>/envs/<env>")]
+pub async fn delete_service_env(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    service_identifier: String,
+    env: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            let updated_rows = diesel::update(
+                service_envs::table
+                    .filter(service_envs::env.eq(&env))
+                    .filter(service_envs::deleted_at.is_null())
+                    .filter(
+                        service_envs::parent_id.eq_any(
+                            service::table
+                                .filter(service::organization_id.eq(&org.slug))
+                                .filter(service::identifier.eq(&service_identifier))
+                                .filter(service::deleted_at.is_null())
+                                .select(service::id),
+                        ),
+                    ),
+            )
+            .set(service_envs::deleted_at.eq(Some(Utc::now())))
+            .execute(conn)?;
+
+            if updated_rows == 0 {
+                return Err(ApiError::NotFound(
+                    "Service environment not found".to_string(),
+                ));
+            }
+
+            let path = format!("{}/{}", service_identifier, env);
+            outbox::enqueue_catalog(conn, &org.slug, "service_env", &path, "deleted")?;
+            Ok(())
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &org.slug);
+
+        Ok(deleted("Service environment deleted"))
+    })
+}
+
+#[openapi()]
+#[delete("/workspace/<org_id>/packages/<package_identifier>")]
+pub async fn delete_package(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    package_identifier: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            let package_id = package::table
+                .filter(package::organization_id.eq(&org.slug))
+                .filter(package::identifier.eq(&package_identifier))
+                .filter(package::deleted_at.is_null())
+                .select(package::id)
+                .for_update()
+                .first::<i64>(conn)
+                .optional()?
+                .ok_or_else(|| ApiError::NotFound("Package not found".to_string()))?;
+
+            soft_delete::delete_package(conn, package_id, Utc::now())?;
+            outbox::enqueue_catalog(conn, &org.slug, "package", &package_identifier, "deleted")?;
+            Ok(())
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &org.slug);
+
+        Ok(deleted("Package and its environments deleted"))
+    })
+}
+
+#[openapi()]
+#[delete("/workspace/<org_id>/packages/<package_identifier>/envs/<env>")]
+pub async fn delete_package_env(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    package_identifier: String,
+    env: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            let updated_rows = diesel::update(
+                package_env::table
+                    .filter(package_env::env.eq(&env))
+                    .filter(package_env::deleted_at.is_null())
+                    .filter(
+                        package_env::parent_id.eq_any(
+                            package::table
+                                .filter(package::organization_id.eq(&org.slug))
+                                .filter(package::identifier.eq(&package_identifier))
+                                .filter(package::deleted_at.is_null())
+                                .select(package::id),
+                        ),
+                    ),
+            )
+            .set(package_env::deleted_at.eq(Some(Utc::now())))
+            .execute(conn)?;
+
+            if updated_rows == 0 {
+                return Err(ApiError::NotFound(
+                    "Package environment not found".to_string(),
+                ));
+            }
+
+            let path = format!("{}/{}", package_identifier, env);
+            outbox::enqueue_catalog(conn, &org.slug, "package_env", &path, "deleted")?;
+            Ok(())
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &org.slug);
+
+        Ok(deleted("Package environment deleted"))
+    })
+}
+
+#[openapi()]
+#[delete("/workspace/<org_id>/dbschemas/<schema_id>")]
+pub async fn delete_dbschema(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    schema_id: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            let dbschema_id = dbschema::table
+                .filter(dbschema::organization_id.eq(&org.slug))
+                .filter(dbschema::identifier.eq(&schema_id))
+                .filter(dbschema::deleted_at.is_null())
+                .select(dbschema::id)
+                .for_update()
+                .first::<i64>(conn)
+                .optional()?
+                .ok_or_else(|| ApiError::NotFound("Dbschema not found".to_string()))?;
+
+            soft_delete::delete_dbschema(conn, dbschema_id, Utc::now())?;
+            outbox::enqueue_catalog(conn, &org.slug, "dbschema", &schema_id, "deleted")?;
+            Ok(())
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &org.slug);
+
+        Ok(deleted("Dbschema and its branches deleted"))
+    })
+}
+
+#[openapi()]
+#[delete("/workspace/<org_id>/dbschemas/<schema_id>/branches/<branch_name>")]
+pub async fn delete_dbschema_branch(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    schema_id: String,
+    branch_name: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            let updated_rows = diesel::update(
+                dbschema_branch::table
+                    .filter(dbschema_branch::branch_name.eq(&branch_name))
+                    .filter(dbschema_branch::deleted_at.is_null())
+                    .filter(
+                        dbschema_branch::parent_id.eq_any(
+                            dbschema::table
+                                .filter(dbschema::organization_id.eq(&org.slug))
+                                .filter(dbschema::identifier.eq(&schema_id))
+                                .filter(dbschema::deleted_at.is_null())
+                                .select(dbschema::id),
+                        ),
+                    ),
+            )
+            .set(dbschema_branch::deleted_at.eq(Some(Utc::now())))
+            .execute(conn)?;
+
+            if updated_rows == 0 {
+                return Err(ApiError::NotFound("Dbschema branch not found".to_string()));
+            }
+
+            let path = format!("{}/{}", schema_id, branch_name);
+            outbox::enqueue_catalog(conn, &org.slug, "dbschema_branch", &path, "deleted")?;
+            Ok(())
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &org.slug);
+
+        Ok(deleted("Dbschema branch deleted"))
+    })
+}
+
+#[openapi()]
+#[get("/workspace/<org_id>/deleted-items?<cursor>&<limit>")]
+pub async fn get_deleted_items(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Json<Page<DeletedItemResponse>>, ApiError> {
+    let page_request = PageRequest::parse(cursor.as_deref(), limit)?;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        let cutoff = soft_delete::retention_cutoff();
+        let retention = soft_delete::retention();
+
+        // (kind, id, name, parent, deleted_at) of every restorable row, children are only listed
+        // while their parent is live since restoring the parent brings them back
+        let mut items: Vec<(&str, i64, String, Option<String>, DateTime<Utc>)> = vec![];
+
+        items.extend(
+            service::table
+                .filter(service::organization_id.eq(&org.slug))
+                .filter(service::deleted_at.ge(cutoff))
+                .select((service::id, service::identifier, service::deleted_at))
+                .load::<(i64, String, Option<DateTime<Utc>>)>(conn)?
+                .into_iter()
+                .filter_map(|(id, name, at)| Some(("service", id, name, None, at?))),
+        );
+
+        items.extend(
+            service_envs::table
+                .inner_join(service::table)
+                .filter(service::organization_id.eq(&org.slug))
+                .filter(service::deleted_at.is_null())
+                .filter(service_envs::deleted_at.ge(cutoff))
+                .select((
+                    service_envs::id,
+                    service_envs::env,
+                    service::identifier,
+                    service_envs::deleted_at,
+                ))
+                .load::<(i64, String, String, Option<DateTime<Utc>>)>(conn)?
+                .into_iter()
+                .filter_map(|(id, name, parent, at)| {
+                    Some(("service_env", id, name, Some(parent), at?))
+                }),
+        );
+
+        items.extend(
+            package::table
+                .filter(package::organization_id.eq(&org.slug))
+                .filter(package::deleted_at.ge(cutoff))
+                .select((package::id, package::identifier, package::deleted_at))
+                .load::<(i64, String, Option<DateTime<Utc>>)>(conn)?
+                .into_iter()
+                .filter_map(|(id, name, at)| Some(("package", id, name, None, at?))),
+        );
+
+        items.extend(
+            package_env::table
+                .inner_join(package::table)
+                .filter(package::organization_id.eq(&org.slug))
+                .filter(package::deleted_at.is_null())
+                .filter(package_env::deleted_at.ge(cutoff))
+                .select((
+                    package_env::id,
+                    package_env::env,
+                    package::identifier,
+                    package_env::deleted_at,
+                ))
+                .load::<(i64, String, String, Option<DateTime<Utc>>)>(conn)?
+                .into_iter()
+                .filter_map(|(id, name, parent, at)| {
+                    Some(("package_env", id, name, Some(parent), at?))
+                }),
+        );
+
+        items.extend(
+            dbschema::table
+                .filter(dbschema::organization_id.eq(&org.slug))
+                .filter(dbschema::deleted_at.ge(cutoff))
+                .select((dbschema::id, dbschema::name, dbschema::deleted_at))
+                .load::<(i64, String, Option<DateTime<Utc>>)>(conn)?
+                .into_iter()
+                .filter_map(|(id, name, at)| Some(("dbschema", id, name, None, at?))),
+        );
+
+        items.extend(
+            dbschema_branch::table
+                .inner_join(dbschema::table)
+                .filter(dbschema::organization_id.eq(&org.slug))
+                .filter(dbschema::deleted_at.is_null())
+                .filter(dbschema_branch::deleted_at.ge(cutoff))
+                .select((
+                    dbschema_branch::id,
+                    dbschema_branch::branch_name,
+                    dbschema::name,
+                    dbschema_branch::deleted_at,
+                ))
+                .load::<(i64, String, String, Option<DateTime<Utc>>)>(conn)?
+                .into_iter()
+                .filter_map(|(id, name, parent, at)| {
+                    Some(("dbschema_branch", id, name, Some(parent), at?))
+                }),
+        );
+
+        // Newest deletions first, the cursor key carries `deleted_at|kind` of the last item returned
+        items.sort_by(|a, b| b.4.cmp(&a.4).then(a.0.cmp(b.0)).then(a.1.cmp(&b.1)));
+        let total = items.len() as i64;
+
+        if let (Some(key), Some(after_id)) = (&page_request.after_key, page_request.after_id) {
+            let (after_at, after_kind) = key
+                .split_once('|')
+                .and_then(|(at, kind)| {
+                    Some((
+                        DateTime::parse_from_rfc3339(at).ok()?.with_timezone(&Utc),
+                        kind,
+                    ))
+                })
+                .ok_or_else(|| ApiError::BadRequest("cursor is malformed".to_string()))?;
+            items.retain(|item| {
+                (Reverse(item.4), item.0, item.1) > (Reverse(after_at), after_kind, after_id)
+            });
+        }
+        items.truncate(page_request.fetch_limit() as usize);
+
+        Ok(Json(
+            page_request
+                .page(items, total, |item| {
+                    (Some(format!("{}|{}", item.4.to_rfc3339(), item.0)), item.1)
+                })
+                .map(|(kind, id, name, parent, deleted_at)| DeletedItemResponse {
+                    kind: kind.to_string(),
+                    id,
+                    name,
+                    parent,
+                    deleted_at,
+                    restorable_until: deleted_at + retention,
+                }),
+        ))
+    })
+}
+
+#[openapi()]
+#[post("/workspace/<org_id>/deleted-items/<kind>/<item_id>/restore")]
+pub async fn restore_deleted_item(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    kind: String,
+    item_id: i64,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+        let cutoff = soft_delete::retention_cutoff();
+        let parent_deleted = |what: &str| {
+            ApiError::Conflict(format!(
+                "The {} it belongs to is deleted, restore it first",
+                what
+            ))
+        };
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            // Same identifiers as the deleted events, so subscribers can match them up
+            let identifier = match kind.as_str() {
+                "service" => {
+                    let row = service::table
+                        .find(item_id)
+                        .filter(service::organization_id.eq(&org.slug))
+                        .select((service::deleted_at, service::identifier))
+                        .for_update()
+                        .first::<(Option<DateTime<Utc>>, String)>(conn)
+                        .optional()?;
+                    let at = restorable(row.as_ref().map(|(at, _)| *at), cutoff)?;
+                    soft_delete::restore_service(conn, item_id, at)?;
+                    Ok(row.map(|(_, identifier)| identifier).unwrap_or_default())
+                }
+                "service_env" => {
+                    let row = service_envs::table
+                        .inner_join(service::table)
+                        .filter(service_envs::id.eq(item_id))
+                        .filter(service::organization_id.eq(&org.slug))
+                        .select((
+                            service_envs::deleted_at,
+                            service::deleted_at,
+                            service::identifier,
+                            service_envs::env,
+                        ))
+                        .first::<(Option<DateTime<Utc>>, Option<DateTime<Utc>>, String, String)>(
+                            conn,
+                        )
+                        .optional()?;
+                    restorable(row.as_ref().map(|(at, ..)| *at), cutoff)?;
+                    let (_, parent, identifier, env) = row.unwrap_or_default();
+                    if parent.is_some() {
+                        return Err(parent_deleted("service"));
+                    }
+                    diesel::update(service_envs::table.find(item_id))
+                        .set(service_envs::deleted_at.eq(None::<DateTime<Utc>>))
+                        .execute(conn)?;
+                    Ok(format!("{}/{}", identifier, env))
+                }
+                "package" => {
+                    let row = package::table
+                        .find(item_id)
+                        .filter(package::organization_id.eq(&org.slug))
+                        .select((package::deleted_at, package::identifier))
+                        .for_update()
+                        .first::<(Option<DateTime<Utc>>, String)>(conn)
+                        .optional()?;
+                    let at = restorable(row.as_ref().map(|(at, _)| *at), cutoff)?;
+                    soft_delete::restore_package(conn, item_id, at)?;
+                    Ok(row.map(|(_, identifier)| identifier).unwrap_or_default())
+                }
+                "package_env" => {
+                    let row = package_env::table
+                        .inner_join(package::table)
+                        .filter(package_env::id.eq(item_id))
+                        .filter(package::organization_id.eq(&org.slug))
+                        .select((
+                            package_env::deleted_at,
+                            package::deleted_at,
+                            package::identifier,
+                            package_env::env,
+                        ))
+                        .first::<(Option<DateTime<Utc>>, Option<DateTime<Utc>>, String, String)>(
+                            conn,
+                        )
+                        .optional()?;
+                    restorable(row.as_ref().map(|(at, ..)| *at), cutoff)?;
+                    let (_, parent, identifier, env) = row.unwrap_or_default();
+                    if parent.is_some() {
+                        return Err(parent_deleted("package"));
+                    }
+                    diesel::update(package_env::table.find(item_id))
+                        .set(package_env::deleted_at.eq(None::<DateTime<Utc>>))
+                        .execute(conn)?;
+                    Ok(format!("{}/{}", identifier, env))
+                }
+                "dbschema" => {
+                    let row = dbschema::table
+                        .find(item_id)
+                        .filter(dbschema::organization_id.eq(&org.slug))
+                        .select((dbschema::deleted_at, dbschema::identifier))
+                        .for_update()
+                        .first::<(Option<DateTime<Utc>>, Option<String>)>(conn)
+                        .optional()?;
+                    let at = restorable(row.as_ref().map(|(at, _)| *at), cutoff)?;
+                    soft_delete::restore_dbschema(conn, item_id, at)?;
+                    let identifier = row.and_then(|(_, identifier)| identifier);
+                    Ok(schema_identifier(identifier, item_id))
+                }
+                "dbschema_branch" => {
+                    let row = dbschema_branch::table
+                        .inner_join(dbschema::table)
+                        .filter(dbschema_branch::id.eq(item_id))
+                        .filter(dbschema::organization_id.eq(&org.slug))
+                        .select((
+                            dbschema_branch::deleted_at,
+                            dbschema::deleted_at,
+                            dbschema::id,
+                            dbschema::identifier,
+                            dbschema_branch::branch_name,
+                        ))
+                        .first::<(
+                            Option<DateTime<Utc>>,
+                            Option<DateTime<Utc>>,
+                            i64,
+                            Option<String>,
+                            String,
+                        )>(conn)
+                        .optional()?;
+                    restorable(row.as_ref().map(|(at, ..)| *at), cutoff)?;
+                    let (_, parent, schema_id, identifier, branch_name) = row.unwrap_or_default();
+                    let identifier = schema_identifier(identifier, schema_id);
+                    if parent.is_some() {
+                        return Err(parent_deleted("dbschema"));
+                    }
+                    diesel::update(dbschema_branch::table.find(item_id))
+                        .set(dbschema_branch::deleted_at.eq(None::<DateTime<Utc>>))
+                        .execute(conn)?;
+                    Ok(format!("{}/{}", identifier, branch_name))
+                }
+                _ => Err(ApiError::NotFound(format!("Unknown item kind {}", kind))),
+            }?;
+
+            outbox::enqueue_catalog(conn, &org.slug, &kind, &identifier, "restored")?;
+            Ok(())
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &org.slug);
+
+        Ok(deleted("Item restored"))
+    })
+}
+
+#[openapi()]
+#[post("/manage-workspace/<org_id>/restore")]
+pub async fn restore_workspace(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let cutoff = soft_delete::retention_cutoff();
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            let workspace = organization::table
+                .filter(organization::slug.eq(&org_id))
+                .select((organization::group_id, organization::deleted_at))
+                .for_update()
+                .first::<(String, Option<DateTime<Utc>>)>(conn)
+                .optional()?;
+
+            if let Some((group_id, _)) = &workspace {
+                if !groups_owned.0.contains(group_id) {
+                    return Err(ApiError::Forbidden(
+                        "Permission Denied: Only workspace owners can restore it.".to_string(),
+                    ));
+                }
+            }
+
+            let at = restorable(workspace.map(|(_, at)| at), cutoff)?;
+            soft_delete::restore_workspace(conn, &org_id, at)?;
+            outbox::enqueue_workspace(conn, &org_id, "restored")?;
+            Ok(())
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &org_id);
+
+        Ok(deleted("Workspace restored, its API tokens stay revoked"))
+    })
+}
@@ -0,0 +1,155 @@
+use crate::db::blobs::content_hash;
+use crate::db::pool::run_blocking;
+use crate::db::redis::{cached_for, discovery_cache_ttl, RedisPoolState};
+use crate::errors::ApiError;
+use crate::middlewares::api_token_claims::{ActiveAPIClaims, SCOPE_READ};
+use crate::middlewares::conditional::{etag, ETagged, IfNoneMatch};
+use crate::models::response::{ResolvedServicesResponse, ServiceLocationResponse};
+use crate::models::schema::schema::{service, service_envs};
+use crate::routes::canonical_slug;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+
+const MAX_RESOLVED_IDENTIFIERS: usize = 100;
+
+fn load_locations(
+    conn: &mut PgConnection,
+    org_id: &str,
+    env: &str,
+    identifiers: &[String],
+) -> Result<ResolvedServicesResponse, ApiError> {
+    let services = service::table
+        .inner_join(service_envs::table)
+        .filter(service::organization_id.eq(org_id))
+        .filter(service::identifier.eq_any(identifiers))
+        .filter(service::deleted_at.is_null())
+        .filter(service_envs::env.eq(env))
+        .filter(service_envs::deleted_at.is_null())
+        .order(service::identifier.asc())
+        .select((
+            service::identifier,
+            service_envs::base_url,
+            service_envs::base_url_ws,
+            service_envs::version,
+        ))
+        .load::<(String, String, Option<String>, String)>(conn)?
+        .into_iter()
+        .map(
+            |(identifier, base_url, base_url_ws, version)| ServiceLocationResponse {
+                identifier,
+                env: env.to_string(),
+                base_url,
+                base_url_ws,
+                version,
+            },
+        )
+        .collect::<Vec<_>>();
+
+    let unresolved = identifiers
+        .iter()
+        .filter(|identifier| !services.iter().any(|s| &s.identifier == *identifier))
+        .cloned()
+        .collect();
+
+    Ok(ResolvedServicesResponse {
+        env: env.to_string(),
+        services,
+        unresolved,
+    })
+}
+
+/// Locations are cached per workspace until the next publish invalidates them
+fn resolve(
+    rdb: &Pool<ConnectionManager<PgConnection>>,
+    cache: Option<&RedisPoolState>,
+    claims: &ActiveAPIClaims,
+    org_id: &str,
+    env: &str,
+    mut identifiers: Vec<String>,
+) -> Result<ResolvedServicesResponse, ApiError> {
+    identifiers.sort();
+    identifiers.dedup();
+    if identifiers.is_empty() || identifiers.len() > MAX_RESOLVED_IDENTIFIERS {
+        return Err(ApiError::BadRequest(format!(
+            "Between 1 and {} identifiers can be resolved at once",
+            MAX_RESOLVED_IDENTIFIERS
+        )));
+    }
+
+    let org_id = run_blocking(rdb, |conn| {
+        let org_id = canonical_slug(conn, org_id)?;
+        claims.authorize(conn, &org_id, SCOPE_READ)?;
+        Ok(org_id)
+    })?;
+    let cache_key = format!(
+        "discovery:{}:{}",
+        env,
+        content_hash(&serde_json::to_string(&identifiers).unwrap_or_default())
+    );
+
+    cached_for(cache, &org_id, &cache_key, discovery_cache_ttl(), || {
+        run_blocking(rdb, |conn| load_locations(conn, &org_id, env, &identifiers))
+    })
+}
+
+/// Base URLs and version of a service in an env, without its spec
+#[openapi()]
+#[get("/discovery/<org_id>/<service_identifier>/<env>")]
+pub fn get_service_location(
+    org_id: String,
+    service_identifier: String,
+    env: String,
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    claims: ActiveAPIClaims,
+    cache: Option<&State<RedisPoolState>>,
+    if_none_match: IfNoneMatch,
+) -> Result<ETagged<Json<ServiceLocationResponse>>, ApiError> {
+    let location = resolve(
+        rdb,
+        cache.map(|c| c.inner()),
+        &claims,
+        &org_id,
+        &env,
+        vec![service_identifier],
+    )?
+    .services
+    .pop()
+    .ok_or_else(|| ApiError::NotFound("Service environment not found".to_string()))?;
+
+    let revision = etag(&location);
+    if if_none_match.matches(&revision) {
+        return Ok(ETagged::NotModified(revision));
+    }
+    Ok(ETagged::Body(revision, Json(location)))
+}
+
+/// Same as `get_service_location` for several services, `?identifiers=a&identifiers=b`
+#[openapi()]
+#[get("/discovery/<org_id>/<env>?<identifiers>")]
+pub fn resolve_services(
+    org_id: String,
+    env: String,
+    identifiers: Vec<String>,
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    claims: ActiveAPIClaims,
+    cache: Option<&State<RedisPoolState>>,
+    if_none_match: IfNoneMatch,
+) -> Result<ETagged<Json<ResolvedServicesResponse>>, ApiError> {
+    let resolved = resolve(
+        rdb,
+        cache.map(|c| c.inner()),
+        &claims,
+        &org_id,
+        &env,
+        identifiers,
+    )?;
+
+    let revision = etag(&resolved);
+    if if_none_match.matches(&revision) {
+        return Ok(ETagged::NotModified(revision));
+    }
+    Ok(ETagged::Body(revision, Json(resolved)))
+}
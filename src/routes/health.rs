@@ -0,0 +1,186 @@
+use crate::db::pool::blocking;
+use crate::db::redis::RedisPoolState;
+use crate::models::response::{DependencyStatusResponse, ReadinessResponse};
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::{PgConnection, RunQueryDsl};
+use futures::join;
+use ginger_shared_rs::rocket_models::MessageResponse;
+use mongodb::bson::doc;
+use mongodb::Database;
+use r2d2_redis::redis;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::tokio::time::timeout;
+use rocket::State;
+use rocket_okapi::openapi;
+use std::env;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+// Probes run every few seconds, a dependency slower than this is reported down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn measured(
+    name: &str,
+    required: bool,
+    started: Instant,
+    result: Result<(), String>,
+) -> DependencyStatusResponse {
+    DependencyStatusResponse {
+        name: name.to_string(),
+        status: if result.is_ok() { "up" } else { "down" }.to_string(),
+        required,
+        latency_ms: Some(started.elapsed().as_millis() as u64),
+        error: result.err(),
+    }
+}
+
+fn unmeasured(name: &str, status: &str, error: Option<String>) -> DependencyStatusResponse {
+    DependencyStatusResponse {
+        name: name.to_string(),
+        status: status.to_string(),
+        required: false,
+        latency_ms: None,
+        error,
+    }
+}
+
+async fn timed(
+    name: &str,
+    required: bool,
+    check: impl Future<Output = Result<(), String>>,
+) -> DependencyStatusResponse {
+    let started = Instant::now();
+    let result = match timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("Timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    measured(name, required, started, result)
+}
+
+fn check_postgres(rdb: &Pool<ConnectionManager<PgConnection>>) -> Result<(), String> {
+    blocking(|| {
+        let mut conn = rdb.get_timeout(CHECK_TIMEOUT).map_err(|e| e.to_string())?;
+        diesel::sql_query("SELECT 1")
+            .execute(&mut conn)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+}
+
+fn check_redis(cache: &RedisPoolState) -> Result<(), String> {
+    blocking(|| {
+        let mut conn = cache
+            .get_timeout(CHECK_TIMEOUT)
+            .map_err(|e| e.to_string())?;
+        redis::cmd("PING")
+            .query::<String>(&mut *conn)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+}
+
+// Any HTTP response counts as reachable, credentials are not exercised
+async fn iam_reachable() -> Result<(), String> {
+    let configuration = IAMService::get_configuration();
+    configuration
+        .client
+        .get(&configuration.base_path)
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn notification_reachable() -> Result<(), String> {
+    let configuration = NotificationService::get_configuration();
+    configuration
+        .client
+        .get(&configuration.base_path)
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Only Postgres is required, the other dependencies have a fallback and degrade the service
+pub fn readiness(dependencies: &[DependencyStatusResponse]) -> (Status, &'static str) {
+    let down = |required: bool| {
+        dependencies
+            .iter()
+            .any(|dependency| dependency.required == required && dependency.status == "down")
+    };
+
+    if down(true) {
+        (Status::ServiceUnavailable, "unavailable")
+    } else if down(false) {
+        (Status::Ok, "degraded")
+    } else {
+        (Status::Ok, "ready")
+    }
+}
+
+/// Answers as long as the process serves requests, dependencies are left to readiness
+#[openapi()]
+#[get("/health/live")]
+pub fn live() -> Json<MessageResponse> {
+    Json(MessageResponse {
+        message: "Ok".to_string(),
+    })
+}
+
+#[openapi()]
+#[get("/health/ready")]
+pub async fn ready(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    cache: Option<&State<RedisPoolState>>,
+    blobs: Option<&State<Database>>,
+) -> status::Custom<Json<ReadinessResponse>> {
+    let started = Instant::now();
+    let postgres = measured("postgres", true, started, check_postgres(rdb));
+
+    let redis = match cache {
+        Some(cache) => {
+            let started = Instant::now();
+            measured("redis", false, started, check_redis(cache))
+        }
+        None => unmeasured("redis", "not_configured", None),
+    };
+
+    let (mongo, iam, notification) = join!(
+        async {
+            match (blobs, env::var("MONGO_URI").is_ok()) {
+                (Some(database), _) => {
+                    timed("mongo", false, async {
+                        database
+                            .run_command(doc! {"ping": 1}, None)
+                            .await
+                            .map(|_| ())
+                            .map_err(|e| e.to_string())
+                    })
+                    .await
+                }
+                (None, true) => unmeasured(
+                    "mongo",
+                    "down",
+                    Some("Not connected since startup".to_string()),
+                ),
+                (None, false) => unmeasured("mongo", "not_configured", None),
+            }
+        },
+        timed("iam", false, iam_reachable()),
+        timed("notification", false, notification_reachable()),
+    );
+
+    let dependencies = vec![postgres, redis, mongo, iam, notification];
+    let (status_code, status) = readiness(&dependencies);
+
+    status::Custom(
+        status_code,
+        Json(ReadinessResponse {
+            status: status.to_string(),
+            dependencies,
+        }),
+    )
+}
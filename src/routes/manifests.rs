@@ -0,0 +1,613 @@
+use crate::db::blobs::{content_hash, store_document, stored_hash};
+use crate::db::pool::run_blocking;
+use crate::db::redis::{invalidate, RedisPoolState};
+use crate::errors::ApiError;
+use crate::metrics::observe_spec;
+use crate::middlewares::api_token_claims::{ActiveAPIClaims, SCOPE_READ, SCOPE_WRITE};
+use crate::models::manifest::{EnvUrls, MetadataManifest, ReleaserManifest, ServicesManifest};
+use crate::models::quick_link::{parse_quick_links, stored_quick_links, QuickLink};
+use crate::models::request::{
+    CreateOrUpdatePackageRequest, IngestManifestsRequest, UpdateServiceRequest,
+};
+use crate::models::response::{IngestManifestsResponse, ManifestChangeResponse};
+use crate::models::schema::schema::{package, package_env, service, service_envs};
+use crate::models::schema::{Package, Package_Env, Service, Service_Envs};
+use crate::routes::metadata::{
+    check_identifier_owner, parse_identifiers, upsert_package, upsert_service,
+    SERVICE_SPEC_MAX_LENGTH,
+};
+use crate::routes::quick_links::validate_quick_links;
+use crate::routes::{canonical_slug, ensure_active};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use mongodb::Database;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use tracing::Span;
+
+// Dependencies of this service type are web portals, listed under `portals_refs`
+const PORTAL_SERVICE_TYPE: &str = "portal";
+
+/// The manifests of the request, parsed
+struct Manifests {
+    metadata: MetadataManifest,
+    services: Option<ServicesManifest>,
+    version: Option<String>,
+}
+
+fn parse_toml<T: DeserializeOwned>(file_name: &str, content: &str) -> Result<T, ApiError> {
+    toml::from_str(content).map_err(|e| {
+        ApiError::UnprocessableEntity(format!("{} is invalid: {}", file_name, e.message()))
+    })
+}
+
+impl Manifests {
+    fn parse(request: &IngestManifestsRequest) -> Result<Self, ApiError> {
+        let releaser = request
+            .releaser_toml
+            .as_deref()
+            .map(|content| parse_toml::<ReleaserManifest>("releaser.toml", content))
+            .transpose()?;
+
+        let metadata: MetadataManifest = parse_toml("metadata.toml", &request.metadata_toml)?;
+        validate_quick_links(&metadata.links)?;
+
+        Ok(Manifests {
+            metadata,
+            services: request
+                .services_toml
+                .as_deref()
+                .map(|content| parse_toml("services.toml", content))
+                .transpose()?,
+            version: releaser.map(|releaser| releaser.version.to_string()),
+        })
+    }
+
+    // `[[links]]` stored as quick links, without any the registered ones are kept
+    fn quick_links(&self) -> Option<Vec<QuickLink>> {
+        (!self.metadata.links.is_empty()).then(|| self.metadata.links.clone())
+    }
+}
+
+fn compare(
+    changes: &mut Vec<ManifestChangeResponse>,
+    field: &str,
+    before: Option<String>,
+    after: Option<String>,
+) {
+    if before != after {
+        changes.push(ManifestChangeResponse {
+            field: field.to_string(),
+            before,
+            after,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ingest_service(
+    conn: &mut PgConnection,
+    request: &IngestManifestsRequest,
+    manifests: &Manifests,
+    services: &ServicesManifest,
+    stored_spec: Option<String>,
+    dry_run: bool,
+    claims: &ActiveAPIClaims,
+    cache: Option<&RedisPoolState>,
+) -> Result<IngestManifestsResponse, ApiError> {
+    let org_id = request
+        .organization_id
+        .clone()
+        .or_else(|| services.organization_id.clone())
+        .ok_or_else(|| ApiError::BadRequest("organization_id is required".to_string()))?;
+    let org_id = canonical_slug(conn, &org_id)?;
+    claims.authorize(conn, &org_id, SCOPE_WRITE)?;
+    ensure_active(conn, &org_id)?;
+    Span::current().record("org_id", org_id.as_str());
+
+    let current = service::table
+        .filter(service::identifier.eq(&request.identifier))
+        .first::<Service>(conn)
+        .optional()?;
+    check_identifier_owner(
+        "Service",
+        current.as_ref().map(|s| s.organization_id.clone()),
+        &org_id,
+    )?;
+    let current = current.filter(|s| s.deleted_at.is_none());
+    let current_env = match &current {
+        Some(current) => service_envs::table
+            .filter(service_envs::parent_id.eq(current.id))
+            .filter(service_envs::env.eq(&request.env))
+            .filter(service_envs::deleted_at.is_null())
+            .first::<Service_Envs>(conn)
+            .optional()?,
+        None => None,
+    };
+
+    let base_url = services
+        .urls
+        .get(&request.env)
+        .filter(|url| !url.is_empty())
+        .cloned()
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!("services.toml has no url for env {}", request.env))
+        })?;
+
+    // What the manifests do not describe stays as registered
+    let service_request = UpdateServiceRequest {
+        identifier: request.identifier.clone(),
+        env: request.env.clone(),
+        base_url,
+        base_url_ws: services
+            .urls_ws
+            .get(&request.env)
+            .filter(|url| !url.is_empty())
+            .cloned(),
+        spec: String::new(), // the stored spec is passed to `upsert_service` on its own
+        dependencies: services.dependencies(),
+        tables: parse_identifiers(current.as_ref().and_then(|s| s.tables_json.clone()))?,
+        db_schema_id: current.as_ref().and_then(|s| s.db_schema_id.clone()),
+        cache_schema_id: current.as_ref().and_then(|s| s.cache_schema_id.clone()),
+        message_queue_schema_id: current
+            .as_ref()
+            .and_then(|s| s.message_queue_schema_id.clone()),
+        service_type: services
+            .service_type
+            .clone()
+            .or_else(|| current.as_ref().map(|s| s.service_type.clone())),
+        version: manifests
+            .version
+            .clone()
+            .or_else(|| current_env.as_ref().map(|e| e.version.clone())),
+        lang: services
+            .lang
+            .clone()
+            .or_else(|| manifests.metadata.lang.clone())
+            .or_else(|| current.as_ref().and_then(|s| s.lang.clone())),
+        description: request
+            .description
+            .clone()
+            .or_else(|| current.as_ref().and_then(|s| s.description.clone()))
+            .unwrap_or_default(),
+        organization_id: org_id.clone(),
+        repo_origin: request
+            .repo_origin
+            .clone()
+            .or_else(|| current.as_ref().and_then(|s| s.repo_origin.clone())),
+        quick_links: manifests.quick_links().or_else(|| {
+            current
+                .as_ref()
+                .map(|s| parse_quick_links(s.quick_links.as_deref()))
+        }),
+    };
+
+    let current_spec = current_env.as_ref().map(|e| e.spec.clone());
+    let spec_hash = match &request.spec {
+        Some(spec) => Some(content_hash(spec)),
+        None => current_spec.as_deref().map(stored_hash),
+    };
+
+    let mut changes = vec![];
+    compare(
+        &mut changes,
+        "organization_id",
+        current.as_ref().and_then(|s| s.organization_id.clone()),
+        Some(org_id.clone()),
+    );
+    compare(
+        &mut changes,
+        "service_type",
+        current.as_ref().map(|s| s.service_type.clone()),
+        service_request.service_type.clone(),
+    );
+    compare(
+        &mut changes,
+        "lang",
+        current.as_ref().and_then(|s| s.lang.clone()),
+        service_request.lang.clone(),
+    );
+    compare(
+        &mut changes,
+        "description",
+        current.as_ref().and_then(|s| s.description.clone()),
+        Some(service_request.description.clone()),
+    );
+    compare(
+        &mut changes,
+        "dependencies",
+        current.as_ref().and_then(|s| s.dependencies_json.clone()),
+        serde_json::to_string(&service_request.dependencies).ok(),
+    );
+    compare(
+        &mut changes,
+        "quick_links",
+        current.as_ref().and_then(|s| s.quick_links.clone()),
+        service_request
+            .quick_links
+            .as_deref()
+            .and_then(stored_quick_links),
+    );
+    compare(
+        &mut changes,
+        "repo_origin",
+        current.as_ref().and_then(|s| s.repo_origin.clone()),
+        service_request.repo_origin.clone(),
+    );
+    compare(
+        &mut changes,
+        "base_url",
+        current_env.as_ref().map(|e| e.base_url.clone()),
+        Some(service_request.base_url.clone()),
+    );
+    compare(
+        &mut changes,
+        "base_url_ws",
+        current_env.as_ref().and_then(|e| e.base_url_ws.clone()),
+        service_request.base_url_ws.clone(),
+    );
+    compare(
+        &mut changes,
+        "version",
+        current_env.as_ref().map(|e| e.version.clone()),
+        Some(
+            service_request
+                .version
+                .clone()
+                .unwrap_or("0.0.0".to_string()),
+        ),
+    );
+    compare(
+        &mut changes,
+        "spec",
+        current_spec.as_deref().map(stored_hash),
+        spec_hash,
+    );
+
+    let created = current_env.is_none();
+    let mut id = current.as_ref().map(|s| s.id);
+    if !dry_run && !changes.is_empty() {
+        let spec = stored_spec.or(current_spec).unwrap_or_default();
+        let service_id = conn
+            .transaction::<_, ApiError, _>(|conn| upsert_service(conn, &service_request, spec))?;
+        invalidate(cache, &org_id);
+        id = Some(service_id);
+    }
+
+    Ok(IngestManifestsResponse {
+        kind: "service".to_string(),
+        identifier: request.identifier.clone(),
+        env: request.env.clone(),
+        id,
+        created,
+        dry_run,
+        changes,
+    })
+}
+
+fn ingest_package(
+    conn: &mut PgConnection,
+    request: &IngestManifestsRequest,
+    manifests: &Manifests,
+    dry_run: bool,
+    claims: &ActiveAPIClaims,
+    cache: Option<&RedisPoolState>,
+) -> Result<IngestManifestsResponse, ApiError> {
+    let org_id = request
+        .organization_id
+        .clone()
+        .ok_or_else(|| ApiError::BadRequest("organization_id is required".to_string()))?;
+    let org_id = canonical_slug(conn, &org_id)?;
+    claims.authorize(conn, &org_id, SCOPE_WRITE)?;
+    ensure_active(conn, &org_id)?;
+    Span::current().record("org_id", org_id.as_str());
+
+    let current = package::table
+        .filter(package::identifier.eq(&request.identifier))
+        .first::<Package>(conn)
+        .optional()?;
+    check_identifier_owner(
+        "Package",
+        current.as_ref().map(|p| p.organization_id.clone()),
+        &org_id,
+    )?;
+    let current = current.filter(|p| p.deleted_at.is_none());
+    let current_env = match &current {
+        Some(current) => package_env::table
+            .filter(package_env::parent_id.eq(current.id))
+            .filter(package_env::env.eq(&request.env))
+            .filter(package_env::deleted_at.is_null())
+            .first::<Package_Env>(conn)
+            .optional()?,
+        None => None,
+    };
+
+    let required = |field: &str, file_name: &str| {
+        ApiError::BadRequest(format!("{} is required, set it in {}", field, file_name))
+    };
+
+    let package_request = CreateOrUpdatePackageRequest {
+        identifier: request.identifier.clone(),
+        package_type: manifests
+            .metadata
+            .package_type
+            .clone()
+            .or_else(|| current.as_ref().map(|p| p.package_type.clone()))
+            .ok_or_else(|| required("package_type", "metadata.toml"))?,
+        lang: manifests
+            .metadata
+            .lang
+            .clone()
+            .or_else(|| current.as_ref().map(|p| p.lang.clone()))
+            .ok_or_else(|| required("lang", "metadata.toml"))?,
+        version: manifests
+            .version
+            .clone()
+            .or_else(|| current_env.as_ref().map(|e| e.version.clone()))
+            .ok_or_else(|| required("version", "releaser.toml"))?,
+        description: request
+            .description
+            .clone()
+            .or_else(|| current.as_ref().and_then(|p| p.description.clone()))
+            .unwrap_or_default(),
+        organization_id: org_id.clone(),
+        dependencies: parse_identifiers(
+            current.as_ref().and_then(|p| p.dependencies_json.clone()),
+        )?,
+        env: request.env.clone(),
+        repo_origin: request
+            .repo_origin
+            .clone()
+            .or_else(|| current.as_ref().and_then(|p| p.repo_origin.clone())),
+        quick_links: manifests.quick_links().or_else(|| {
+            current
+                .as_ref()
+                .map(|p| parse_quick_links(p.quick_links.as_deref()))
+        }),
+    };
+
+    let mut changes = vec![];
+    compare(
+        &mut changes,
+        "organization_id",
+        current.as_ref().and_then(|p| p.organization_id.clone()),
+        Some(org_id.clone()),
+    );
+    compare(
+        &mut changes,
+        "package_type",
+        current.as_ref().map(|p| p.package_type.clone()),
+        Some(package_request.package_type.clone()),
+    );
+    compare(
+        &mut changes,
+        "lang",
+        current.as_ref().map(|p| p.lang.clone()),
+        Some(package_request.lang.clone()),
+    );
+    compare(
+        &mut changes,
+        "description",
+        current.as_ref().and_then(|p| p.description.clone()),
+        Some(package_request.description.clone()),
+    );
+    compare(
+        &mut changes,
+        "quick_links",
+        current.as_ref().and_then(|p| p.quick_links.clone()),
+        package_request
+            .quick_links
+            .as_deref()
+            .and_then(stored_quick_links),
+    );
+    compare(
+        &mut changes,
+        "repo_origin",
+        current.as_ref().and_then(|p| p.repo_origin.clone()),
+        package_request.repo_origin.clone(),
+    );
+    compare(
+        &mut changes,
+        "version",
+        current_env.as_ref().map(|e| e.version.clone()),
+        Some(package_request.version.clone()),
+    );
+
+    let created = current_env.is_none();
+    let mut id = current.as_ref().map(|p| p.id);
+    if !dry_run && !changes.is_empty() {
+        let package_id =
+            conn.transaction::<_, ApiError, _>(|conn| upsert_package(conn, &package_request))?;
+        invalidate(cache, &org_id);
+        id = Some(package_id);
+    }
+
+    Ok(IngestManifestsResponse {
+        kind: "package".to_string(),
+        identifier: request.identifier.clone(),
+        env: request.env.clone(),
+        id,
+        created,
+        dry_run,
+        changes,
+    })
+}
+
+/// Publishes a service (when `services_toml` is given) or a package straight from the manifests
+/// of its repo. With `dry_run` nothing is written and the response lists what would change
+#[openapi()]
+#[post("/manifests?<dry_run>", data = "<ingest_request>")]
+pub async fn ingest_manifests(
+    ingest_request: Json<IngestManifestsRequest>,
+    dry_run: Option<bool>,
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    claims: ActiveAPIClaims,
+    cache: Option<&State<RedisPoolState>>,
+    blobs: Option<&State<Database>>,
+) -> Result<Json<IngestManifestsResponse>, ApiError> {
+    let dry_run = dry_run.unwrap_or(false);
+    let manifests = Manifests::parse(&ingest_request)?;
+
+    // The spec goes to the blob store before the transaction, as for `update_or_create_service`
+    let stored_spec = match (&manifests.services, dry_run) {
+        (Some(_), false) => {
+            observe_spec("service", ingest_request.spec.as_deref());
+            store_document(
+                blobs.map(|b| b.inner()),
+                ingest_request.spec.clone(),
+                SERVICE_SPEC_MAX_LENGTH,
+            )
+            .await?
+        }
+        _ => None,
+    };
+
+    run_blocking(rdb, |conn| {
+        let cache = cache.map(|c| c.inner());
+        let response = match &manifests.services {
+            Some(services) => ingest_service(
+                conn,
+                &ingest_request,
+                &manifests,
+                services,
+                stored_spec,
+                dry_run,
+                &claims,
+                cache,
+            )?,
+            None => ingest_package(conn, &ingest_request, &manifests, dry_run, &claims, cache)?,
+        };
+        Ok(Json(response))
+    })
+}
+
+fn env_urls(
+    envs: &[Service_Envs],
+    env: Option<&str>,
+    url: impl Fn(&Service_Envs) -> String,
+) -> EnvUrls {
+    envs.iter()
+        .filter(|service_env| env.map_or(true, |env| service_env.env == env))
+        .map(|service_env| (service_env.env.clone(), url(service_env)))
+        .collect()
+}
+
+/// `services.toml` of `service` from what its dependencies registered, in every env or only
+/// `env`. Services always get a `ws_refs` entry, empty when they have no websocket URL
+pub fn render_services_manifest(
+    service: &Service,
+    envs: &[Service_Envs],
+    dependencies: &[(Service, Vec<Service_Envs>)],
+    env: Option<&str>,
+) -> ServicesManifest {
+    let mut services = BTreeMap::new();
+    let mut portals_refs = BTreeMap::new();
+    let mut ws_refs = BTreeMap::new();
+
+    for (dependency, dependency_envs) in dependencies {
+        let urls = env_urls(dependency_envs, env, |e| e.base_url.clone());
+        if dependency
+            .service_type
+            .eq_ignore_ascii_case(PORTAL_SERVICE_TYPE)
+        {
+            portals_refs.insert(dependency.identifier.clone(), urls);
+        } else {
+            services.insert(dependency.identifier.clone(), urls);
+            ws_refs.insert(
+                dependency.identifier.clone(),
+                env_urls(dependency_envs, env, |e| {
+                    e.base_url_ws.clone().unwrap_or_default()
+                }),
+            );
+        }
+    }
+
+    ServicesManifest {
+        lang: service.lang.clone(),
+        organization_id: service.organization_id.clone(),
+        dir: None,
+        spec_url: None,
+        service_type: Some(service.service_type.clone()),
+        services,
+        portals_refs,
+        ws_refs,
+        urls: env_urls(envs, env, |e| e.base_url.clone()),
+        urls_ws: env_urls(envs, env, |e| e.base_url_ws.clone().unwrap_or_default())
+            .into_iter()
+            .filter(|(_, url)| !url.is_empty())
+            .collect(),
+    }
+}
+
+/// Renders the `services.toml` of a service from its registered dependencies, so repos can
+/// regenerate it. `dir` and `spec_url` only describe the repo, they are copied as given.
+/// Dependencies that are not registered are listed in a comment at the top
+#[openapi()]
+#[get("/services-toml/<org_id>/<service_identifier>?<env>&<dir>&<spec_url>")]
+pub async fn get_services_toml(
+    org_id: String,
+    service_identifier: String,
+    env: Option<String>,
+    dir: Option<String>,
+    spec_url: Option<String>,
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    claims: ActiveAPIClaims,
+) -> Result<String, ApiError> {
+    let (mut manifest, unregistered) = run_blocking(rdb, |conn| {
+        let org_id = canonical_slug(conn, &org_id)?;
+        claims.authorize(conn, &org_id, SCOPE_READ)?;
+
+        let service_item = service::table
+            .filter(service::organization_id.eq(&org_id))
+            .filter(service::identifier.eq(&service_identifier))
+            .filter(service::deleted_at.is_null())
+            .first::<Service>(conn)
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound("Service not found".to_string()))?;
+        let envs = Service_Envs::belonging_to(&service_item)
+            .filter(service_envs::deleted_at.is_null())
+            .order(service_envs::id.asc())
+            .load::<Service_Envs>(conn)?;
+
+        let identifiers = parse_identifiers(service_item.dependencies_json.clone())?;
+        // Services of other workspaces are reported as unregistered, their URLs are not exposed
+        let dependencies = service::table
+            .filter(service::organization_id.eq(&org_id))
+            .filter(service::identifier.eq_any(&identifiers))
+            .filter(service::deleted_at.is_null())
+            .order(service::identifier.asc())
+            .load::<Service>(conn)?;
+        let dependency_envs = Service_Envs::belonging_to(&dependencies)
+            .filter(service_envs::deleted_at.is_null())
+            .order(service_envs::id.asc())
+            .load::<Service_Envs>(conn)?
+            .grouped_by(&dependencies);
+
+        let unregistered: Vec<String> = identifiers
+            .into_iter()
+            .filter(|identifier| !dependencies.iter().any(|d| &d.identifier == identifier))
+            .collect();
+        let dependencies: Vec<(Service, Vec<Service_Envs>)> =
+            dependencies.into_iter().zip(dependency_envs).collect();
+
+        Ok((
+            render_services_manifest(&service_item, &envs, &dependencies, env.as_deref()),
+            unregistered,
+        ))
+    })?;
+
+    manifest.dir = dir;
+    manifest.spec_url = spec_url;
+
+    let rendered = toml::to_string(&manifest)
+        .map_err(|_| ApiError::Internal("Error rendering services.toml".to_string()))?;
+    let header: String = unregistered
+        .iter()
+        .map(|identifier| format!("# {} is not registered\n", identifier))
+        .collect();
+
+    Ok(header + &rendered)
+}
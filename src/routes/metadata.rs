@@ -1,34 +1,58 @@
+use crate::db::blobs::{content_hash, resolve_document, store_document};
+use crate::db::idempotency::{self, request_hash};
+use crate::db::outbox;
+use crate::db::pipeline;
+use crate::db::pool::run_blocking;
+use crate::db::redis::{cached, cached_async, invalidate, RedisPoolState};
+use crate::db::soft_delete;
+use crate::db::webhooks;
+use crate::errors::ApiError;
+use crate::metrics::{iam_call, observe_spec};
+use crate::middlewares::api_token_claims::{
+    ActiveAPIClaims, SCOPE_PIPELINE, SCOPE_READ, SCOPE_WRITE,
+};
+use crate::middlewares::conditional::{etag, ETagged, IfMatch, IfNoneMatch};
 use crate::middlewares::groups::GroupMemberships;
 use crate::middlewares::groups_owned::GroupOwnerships;
+use crate::middlewares::idempotency_key::IdempotencyKey;
 use crate::middlewares::IAMService_config::IAMService_config;
-use crate::middlewares::NotificationService_api_config::NotificationService_api_config;
+use crate::models::canvas::{parse_layout, stored_layout, CanvasLayout};
+use crate::models::quick_link::{parse_quick_links, stored_quick_links};
 use crate::models::schema::{
     Dbschema, DbschemaInsertable, Dbschema_Branch, Dbschema_BranchInsertable, Package,
-    PackageInsertable, Package_Env, Package_EnvInsertable, Service, ServiceInsertable,
-    Service_Envs, Service_EnvsInsertable, Snapshots, SnapshotsInsertable, Templates,
+    PackageInsertable, Package_EnvInsertable, Service, ServiceInsertable, Service_Envs,
+    Service_EnvsInsertable, Snapshots, SnapshotsInsertable, Templates,
+};
+use crate::routes::canvas::{canvas_block_ids, validate_layout};
+use crate::routes::pagination::{PageRequest, Sort, SortKey};
+use crate::routes::projection::{project, Projected};
+use crate::routes::quick_links::validate_quick_links;
+use crate::routes::{
+    canonical_slug, ensure_active, member_organization, owned_organization, slug_taken,
 };
-use ginger_shared_rs::rocket_models::RealtimeMessage;
-use ginger_shared_rs::rocket_utils::{APIClaims, Claims};
+use crate::telemetry::redacted;
+use ginger_shared_rs::rocket_utils::Claims;
 
 use crate::models::request::{
     CreateDbschemaBranchRequest, CreateDbschemaRequest, CreateOrUpdatePackageRequest,
-    CreateOrganizationRequest, CreateSnapshotRequest, PipelineStatusUpdateRequest,
-    UpdateDbPipelineRequest, UpdateDbschemaBranchRequest, UpdateDbschemaRequest,
-    UpdateServiceRequest,
+    CreateOrganizationRequest, CreateSnapshotRequest, PackageListQuery,
+    PipelineStatusUpdateRequest, ServiceListQuery, UpdateDbPipelineRequest,
+    UpdateDbschemaBranchRequest, UpdateDbschemaRequest, UpdateServiceRequest,
 };
 use crate::models::response::{
     APISessionDetailsResponse, CreateDbschemaBranchResponse, CreateDbschemaResponse,
     CreateOrUpdatePackageResponse, CreateOrganizationResponse, GetDbschemaAndTablesResponse,
-    GetDbschemaByIdResponse, GetDbschemaResponse, PackageResponse, ServiceResponse,
+    GetDbschemaByIdResponse, GetDbschemaResponse, PackageResponse, Page, ServiceResponse,
     ServicesEnvResponse, ServicesEnvTrimmedResponse, ServicesTrimmedResponse, SnapshotsResponse,
     UpdateDbschemaBranchResponse, UpdateServiceResponse, VersionResponse, WorkspaceDetailResponse,
-    WorkspaceSummaryResponse,
+    WorkspaceLifecycleResponse, WorkspaceSummaryResponse,
 };
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
-use rocket::http::Status;
+use diesel::upsert::excluded;
+use mongodb::Database;
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rocket::State;
@@ -36,142 +60,196 @@ use rocket_okapi::openapi;
 use schemars::JsonSchema;
 use serde::Serialize;
 use serde_json::json;
+use tracing::Span;
 use uuid::Uuid;
 use IAMService::apis::default_api::{identity_create_group, IdentityCreateGroupParams};
 use IAMService::models::CreateGroupRequest;
-use NotificationService::apis::default_api::{
-    publish_message_to_group, publish_message_to_group_api_land, PublishMessageToGroupApiLandParams, PublishMessageToGroupParams
-};
-use NotificationService::models::{PublishRequest, PublishType};
+
+// Max lengths of the Postgres columns, used when documents cannot be offloaded to the blob store
+const DBSCHEMA_DATA_MAX_LENGTH: usize = 10000;
+pub const SERVICE_SPEC_MAX_LENGTH: usize = 35000;
+
+// `tables_json` and `dependencies_json` hold a JSON list of identifiers, a missing column is an empty list
+pub fn parse_identifiers(stored: Option<String>) -> Result<Vec<String>, ApiError> {
+    match stored {
+        Some(stored) => serde_json::from_str(&stored)
+            .map_err(|_| ApiError::Internal("Stored identifier list is corrupted".to_string())),
+        None => Ok(vec![]),
+    }
+}
+
+// Matches `value` literally inside a LIKE pattern, identifiers often contain `_`
+fn like_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
 
 #[openapi()]
 #[post("/dbschema", data = "<create_request>")]
 pub async fn create_dbschema(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    create_request: Json<CreateDbschemaRequest>,
-    claims: APIClaims,
+    mut create_request: Json<CreateDbschemaRequest>,
+    claims: ActiveAPIClaims,
     iam_service_config: IAMService_config,
-) -> Result<status::Created<Json<CreateDbschemaResponse>>, status::Custom<String>> {
+    idempotency_key: IdempotencyKey,
+    cache: Option<&State<RedisPoolState>>,
+    blobs: Option<&State<Database>>,
+) -> Result<status::Created<Json<CreateDbschemaResponse>>, ApiError> {
     use crate::models::schema::schema::dbschema::dsl::*;
     use crate::models::schema::schema::dbschema_branch::dsl::*;
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
 
-    let group_uuid = Uuid::new_v4().to_string();
+    validate_quick_links(create_request.quick_links.as_deref().unwrap_or_default())?;
+    observe_spec("schema", create_request.data.as_deref());
+    let blobs = blobs.map(|b| b.inner());
+    let stored_data =
+        store_document(blobs, create_request.data.clone(), DBSCHEMA_DATA_MAX_LENGTH).await?;
+    let stored_schema = store_document(
+        blobs,
+        create_request.schema.clone(),
+        DBSCHEMA_DATA_MAX_LENGTH,
+    )
+    .await?;
 
-    let dbschema_uuid = Uuid::new_v4().to_string();
+    run_blocking(rdb, |conn| {
+        create_request.organisation_id = canonical_slug(conn, &create_request.organisation_id)?;
+        claims.authorize(conn, &create_request.organisation_id, SCOPE_WRITE)?;
+        ensure_active(conn, &create_request.organisation_id)?;
 
-    let new_dbschema = DbschemaInsertable {
-        name: create_request.name.clone(),
-        description: create_request.description.clone(),
-        created_at: Utc::now(),
-        updated_at: Utc::now(),
-        data: create_request.data.clone(),
-        group_id: None,
-        identifier: Some(dbschema_uuid),
-        organization_id: Some(create_request.organisation_id.clone()),
-        repo_origin: Some(create_request.repo_origin.clone()),
-        db_type: create_request.db_type.clone(),
-        quick_links: create_request.quick_links.clone(),
-    };
+        let key = idempotency_key.0.as_deref();
+        let key_scope = format!("dbschemas:{}", claims.sub);
+        let hash = request_hash(&*create_request);
 
-    let created_dbschema: Dbschema = diesel::insert_into(dbschema)
-        .values(&new_dbschema)
-        .get_result::<Dbschema>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error inserting new dbschema".to_string(),
-            )
-        })?;
+        let response = conn.transaction::<_, ApiError, _>(|conn| {
+            if let Some(replayed) = idempotency::claim(conn, key, &key_scope, &hash)? {
+                return Ok(replayed);
+            }
 
-    // Insert new branch with name "main"
-    let new_branch = Dbschema_BranchInsertable {
-        data: create_request.schema.clone(),
-        branch_name: "stage".to_string(),
-        created_at: Utc::now(),
-        updated_at: Utc::now(),
-        parent_id: created_dbschema.id,
-        version: Some(create_request.version.clone()),
-        pipeline_status: None,
-    };
+            let new_dbschema = DbschemaInsertable {
+                name: create_request.name.clone(),
+                description: create_request.description.clone(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                data: stored_data,
+                group_id: None,
+                identifier: Some(Uuid::new_v4().to_string()),
+                organization_id: Some(create_request.organisation_id.clone()),
+                repo_origin: Some(create_request.repo_origin.clone()),
+                db_type: create_request.db_type.clone(),
+                quick_links: create_request
+                    .quick_links
+                    .as_deref()
+                    .and_then(stored_quick_links),
+            };
 
-    diesel::insert_into(dbschema_branch)
-        .values(&new_branch)
-        .execute(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error inserting new branch".to_string(),
-            )
-        })
-        .map(|created_branch| {
-            status::Created::new("/dbschema").body(Json(CreateDbschemaResponse {
+            let created_dbschema: Dbschema = diesel::insert_into(dbschema)
+                .values(&new_dbschema)
+                .get_result::<Dbschema>(conn)
+                .map_err(|_| ApiError::Internal("Error inserting new dbschema".to_string()))?;
+
+            // Insert new branch with name "stage"
+            let new_branch = Dbschema_BranchInsertable {
+                data: stored_schema,
+                branch_name: "stage".to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                parent_id: created_dbschema.id,
+                version: Some(create_request.version.clone()),
+                pipeline_status: None,
+                pipeline_changed_at: None,
+                last_passed_at: None,
+            };
+
+            diesel::insert_into(dbschema_branch)
+                .values(&new_branch)
+                .execute(conn)
+                .map_err(|_| ApiError::Internal("Error inserting new branch".to_string()))?;
+
+            outbox::enqueue_catalog(
+                conn,
+                &create_request.organisation_id,
+                "dbschema",
+                created_dbschema.identifier.as_deref().unwrap_or_default(),
+                "created",
+            )?;
+
+            let response = CreateDbschemaResponse {
                 message: "Dbschema created successfully".to_string(),
                 id: created_dbschema.id,
-                identifier: created_dbschema.identifier.unwrap(),
-            }))
-        })
+                identifier: created_dbschema.identifier.unwrap_or_default(),
+            };
+
+            idempotency::complete(conn, key, &key_scope, &response)?;
+            Ok(response)
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &create_request.organisation_id);
+
+        Ok(status::Created::new("/dbschema").body(Json(response)))
+    })
 }
 
 #[openapi()]
-#[get("/dbschemas?<search>&<page_number>&<page_size>")]
+#[get("/dbschemas?<search>&<cursor>&<limit>")]
 pub fn get_dbschemas(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     groups: GroupMemberships,
     _claims: Claims,
     search: Option<String>,
-    page_number: Option<i64>,
-    page_size: Option<i64>,
-) -> Result<Json<Vec<GetDbschemaResponse>>, status::Custom<String>> {
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Json<Page<GetDbschemaResponse>>, ApiError> {
     use crate::models::schema::schema::dbschema::dsl::*;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
+    let page_request = PageRequest::parse(cursor.as_deref(), limit)?;
 
-    let memberships: Vec<String> = groups.0;
+    run_blocking(rdb, |conn| {
+        let memberships: Vec<String> = groups.0;
+        let search_pattern = search.map(|search_term| format!("%{}%", search_term));
 
-    let mut query = dbschema.filter(group_id.eq_any(memberships)).into_boxed();
+        let mut query = dbschema
+            .filter(group_id.eq_any(&memberships))
+            .filter(deleted_at.is_null())
+            .into_boxed();
+        let mut count_query = dbschema
+            .filter(group_id.eq_any(&memberships))
+            .filter(deleted_at.is_null())
+            .into_boxed();
 
-    if let Some(search_term) = search {
-        query = query.filter(name.like(format!("%{}%", search_term)));
-    }
+        if let Some(pattern) = &search_pattern {
+            query = query.filter(name.like(pattern.clone()));
+            count_query = count_query.filter(name.like(pattern.clone()));
+        }
 
-    // Pagination logic
-    let page_number = page_number.unwrap_or(1);
-    let page_size = page_size.unwrap_or(10);
-    let offset = (page_number - 1) * page_size;
+        if let Some(after_id) = page_request.after_id {
+            query = query.filter(id.gt(after_id));
+        }
 
-    query = query.offset(offset).limit(page_size);
+        let results = query
+            .order(id.asc())
+            .limit(page_request.fetch_limit())
+            .load::<Dbschema>(conn)
+            .map_err(|_| ApiError::Internal("Error retrieving dbschemas".to_string()))?;
 
-    let results = query.load::<Dbschema>(&mut conn).map_err(|_| {
-        status::Custom(
-            Status::InternalServerError,
-            "Error retrieving dbschemas".to_string(),
-        )
-    })?;
+        let total = count_query
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|_| ApiError::Internal("Error counting dbschemas".to_string()))?;
 
-    let response = results
-        .into_iter()
-        .map(|db_schema_| GetDbschemaResponse {
-            id: db_schema_.id,
-            name: db_schema_.name,
-            description: db_schema_.description,
-            updated_at: db_schema_.updated_at,
-            identifier: db_schema_.identifier,
-            organization_id: db_schema_.organization_id.unwrap(),
-        })
-        .collect();
+        let response = page_request
+            .page(results, total, |db_schema_| (None, db_schema_.id))
+            .map(|db_schema_| GetDbschemaResponse {
+                id: db_schema_.id,
+                name: db_schema_.name,
+                description: db_schema_.description,
+                updated_at: db_schema_.updated_at,
+                identifier: db_schema_.identifier,
+                organization_id: db_schema_.organization_id.unwrap_or_default(),
+            });
 
-    Ok(Json(response))
+        Ok(Json(response))
+    })
 }
 
 #[openapi()]
@@ -180,92 +258,99 @@ pub fn update_dbschema(
     schema_id: String,
     branch_name: String,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    update_request: Json<UpdateDbschemaRequest>,
-    _claims: APIClaims,
-) -> Result<Json<Dbschema>, status::Custom<String>> {
+    mut update_request: Json<UpdateDbschemaRequest>,
+    claims: ActiveAPIClaims,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<Dbschema>, ApiError> {
     use crate::models::schema::schema::dbschema::dsl::*;
     use crate::models::schema::schema::dbschema_branch::dsl as dbschema_branch_dsl;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
+    validate_quick_links(update_request.quick_links.as_deref().unwrap_or_default())?;
 
-    let updated_rows = diesel::update(dbschema.filter(identifier.eq(schema_id.clone())))
-        .set((
-            name.eq(update_request.name.clone()),
-            description.eq(update_request.description.clone()),
-            repo_origin.eq(update_request.repo_origin.clone()),
-            organization_id.eq(update_request.organisation_id.clone()),
-            quick_links.eq(update_request.quick_links.clone()),
-        ))
-        .execute(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Failed to update dbschema".to_string(),
+    run_blocking(rdb, |conn| {
+        update_request.organisation_id = canonical_slug(conn, &update_request.organisation_id)?;
+        claims.authorize(conn, &update_request.organisation_id, SCOPE_WRITE)?;
+        ensure_active(conn, &update_request.organisation_id)?;
+
+        let updated_dbschema = conn.transaction::<_, ApiError, _>(|conn| {
+            // Schemas of other workspaces are not found rather than moved over
+            let updated_rows = diesel::update(
+                dbschema
+                    .filter(identifier.eq(schema_id.clone()))
+                    .filter(organization_id.eq(&update_request.organisation_id))
+                    .filter(deleted_at.is_null()),
             )
-        })?;
+            .set((
+                name.eq(update_request.name.clone()),
+                description.eq(update_request.description.clone()),
+                repo_origin.eq(update_request.repo_origin.clone()),
+                quick_links.eq(update_request
+                    .quick_links
+                    .as_deref()
+                    .and_then(stored_quick_links)),
+            ))
+            .execute(conn)
+            .map_err(|_| ApiError::Internal("Failed to update dbschema".to_string()))?;
 
-    if updated_rows == 0 {
-        return Err(status::Custom(
-            Status::NotFound,
-            "Dbschema not found".to_string(),
-        ));
-    }
+            if updated_rows == 0 {
+                return Err(ApiError::NotFound("Dbschema not found".to_string()));
+            }
 
-    let updated_dbschema = dbschema
-        .filter(identifier.eq(schema_id))
-        .first::<Dbschema>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving updated dbschema".to_string(),
+            let updated_dbschema = dbschema
+                .filter(identifier.eq(&schema_id))
+                .first::<Dbschema>(conn)
+                .map_err(|_| ApiError::Internal("Error retrieving updated dbschema".to_string()))?;
+
+            diesel::update(
+                dbschema_branch_dsl::dbschema_branch
+                    .filter(dbschema_branch_dsl::parent_id.eq(updated_dbschema.id))
+                    .filter(dbschema_branch_dsl::branch_name.eq(&branch_name))
+                    .filter(dbschema_branch_dsl::deleted_at.is_null()),
             )
+            .set(dbschema_branch_dsl::version.eq(update_request.version.clone()))
+            .execute(conn)
+            .map_err(|_| ApiError::Internal("Failed to update schema version".to_string()))?;
+
+            outbox::enqueue_catalog(
+                conn,
+                &update_request.organisation_id,
+                "dbschema",
+                &schema_id,
+                "updated",
+            )?;
+            Ok(updated_dbschema)
         })?;
 
-    diesel::update(
-        dbschema_branch_dsl::dbschema_branch
-            .filter(dbschema_branch_dsl::parent_id.eq(updated_dbschema.id))
-            .filter(dbschema_branch_dsl::branch_name.eq(&branch_name)),
-    )
-    .set(dbschema_branch_dsl::version.eq(update_request.version.clone()))
-    .execute(&mut conn)
-    .map_err(|_| {
-        status::Custom(
-            Status::InternalServerError,
-            "Failed to update schema version".to_string(),
-        )
-    })?;
+        invalidate(cache.map(|c| c.inner()), &update_request.organisation_id);
+
+        Ok(Json(updated_dbschema))
+    })
+}
 
-    Ok(Json(updated_dbschema))
+// Identifies a branch in outbox events, `<schema identifier>/<branch name>`
+fn branch_path(db_schema_: &Dbschema, branch_name: &str) -> String {
+    format!(
+        "{}/{}",
+        db_schema_.identifier.as_deref().unwrap_or_default(),
+        branch_name
+    )
 }
-fn fetch_dbschema_by_id(
+
+/// The schema and the requested branch as stored, documents are not resolved from the blob
+/// store yet so the ETag can be computed without reading them
+fn load_dbschema(
+    conn: &mut PgConnection,
     schema_id: &str,
-    branch: Option<&String>,
-    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-) -> Result<GetDbschemaByIdResponse, status::Custom<String>> {
+    branch: Option<&str>,
+) -> Result<GetDbschemaByIdResponse, ApiError> {
     use crate::models::schema::schema::dbschema::dsl::*;
-    use crate::models::schema::schema::dbschema_branch::dsl::*;
-
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
+    use crate::models::schema::schema::dbschema_branch::dsl as branch_dsl;
 
     let result_dbschema = dbschema
         .filter(identifier.eq(schema_id))
-        .first::<Dbschema>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::NotFound,
-                format!("Dbschema with id {} not found", schema_id),
-            )
-        })?;
+        .filter(deleted_at.is_null())
+        .first::<Dbschema>(conn)
+        .map_err(|_| ApiError::NotFound(format!("Dbschema with id {} not found", schema_id)))?;
 
     let mut response = GetDbschemaByIdResponse {
         id: result_dbschema.id,
@@ -281,21 +366,19 @@ fn fetch_dbschema_by_id(
     };
 
     if let Some(branch_name_val) = branch {
-        let result_branch: Dbschema_Branch = dbschema_branch
+        let result_branch: Dbschema_Branch = branch_dsl::dbschema_branch
             .filter(
-                parent_id
+                branch_dsl::parent_id
                     .eq(result_dbschema.id)
-                    .and(branch_name.eq(branch_name_val)),
+                    .and(branch_dsl::branch_name.eq(branch_name_val)),
             )
-            .first::<Dbschema_Branch>(&mut conn)
+            .filter(branch_dsl::deleted_at.is_null())
+            .first::<Dbschema_Branch>(conn)
             .map_err(|_| {
-                status::Custom(
-                    Status::NotFound,
-                    format!(
-                        "Dbschema branch with parent_id {} and branch_name {} not found",
-                        schema_id, branch_name_val
-                    ),
-                )
+                ApiError::NotFound(format!(
+                    "Dbschema branch with parent_id {} and branch_name {} not found",
+                    schema_id, branch_name_val
+                ))
             })?;
 
         response.version = result_branch.version.clone();
@@ -306,97 +389,194 @@ fn fetch_dbschema_by_id(
     Ok(response)
 }
 
+async fn fetch_dbschema_by_id(
+    schema_id: &str,
+    branch: Option<&String>,
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    blobs: Option<&Database>,
+    if_none_match: &IfNoneMatch,
+) -> Result<ETagged<Json<GetDbschemaByIdResponse>>, ApiError> {
+    let mut response = run_blocking(rdb, |conn| {
+        load_dbschema(conn, schema_id, branch.map(|b| b.as_str()))
+    })?;
+
+    let revision = etag(&response);
+    if if_none_match.matches(&revision) {
+        return Ok(ETagged::NotModified(revision));
+    }
+
+    response.data = resolve_document(blobs, response.data).await?;
+
+    Ok(ETagged::Body(revision, Json(response)))
+}
+
 #[openapi()]
 #[get("/dbschemas-branch/<schema_id>?<branch>")]
-pub fn get_dbschema_by_id(
+pub async fn get_dbschema_by_id(
     schema_id: String,
     branch: Option<String>,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    _claims: APIClaims,
-) -> Result<Json<GetDbschemaByIdResponse>, status::Custom<String>> {
-    let response = fetch_dbschema_by_id(&schema_id, branch.as_ref(), rdb)?;
-    Ok(Json(response))
+    claims: ActiveAPIClaims,
+    blobs: Option<&State<Database>>,
+    if_none_match: IfNoneMatch,
+) -> Result<ETagged<Json<GetDbschemaByIdResponse>>, ApiError> {
+    claims.require_scope(SCOPE_READ)?;
+    fetch_dbschema_by_id(
+        &schema_id,
+        branch.as_ref(),
+        rdb,
+        blobs.map(|b| b.inner()),
+        &if_none_match,
+    )
+    .await
 }
 
 #[openapi()]
 #[get("/public/dbschemas-branch/<schema_id>?<branch>")]
-pub fn get_dbschema_by_id_public(
+pub async fn get_dbschema_by_id_public(
     schema_id: String,
     branch: Option<String>,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-) -> Result<Json<GetDbschemaByIdResponse>, status::Custom<String>> {
-    let response = fetch_dbschema_by_id(&schema_id, branch.as_ref(), rdb)?;
-    Ok(Json(response))
+    blobs: Option<&State<Database>>,
+    if_none_match: IfNoneMatch,
+) -> Result<ETagged<Json<GetDbschemaByIdResponse>>, ApiError> {
+    fetch_dbschema_by_id(
+        &schema_id,
+        branch.as_ref(),
+        rdb,
+        blobs.map(|b| b.inner()),
+        &if_none_match,
+    )
+    .await
 }
 
 #[openapi()]
 #[get("/user-land/dbschemas-branch/<schema_id>?<branch>")]
-pub fn get_dbschema_by_id_userland(
+pub async fn get_dbschema_by_id_userland(
     schema_id: String,
     branch: Option<String>,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     _claims: Claims,
-) -> Result<Json<GetDbschemaByIdResponse>, status::Custom<String>> {
-    let response = fetch_dbschema_by_id(&schema_id, branch.as_ref(), rdb)?;
-    Ok(Json(response))
+    blobs: Option<&State<Database>>,
+    if_none_match: IfNoneMatch,
+) -> Result<ETagged<Json<GetDbschemaByIdResponse>>, ApiError> {
+    fetch_dbschema_by_id(
+        &schema_id,
+        branch.as_ref(),
+        rdb,
+        blobs.map(|b| b.inner()),
+        &if_none_match,
+    )
+    .await
 }
 
 #[openapi()]
 #[post("/dbschemas/<schema_id>/branches", data = "<branch_request>")]
-pub fn create_dbschema_branch(
+pub async fn create_dbschema_branch(
     schema_id: i64,
     branch_request: Json<CreateDbschemaBranchRequest>,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     _claims: Claims,
-) -> Result<Json<CreateDbschemaBranchResponse>, status::Custom<String>> {
+    cache: Option<&State<RedisPoolState>>,
+    blobs: Option<&State<Database>>,
+) -> Result<Json<CreateDbschemaBranchResponse>, ApiError> {
     use crate::models::schema::schema::dbschema::dsl::*;
-    use crate::models::schema::schema::dbschema_branch::dsl::*;
+    use crate::models::schema::schema::dbschema_branch::dsl as branch_dsl;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
+    observe_spec("schema", branch_request.data.as_deref());
+    let stored_data = store_document(
+        blobs.map(|b| b.inner()),
+        branch_request.data.clone(),
+        DBSCHEMA_DATA_MAX_LENGTH,
+    )
+    .await?;
+
+    run_blocking(rdb, |conn| {
+        // Check if the Dbschema exists
+        let parent_dbschema = dbschema
+            .find(schema_id)
+            .filter(deleted_at.is_null())
+            .first::<Dbschema>(conn)
+            .map_err(|_| ApiError::NotFound(format!("Dbschema with id {} not found", schema_id)))?;
+
+        if let Some(org_id) = &parent_dbschema.organization_id {
+            ensure_active(conn, org_id)?;
+        }
 
-    // Check if the Dbschema exists
-    let _ = dbschema
-        .find(schema_id)
-        .first::<Dbschema>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::NotFound,
-                format!("Dbschema with id {} not found", schema_id),
-            )
+        let inserted_branch = conn.transaction::<_, ApiError, _>(|conn| {
+            let existing_branch = branch_dsl::dbschema_branch
+                .filter(branch_dsl::parent_id.eq(schema_id))
+                .filter(branch_dsl::branch_name.eq(&branch_request.branch_name))
+                .for_update()
+                .first::<Dbschema_Branch>(conn)
+                .optional()?;
+
+            let branch = match existing_branch {
+                Some(branch) if branch.deleted_at.is_none() => Err(ApiError::Conflict(format!(
+                    "Dbschema branch {} already exists",
+                    branch_request.branch_name
+                ))),
+                // A deleted branch keeps its name reserved, creating it again starts it over
+                Some(branch) => diesel::update(branch_dsl::dbschema_branch.find(branch.id))
+                    .set((
+                        branch_dsl::data.eq(stored_data),
+                        branch_dsl::created_at.eq(Utc::now()),
+                        branch_dsl::updated_at.eq(Utc::now()),
+                        branch_dsl::version.eq(None::<String>),
+                        branch_dsl::pipeline_status.eq(None::<String>),
+                        branch_dsl::pipeline_changed_at.eq(None::<DateTime<Utc>>),
+                        branch_dsl::last_passed_at.eq(None::<DateTime<Utc>>),
+                        branch_dsl::deleted_at.eq(None::<DateTime<Utc>>),
+                    ))
+                    .get_result::<Dbschema_Branch>(conn)
+                    .map_err(|_| {
+                        ApiError::Internal("Failed to recreate Dbschema_Branch".to_string())
+                    }),
+                None => {
+                    let new_branch = Dbschema_BranchInsertable {
+                        parent_id: schema_id,
+                        branch_name: branch_request.branch_name.clone(),
+                        data: stored_data,
+                        created_at: Utc::now(),
+                        updated_at: Utc::now(),
+                        version: None,
+                        pipeline_status: None,
+                        pipeline_changed_at: None,
+                        last_passed_at: None,
+                    };
+
+                    diesel::insert_into(branch_dsl::dbschema_branch)
+                        .values(&new_branch)
+                        .get_result::<Dbschema_Branch>(conn)
+                        .map_err(|_| {
+                            ApiError::Internal("Failed to insert new Dbschema_Branch".to_string())
+                        })
+                }
+            }?;
+
+            if let Some(org_id) = &parent_dbschema.organization_id {
+                outbox::enqueue_catalog(
+                    conn,
+                    org_id,
+                    "dbschema_branch",
+                    &branch_path(&parent_dbschema, &branch.branch_name),
+                    "created",
+                )?;
+            }
+            Ok(branch)
         })?;
 
-    // Create the new Dbschema_Branch
-    let new_branch = Dbschema_BranchInsertable {
-        parent_id: schema_id,
-        branch_name: branch_request.branch_name.clone(),
-        data: branch_request.data.clone(),
-        created_at: Utc::now(),
-        updated_at: Utc::now(),
-        version: None,
-        pipeline_status: None,
-    };
-
-    let inserted_branch: Dbschema_Branch = diesel::insert_into(dbschema_branch)
-        .values(&new_branch)
-        .get_result::<Dbschema_Branch>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Failed to insert new Dbschema_Branch".to_string(),
-            )
-        })?;
+        if let Some(org_id) = &parent_dbschema.organization_id {
+            invalidate(cache.map(|c| c.inner()), org_id);
+        }
 
-    let response = CreateDbschemaBranchResponse {
-        message: "Dbschema branch created successfully".to_string(),
-        id: inserted_branch.id,
-    };
+        let response = CreateDbschemaBranchResponse {
+            message: "Dbschema branch created successfully".to_string(),
+            id: inserted_branch.id,
+        };
 
-    Ok(Json(response))
+        Ok(Json(response))
+    })
 }
 
 #[openapi()]
@@ -404,304 +584,337 @@ pub fn create_dbschema_branch(
     "/dbschemas/<schema_id>/branches/<branch_id>",
     data = "<branch_request>"
 )]
-pub fn update_dbschema_branch(
+pub async fn update_dbschema_branch(
     schema_id: String,
     branch_id: i64,
     branch_request: Json<UpdateDbschemaBranchRequest>,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     _claims: Claims,
-    groups: GroupMemberships
-) -> Result<Json<UpdateDbschemaBranchResponse>, status::Custom<String>> {
+    groups: GroupMemberships,
+    if_match: IfMatch,
+    cache: Option<&State<RedisPoolState>>,
+    blobs: Option<&State<Database>>,
+) -> Result<ETagged<Json<UpdateDbschemaBranchResponse>>, ApiError> {
     use crate::models::schema::schema::dbschema::dsl::*;
     use crate::models::schema::schema::dbschema_branch::dsl as branch_dsl;
     let memberships: Vec<String> = groups.0;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
+    observe_spec("schema", branch_request.data.as_deref());
+    let stored_data = store_document(
+        blobs.map(|b| b.inner()),
+        branch_request.data.clone(),
+        DBSCHEMA_DATA_MAX_LENGTH,
+    )
+    .await?;
+
+    run_blocking(rdb, |conn| {
+        // Check if the Dbschema and Dbschema_Branch exist
+        let db_schema_retrived = dbschema
+            .filter(identifier.eq(&schema_id))
+            .filter(deleted_at.is_null())
+            .first::<Dbschema>(conn)
+            .map_err(|_| ApiError::NotFound(format!("Dbschema with id {} not found", schema_id)))?;
+
+        // Check permission
+        if let Some(grp_id) = &db_schema_retrived.group_id {
+            if !memberships.contains(grp_id) {
+                return Err(ApiError::Forbidden(
+                    "Permission Denied: You are not authorized to update this schema branch."
+                        .to_string(),
+                ));
+            }
+        } else {
+            return Err(ApiError::Forbidden(
+                "Permission Denied: Dbschema group ID is missing.".to_string(),
+            ));
+        }
 
-    // Check if the Dbschema and Dbschema_Branch exist
-    let db_schema_retrived = dbschema
-        .filter(identifier.eq(&schema_id))
-        .first::<Dbschema>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::NotFound,
-                format!("Dbschema with id {} not found", schema_id),
-            )
+        if let Some(org_id) = &db_schema_retrived.organization_id {
+            ensure_active(conn, org_id)?;
+        }
+
+        let revision = conn.transaction::<_, ApiError, _>(|conn| {
+            // Lock the branch so a concurrent writer cannot slip in between the check and the update
+            let existing_branch = branch_dsl::dbschema_branch
+                .filter(
+                    branch_dsl::id
+                        .eq(branch_id)
+                        .and(branch_dsl::parent_id.eq(db_schema_retrived.id)),
+                )
+                .filter(branch_dsl::deleted_at.is_null())
+                .for_update()
+                .first::<Dbschema_Branch>(conn)
+                .map_err(|_| {
+                    ApiError::NotFound(format!(
+                        "Dbschema branch with id {} not found for schema {}",
+                        branch_id, schema_id
+                    ))
+                })?;
+
+            let current = load_dbschema(conn, &schema_id, Some(&existing_branch.branch_name))?;
+            if_match.verify(&etag(&current))?;
+
+            diesel::update(branch_dsl::dbschema_branch.filter(branch_dsl::id.eq(branch_id)))
+                .set((
+                    branch_dsl::branch_name.eq(branch_request.branch_name.clone()),
+                    branch_dsl::data.eq(stored_data),
+                    branch_dsl::updated_at.eq(Utc::now()),
+                ))
+                .execute(conn)
+                .map_err(|_| ApiError::Internal("Failed to update dbschema branch".to_string()))?;
+
+            if let Some(org_id) = &db_schema_retrived.organization_id {
+                outbox::enqueue_catalog(
+                    conn,
+                    org_id,
+                    "dbschema_branch",
+                    &branch_path(&db_schema_retrived, &branch_request.branch_name),
+                    "updated",
+                )?;
+                if branch_request.merged == Some(true) {
+                    webhooks::enqueue(
+                        conn,
+                        org_id,
+                        webhooks::SCHEMA_BRANCH_MERGED,
+                        json!({
+                            "schema": db_schema_retrived.identifier,
+                            "branch": branch_request.branch_name,
+                        }),
+                    )?;
+                }
+            }
+
+            let updated = load_dbschema(conn, &schema_id, Some(&branch_request.branch_name))?;
+            Ok(etag(&updated))
         })?;
 
-    // Check permission
-    if let Some(grp_id) = &db_schema_retrived.group_id {
-        if !memberships.contains(grp_id) {
-            return Err(status::Custom(
-                Status::Forbidden,
-                "Permission Denied: You are not authorized to update this schema branch.".to_string(),
-            ));
+        if let Some(org_id) = &db_schema_retrived.organization_id {
+            invalidate(cache.map(|c| c.inner()), org_id);
         }
-    } else {
-        return Err(status::Custom(
-            Status::Forbidden,
-            "Permission Denied: Dbschema group ID is missing.".to_string(),
-        ));
-    }
 
-    let _ = branch_dsl::dbschema_branch
-        .filter(
-            branch_dsl::id
-                .eq(branch_id)
-                .and(branch_dsl::parent_id.eq(db_schema_retrived.id)),
-        )
-        .first::<Dbschema_Branch>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::NotFound,
-                format!(
-                    "Dbschema branch with id {} not found for schema {}",
-                    branch_id, schema_id
-                ),
-            )
-        })?;
+        let response = UpdateDbschemaBranchResponse {
+            message: "Dbschema branch updated successfully".to_string(),
+            id: branch_id,
+        };
 
-    // Perform the update
-    let updated_rows =
-        diesel::update(branch_dsl::dbschema_branch.filter(branch_dsl::id.eq(branch_id)))
-            .set((
-                branch_dsl::branch_name.eq(branch_request.branch_name.clone()),
-                branch_dsl::data.eq(branch_request.data.clone()),
-                branch_dsl::updated_at.eq(Utc::now()),
-            ))
-            .execute(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Failed to update dbschema branch".to_string(),
-                )
-            })?;
+        Ok(ETagged::Body(revision, Json(response)))
+    })
+}
 
-    if updated_rows == 0 {
-        return Err(status::Custom(
-            Status::NotFound,
-            "Dbschema branch not found".to_string(),
-        ));
+fn identifier_taken(kind: &str) -> ApiError {
+    ApiError::Conflict(format!(
+        "{} identifier is already registered by another workspace",
+        kind
+    ))
+}
+
+/// Identifiers are unique across workspaces, one registered by another workspace is refused
+/// rather than moved over. Rows without a workspace can be claimed
+pub fn check_identifier_owner(
+    kind: &str,
+    owner: Option<Option<String>>,
+    org_id: &str,
+) -> Result<(), ApiError> {
+    match owner {
+        Some(Some(owner)) if owner != org_id => Err(identifier_taken(kind)),
+        _ => Ok(()),
     }
+}
 
-    let response = UpdateDbschemaBranchResponse {
-        message: "Dbschema branch updated successfully".to_string(),
-        id: branch_id,
+/// Creates or updates the service and its environment in one statement each, so concurrent
+/// CI jobs publishing the same service cannot race. Callers run it inside a transaction, which
+/// also covers the outbox event announcing it. The conflict update only applies to rows of the
+/// same workspace or without one, a service of another workspace is left alone and refused
+pub fn upsert_service(
+    conn: &mut PgConnection,
+    service_request: &UpdateServiceRequest,
+    stored_spec: String,
+) -> Result<i64, ApiError> {
+    use crate::models::schema::schema::service::dsl::*;
+    use crate::models::schema::schema::service_envs::dsl as service_env_dsl;
+
+    let new_service = ServiceInsertable {
+        identifier: service_request.identifier.clone(),
+        group_id: None, // Use the group_id from IAM service response
+        db_schema_id: service_request.db_schema_id.clone(),
+        service_type: service_request
+            .service_type
+            .clone()
+            .ok_or_else(|| ApiError::BadRequest("service_type is required".to_string()))?,
+        tables_json: Some(serde_json::to_string(&service_request.tables).unwrap()),
+        dependencies_json: Some(serde_json::to_string(&service_request.dependencies).unwrap()),
+        lang: service_request.lang.clone(),
+        organization_id: Some(service_request.organization_id.clone()),
+        description: Some(service_request.description.clone()),
+        repo_origin: service_request.repo_origin.clone(),
+        cache_schema_id: service_request.cache_schema_id.clone(),
+        message_queue_schema_id: service_request.message_queue_schema_id.clone(),
+        quick_links: service_request
+            .quick_links
+            .as_deref()
+            .and_then(stored_quick_links),
+        updated_at: Utc::now(),
     };
 
-    Ok(Json(response))
+    let service_id = diesel::insert_into(service)
+        .values(&new_service)
+        .on_conflict(identifier)
+        .do_update()
+        .set((
+            db_schema_id.eq(excluded(db_schema_id)),
+            description.eq(excluded(description)),
+            organization_id.eq(excluded(organization_id)),
+            service_type.eq(excluded(service_type)),
+            lang.eq(excluded(lang)),
+            dependencies_json.eq(excluded(dependencies_json)),
+            tables_json.eq(excluded(tables_json)),
+            repo_origin.eq(excluded(repo_origin)),
+            quick_links.eq(excluded(quick_links)),
+            cache_schema_id.eq(excluded(cache_schema_id)),
+            message_queue_schema_id.eq(excluded(message_queue_schema_id)),
+            updated_at.eq(excluded(updated_at)),
+            deleted_at.eq(None::<DateTime<Utc>>),
+        ))
+        // Soft deleted rows still hold their identifier, another workspace cannot take them over
+        .filter(
+            organization_id
+                .is_null()
+                .or(organization_id.eq(excluded(organization_id))),
+        )
+        .returning(id)
+        .get_result::<i64>(conn)
+        .optional()
+        .map_err(|_| ApiError::Internal("Error upserting service".to_string()))?
+        .ok_or_else(|| identifier_taken("Service"))?;
+
+    // Documents are stored by content hash, comparing what is stored tells whether the spec changed
+    let previous_spec = service_env_dsl::service_envs
+        .filter(service_env_dsl::parent_id.eq(service_id))
+        .filter(service_env_dsl::env.eq(&service_request.env))
+        .filter(service_env_dsl::deleted_at.is_null())
+        .select(service_env_dsl::spec)
+        .first::<String>(conn)
+        .optional()?;
+    let spec_changed = previous_spec.as_deref() != Some(stored_spec.as_str());
+
+    let new_service_env = Service_EnvsInsertable {
+        parent_id: service_id,
+        env: service_request.env.clone(),
+        base_url: service_request.base_url.clone(),
+        base_url_ws: service_request.base_url_ws.clone(),
+        spec: stored_spec,
+        updated_at: Some(Utc::now()),
+        version: service_request
+            .version
+            .clone()
+            .unwrap_or("0.0.0".to_string()),
+        pipeline_status: None,
+        pipeline_changed_at: None,
+        last_passed_at: None,
+    };
+
+    diesel::insert_into(service_env_dsl::service_envs)
+        .values(&new_service_env)
+        .on_conflict((service_env_dsl::parent_id, service_env_dsl::env))
+        .do_update()
+        .set((
+            service_env_dsl::base_url.eq(excluded(service_env_dsl::base_url)),
+            service_env_dsl::base_url_ws.eq(excluded(service_env_dsl::base_url_ws)),
+            service_env_dsl::spec.eq(excluded(service_env_dsl::spec)),
+            service_env_dsl::updated_at.eq(excluded(service_env_dsl::updated_at)),
+            service_env_dsl::version.eq(excluded(service_env_dsl::version)),
+            service_env_dsl::deleted_at.eq(None::<DateTime<Utc>>),
+        ))
+        .execute(conn)
+        .map_err(|_| ApiError::Internal("Error upserting service environment".to_string()))?;
+
+    outbox::enqueue_catalog(
+        conn,
+        &service_request.organization_id,
+        "service_env",
+        &format!("{}/{}", service_request.identifier, service_request.env),
+        "published",
+    )?;
+    if spec_changed {
+        webhooks::enqueue(
+            conn,
+            &service_request.organization_id,
+            webhooks::SERVICE_SPEC_CHANGED,
+            json!({
+                "identifier": service_request.identifier,
+                "env": service_request.env,
+                "version": service_request.version,
+            }),
+        )?;
+    }
+
+    Ok(service_id)
 }
 
 #[openapi()]
 #[put("/services", data = "<service_request>")]
 pub async fn update_or_create_service(
-    service_request: Json<UpdateServiceRequest>,
+    mut service_request: Json<UpdateServiceRequest>,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     iam_service_config: IAMService_config,
-    _claims: APIClaims,
-) -> Result<Json<UpdateServiceResponse>, status::Custom<String>> {
-    use crate::models::schema::schema::service::dsl::*;
-    use crate::models::schema::schema::service_envs::dsl as service_env_dsl;
-    println!("{:?}", service_request);
-
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
-
-    let service_identifier = &service_request.identifier;
-
-    // Check if the service exists
-    let existing_service = service
-        .filter(identifier.eq(service_identifier))
-        .first::<Service>(&mut conn)
-        .optional()
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving service".to_string(),
-            )
-        })?;
+    claims: ActiveAPIClaims,
+    idempotency_key: IdempotencyKey,
+    cache: Option<&State<RedisPoolState>>,
+    blobs: Option<&State<Database>>,
+) -> Result<Json<UpdateServiceResponse>, ApiError> {
+    Span::current().record("org_id", service_request.organization_id.as_str());
+    tracing::debug!(request = %redacted(&*service_request), "Publishing service");
+    validate_quick_links(service_request.quick_links.as_deref().unwrap_or_default())?;
+    observe_spec("service", Some(&service_request.spec));
+
+    let stored_spec = store_document(
+        blobs.map(|b| b.inner()),
+        Some(service_request.spec.clone()),
+        SERVICE_SPEC_MAX_LENGTH,
+    )
+    .await?
+    .unwrap_or_default();
 
-    println!("{:?}", &service_request.service_type.clone().unwrap());
-    println!("{:?}", existing_service);
-    let service_id = if let Some(s) = existing_service {
-        diesel::update(service.filter(id.eq(s.id)))
-            .set((
-                db_schema_id.eq(&service_request.db_schema_id),
-                description.eq(&service_request.description),
-                organization_id.eq(&service_request.organization_id),
-                service_type.eq(&service_request.service_type.clone().unwrap()),
-                lang.eq(&service_request.lang.clone().unwrap()),
-                dependencies_json.eq(Some(
-                    serde_json::to_string(&service_request.dependencies).unwrap(),
-                )),
-                tables_json.eq(Some(
-                    serde_json::to_string(&service_request.tables).unwrap(),
-                )),
-                repo_origin.eq(&service_request.repo_origin),
-                quick_links.eq(&service_request.quick_links),
-                cache_schema_id.eq(&service_request.cache_schema_id),
-                message_queue_schema_id.eq(&service_request.message_queue_schema_id),
-            ))
-            .execute(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Error updating service db_schema_id".to_string(),
-                )
-            })?;
+    run_blocking(rdb, |conn| {
+        service_request.organization_id = canonical_slug(conn, &service_request.organization_id)?;
+        claims.authorize(conn, &service_request.organization_id, SCOPE_WRITE)?;
+        ensure_active(conn, &service_request.organization_id)?;
 
-        s.id
-    } else {
-        let new_service = ServiceInsertable {
-            identifier: service_identifier.clone(),
-            group_id: None, // Use the group_id from IAM service response
-            db_schema_id: service_request.db_schema_id.clone(),
-            service_type: service_request
-                .service_type
-                .clone()
-                .expect("Missing service type"),
-            tables_json: Some(serde_json::to_string(&service_request.tables).unwrap()),
-            dependencies_json: Some(serde_json::to_string(&service_request.dependencies).unwrap()),
-            lang: service_request.lang.clone(),
-            organization_id: Some(service_request.organization_id.clone()),
-            description: Some(service_request.description.clone()),
-            repo_origin: service_request.repo_origin.clone(),
-            cache_schema_id: service_request.cache_schema_id.clone(),
-            message_queue_schema_id: service_request.message_queue_schema_id.clone(),
-            quick_links: service_request.quick_links.clone(),
-        };
+        let key = idempotency_key.0.as_deref();
+        let key_scope = format!("services:{}", claims.sub);
+        let hash = request_hash(&*service_request);
 
-        diesel::insert_into(service)
-            .values(&new_service)
-            .returning(id)
-            .get_result::<i64>(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Error creating service".to_string(),
-                )
-            })?
-    };
+        let response = conn.transaction::<_, ApiError, _>(|conn| {
+            if let Some(replayed) = idempotency::claim(conn, key, &key_scope, &hash)? {
+                return Ok(replayed);
+            }
 
-    // Check if the service environment exists
-    let existing_service_env = service_env_dsl::service_envs
-        .filter(
-            service_env_dsl::parent_id
-                .eq(service_id)
-                .and(service_env_dsl::env.eq(&service_request.env)),
-        )
-        .first::<Service_Envs>(&mut conn)
-        .optional()
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving service environment".to_string(),
-            )
-        })?;
+            let response = UpdateServiceResponse {
+                message: "Service and environment updated successfully".to_string(),
+                service_id: upsert_service(conn, &service_request, stored_spec)?,
+            };
 
-    if let Some(service_env) = existing_service_env {
-        // Update the existing service environment
-        diesel::update(
-            service_env_dsl::service_envs.filter(service_env_dsl::id.eq(service_env.id)),
-        )
-        .set((
-            service_env_dsl::base_url.eq(&service_request.base_url),
-            service_env_dsl::base_url_ws.eq(&service_request.base_url_ws),
-            service_env_dsl::spec.eq(&service_request.spec),
-            service_env_dsl::updated_at.eq(Utc::now()),
-            service_env_dsl::version.eq(service_request
-                .version
-                .clone()
-                .unwrap_or("0.0.0".to_string())),
-        ))
-        .execute(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error updating service environment".to_string(),
-            )
+            idempotency::complete(conn, key, &key_scope, &response)?;
+            Ok(response)
         })?;
-    } else {
-        // Create a new service environment
-        let new_service_env = Service_EnvsInsertable {
-            parent_id: service_id,
-            env: service_request.env.clone(),
-            base_url: service_request.base_url.clone(),
-            base_url_ws: service_request.base_url_ws.clone(),
-            spec: service_request.spec.clone(),
-            updated_at: Some(Utc::now()),
-            version: service_request.version.clone().expect("Version is missing"),
-            pipeline_status: None,
-        };
-
-        diesel::insert_into(service_env_dsl::service_envs)
-            .values(&new_service_env)
-            .execute(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Error creating service environment".to_string(),
-                )
-            })?;
-    }
 
-    let response = UpdateServiceResponse {
-        message: "Service and environment updated successfully".to_string(),
-        service_id,
-    };
+        invalidate(cache.map(|c| c.inner()), &service_request.organization_id);
 
-    Ok(Json(response))
+        Ok(Json(response))
+    })
 }
 
-fn fetch_services_and_envs(
-    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    org_id: &str,
-    page_number: Option<&String>,
-    page_size: Option<&String>,
-) -> Result<Vec<ServicesTrimmedResponse>, rocket::http::Status> {
-    use crate::models::schema::schema::service::dsl::*;
-    use crate::models::schema::schema::service_envs::dsl::*;
-
-    let mut conn = rdb
-        .get()
-        .map_err(|_| rocket::http::Status::ServiceUnavailable)?;
-
-    let page_number = page_number
-        .as_deref()
-        .unwrap_or(&"1".to_string())
-        .parse::<i64>()
-        .unwrap_or(1);
-    let page_size = page_size
-        .as_deref()
-        .unwrap_or(&"10".to_string())
-        .parse::<i64>()
-        .unwrap_or(10);
-
-    let offset = (page_number - 1) * page_size;
-
-    // Query services and their associated environments
-    let services_with_envs = service
-        .filter(organization_id.eq(org_id))
-        .offset(offset)
-        .limit(page_size)
-        .load::<Service>(&mut conn)
-        .map_err(|_| rocket::http::Status::InternalServerError)?
-        .into_iter()
-        .map(|s| {
-            let envs = service_envs
-                .filter(parent_id.eq(s.id))
-                .load::<Service_Envs>(&mut conn)
-                .map_err(|_| rocket::http::Status::InternalServerError)?;
+/// Groups environments loaded for a whole page under their service, keeping the page order
+pub fn assemble_services(
+    services: Vec<Service>,
+    envs: Vec<Service_Envs>,
+) -> Result<Vec<ServicesTrimmedResponse>, ApiError> {
+    let grouped_envs = envs.grouped_by(&services);
 
+    services
+        .into_iter()
+        .zip(grouped_envs)
+        .map(|(s, envs)| {
             let env_responses: Vec<ServicesEnvTrimmedResponse> = envs
                 .into_iter()
                 .map(|e| ServicesEnvTrimmedResponse {
@@ -718,8 +931,8 @@ fn fetch_services_and_envs(
             Ok(ServicesTrimmedResponse {
                 identifier: s.identifier,
                 envs: env_responses,
-                tables: serde_json::from_str(&s.tables_json.unwrap()).unwrap(),
-                dependencies: serde_json::from_str(&s.dependencies_json.unwrap()).unwrap(),
+                tables: parse_identifiers(s.tables_json)?,
+                dependencies: parse_identifiers(s.dependencies_json)?,
                 db_schema_id: s.db_schema_id,
                 cache_schema_id: s.cache_schema_id,
                 message_queue_schema_id: s.message_queue_schema_id,
@@ -728,49 +941,235 @@ fn fetch_services_and_envs(
                 organization_id: s.organization_id.unwrap_or(String::from("")),
                 description: s.description.unwrap_or(String::from("")),
                 repo_origin: s.repo_origin,
-                quick_links: s.quick_links,
+                quick_links: parse_quick_links(s.quick_links.as_deref()),
+                updated_at: s.updated_at,
             })
         })
-        .collect::<Result<Vec<ServicesTrimmedResponse>, rocket::http::Status>>()?;
+        .collect()
+}
+
+/// A page of the services of the workspace with their environments, in two queries besides the
+/// total whatever the page size
+pub fn load_services_page(
+    conn: &mut PgConnection,
+    org_id: &str,
+    page_request: &PageRequest,
+    sort: Sort,
+    filters: &ServiceListQuery,
+) -> Result<Page<ServicesTrimmedResponse>, ApiError> {
+    use crate::models::schema::schema::service::dsl::*;
+    use crate::models::schema::schema::service_envs::dsl as service_envs_dsl;
+
+    // Shared by the page and the total
+    let filtered = || {
+        let mut query = service
+            .filter(organization_id.eq(org_id))
+            .filter(deleted_at.is_null())
+            .into_boxed();
+        if let Some(value) = &filters.service_type {
+            query = query.filter(service_type.eq(value));
+        }
+        if let Some(value) = &filters.lang {
+            query = query.filter(lang.eq(value));
+        }
+        if let Some(value) = &filters.db_schema_id {
+            query = query.filter(db_schema_id.eq(value));
+        }
+        if let Some(value) = &filters.dependency {
+            query = query.filter(dependencies_json.like(format!("%\"{}\"%", like_escape(value))));
+        }
+        if filters.env.is_some() || filters.pipeline_status.is_some() {
+            let mut envs = service_envs_dsl::service_envs
+                .filter(service_envs_dsl::deleted_at.is_null())
+                .select(service_envs_dsl::parent_id)
+                .into_boxed();
+            if let Some(value) = &filters.env {
+                envs = envs.filter(service_envs_dsl::env.eq(value));
+            }
+            if let Some(value) = &filters.pipeline_status {
+                envs = envs.filter(service_envs_dsl::pipeline_status.eq(value));
+            }
+            query = query.filter(id.eq_any(envs));
+        }
+        query
+    };
 
-    Ok(services_with_envs)
+    let mut query = match (sort.key, sort.descending) {
+        (SortKey::Id, false) => filtered().order(id.asc()),
+        (SortKey::Id, true) => filtered().order(id.desc()),
+        (SortKey::Identifier, false) => filtered().order((identifier.asc(), id.asc())),
+        (SortKey::Identifier, true) => filtered().order((identifier.desc(), id.desc())),
+        (SortKey::UpdatedAt, false) => filtered().order((updated_at.asc(), id.asc())),
+        (SortKey::UpdatedAt, true) => filtered().order((updated_at.desc(), id.desc())),
+    };
+    if let Some(after_id) = page_request.after_id {
+        query = match (sort.key, sort.descending) {
+            (SortKey::Id, false) => query.filter(id.gt(after_id)),
+            (SortKey::Id, true) => query.filter(id.lt(after_id)),
+            (SortKey::Identifier, false) => {
+                let key = page_request.sort_key()?.to_string();
+                query.filter(
+                    identifier
+                        .gt(key.clone())
+                        .or(identifier.eq(key).and(id.gt(after_id))),
+                )
+            }
+            (SortKey::Identifier, true) => {
+                let key = page_request.sort_key()?.to_string();
+                query.filter(
+                    identifier
+                        .lt(key.clone())
+                        .or(identifier.eq(key).and(id.lt(after_id))),
+                )
+            }
+            (SortKey::UpdatedAt, false) => {
+                let key = page_request.sort_timestamp()?;
+                query.filter(
+                    updated_at
+                        .gt(key)
+                        .or(updated_at.eq(key).and(id.gt(after_id))),
+                )
+            }
+            (SortKey::UpdatedAt, true) => {
+                let key = page_request.sort_timestamp()?;
+                query.filter(
+                    updated_at
+                        .lt(key)
+                        .or(updated_at.eq(key).and(id.lt(after_id))),
+                )
+            }
+        };
+    }
+
+    let services = query
+        .limit(page_request.fetch_limit())
+        .load::<Service>(conn)
+        .map_err(|_| ApiError::Internal("Error retrieving services".to_string()))?;
+
+    let total = filtered()
+        .count()
+        .get_result::<i64>(conn)
+        .map_err(|_| ApiError::Internal("Error counting services".to_string()))?;
+
+    let page = page_request.page(services, total, |s| {
+        (sort.cursor_key(&s.identifier, s.updated_at), s.id)
+    });
+
+    // Environments of every service on the page in one query
+    let envs = Service_Envs::belonging_to(&page.items)
+        .filter(service_envs_dsl::deleted_at.is_null())
+        .order(service_envs_dsl::id.asc())
+        .load::<Service_Envs>(conn)
+        .map_err(|_| ApiError::Internal("Error retrieving service environments".to_string()))?;
+
+    Ok(Page {
+        items: assemble_services(page.items, envs)?,
+        next_cursor: page.next_cursor,
+        total: page.total,
+    })
+}
+
+fn fetch_services_and_envs(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    cache: Option<&RedisPoolState>,
+    org_id: &str,
+    cursor: Option<&str>,
+    limit: Option<i64>,
+    filters: &ServiceListQuery,
+) -> Result<Page<Projected<ServicesTrimmedResponse>>, ApiError> {
+    let page_request = PageRequest::parse(cursor, limit)?;
+    let sort = Sort::parse(filters.sort.as_deref())?;
+    // The projection is applied on the cached page, `fields` is left out of the key
+    let filter_hash = content_hash(
+        &serde_json::to_string(&(
+            &filters.service_type,
+            &filters.lang,
+            &filters.env,
+            &filters.pipeline_status,
+            &filters.db_schema_id,
+            &filters.dependency,
+        ))
+        .unwrap_or_default(),
+    );
+    let cache_key = format!(
+        "services-and-envs:{}:{}:{}",
+        filter_hash,
+        sort.cache_key(),
+        page_request.cache_key()
+    );
+
+    let page = cached(cache, org_id, &cache_key, || {
+        run_blocking(rdb, |conn| {
+            load_services_page(conn, org_id, &page_request, sort, filters)
+        })
+    })?;
+
+    project(page, filters.fields.as_deref())
 }
 
 #[openapi]
-#[get("/user-land/services-and-envs/<org_id>?<page_number>&<page_size>")]
+#[get("/user-land/services-and-envs/<org_id>?<cursor>&<limit>&<filters..>")]
 pub fn get_services_and_envs_user_land(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     _claims: Claims,
     org_id: String,
-    page_number: Option<String>,
-    page_size: Option<String>,
-) -> Result<Json<Vec<ServicesTrimmedResponse>>, rocket::http::Status> {
-    let response = fetch_services_and_envs(rdb, &org_id, page_number.as_ref(), page_size.as_ref())?;
+    cursor: Option<String>,
+    limit: Option<i64>,
+    filters: ServiceListQuery,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<Page<Projected<ServicesTrimmedResponse>>>, ApiError> {
+    let response = fetch_services_and_envs(
+        rdb,
+        cache.map(|c| c.inner()),
+        &org_id,
+        cursor.as_deref(),
+        limit,
+        &filters,
+    )?;
     Ok(Json(response))
 }
 
 #[openapi]
-#[get("/public/services-and-envs/<org_id>?<page_number>&<page_size>")]
+#[get("/public/services-and-envs/<org_id>?<cursor>&<limit>&<filters..>")]
 pub fn get_services_and_envs_public(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     org_id: String,
-    page_number: Option<String>,
-    page_size: Option<String>,
-) -> Result<Json<Vec<ServicesTrimmedResponse>>, rocket::http::Status> {
-    let response = fetch_services_and_envs(rdb, &org_id, page_number.as_ref(), page_size.as_ref())?;
+    cursor: Option<String>,
+    limit: Option<i64>,
+    filters: ServiceListQuery,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<Page<Projected<ServicesTrimmedResponse>>>, ApiError> {
+    let response = fetch_services_and_envs(
+        rdb,
+        cache.map(|c| c.inner()),
+        &org_id,
+        cursor.as_deref(),
+        limit,
+        &filters,
+    )?;
     Ok(Json(response))
 }
 
 #[openapi]
-#[get("/services-and-envs/<org_id>?<page_number>&<page_size>")]
+#[get("/services-and-envs/<org_id>?<cursor>&<limit>&<filters..>")]
 pub fn get_services_and_envs(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    _claims: APIClaims,
+    claims: ActiveAPIClaims,
     org_id: String,
-    page_number: Option<String>,
-    page_size: Option<String>,
-) -> Result<Json<Vec<ServicesTrimmedResponse>>, rocket::http::Status> {
-    let response = fetch_services_and_envs(rdb, &org_id, page_number.as_ref(), page_size.as_ref())?;
+    cursor: Option<String>,
+    limit: Option<i64>,
+    filters: ServiceListQuery,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<Page<Projected<ServicesTrimmedResponse>>>, ApiError> {
+    run_blocking(rdb, |conn| claims.authorize(conn, &org_id, SCOPE_READ))?;
+    let response = fetch_services_and_envs(
+        rdb,
+        cache.map(|c| c.inner()),
+        &org_id,
+        cursor.as_deref(),
+        limit,
+        &filters,
+    )?;
     Ok(Json(response))
 }
 
@@ -778,63 +1177,70 @@ pub fn get_services_and_envs(
 #[get("/get-current-workspace")]
 pub fn get_current_workspace(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    claims: APIClaims,
-) -> Result<Json<APISessionDetailsResponse>, rocket::http::Status> {
+    claims: ActiveAPIClaims,
+) -> Result<Json<APISessionDetailsResponse>, ApiError> {
     use crate::models::schema::schema::organization::dsl::*;
-    let mut conn = rdb
-        .get()
-        .map_err(|_| rocket::http::Status::InternalServerError)?;
-
-    let result = organization
-        .filter(group_id.eq(claims.sub.clone()))
-        .first::<Organization>(&mut conn)
-        .optional()
-        .map_err(|_| rocket::http::Status::InternalServerError)?;
-
-    if let Some(org) = result {
-        let session_details = APISessionDetailsResponse {
-            sub: claims.sub,
-            exp: claims.exp,
-            scopes: claims.scopes,
-            group_id: claims.group_id,
-            org_id: org.slug, // Assuming org_id maps to group_id in Organization
-        };
-        Ok(Json(session_details))
-    } else {
-        Err(rocket::http::Status::NotFound)
-    }
+    claims.require_scope(SCOPE_READ)?;
+    let claims = claims.0;
+    run_blocking(rdb, |conn| {
+        let result = organization
+            .filter(group_id.eq(claims.sub.clone()))
+            .filter(deleted_at.is_null())
+            .first::<Organization>(conn)
+            .optional()
+            .map_err(|_| ApiError::Internal("Error retrieving workspace".to_string()))?;
+
+        if let Some(org) = result {
+            let session_details = APISessionDetailsResponse {
+                sub: claims.sub,
+                exp: claims.exp,
+                scopes: claims.scopes,
+                group_id: claims.group_id,
+                org_id: org.slug, // Assuming org_id maps to group_id in Organization
+            };
+            Ok(Json(session_details))
+        } else {
+            Err(ApiError::NotFound("Workspace not found".to_string()))
+        }
+    })
 }
-fn fetch_service_and_env_by_id(
+async fn fetch_service_and_env_by_id(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    blobs: Option<&Database>,
     org_id: &str,
     service_identifier: &str,
     env: &str,
-) -> Result<ServicesEnvResponse, rocket::http::Status> {
+) -> Result<ServicesEnvResponse, ApiError> {
     use crate::models::schema::schema::service::dsl::*;
     use crate::models::schema::schema::service_envs::dsl as service_envs_dsl;
 
-    let mut conn = rdb
-        .get()
-        .map_err(|_| rocket::http::Status::ServiceUnavailable)?;
-
-    // Query the service by ID
-    let service_item = service
-        .filter(identifier.eq(service_identifier))
-        .filter(organization_id.eq(org_id))
-        .first::<Service>(&mut conn)
-        .map_err(|_| rocket::http::Status::NotFound)?;
+    let env_item = run_blocking(rdb, |conn| {
+        // Query the service by ID
+        let service_item = service
+            .filter(identifier.eq(service_identifier))
+            .filter(organization_id.eq(org_id))
+            .filter(deleted_at.is_null())
+            .first::<Service>(conn)
+            .map_err(|_| ApiError::NotFound("Service not found".to_string()))?;
+
+        // Query the specific environment for the service
+        service_envs_dsl::service_envs
+            .filter(service_envs_dsl::parent_id.eq(service_item.id))
+            .filter(service_envs_dsl::env.eq(env))
+            .filter(service_envs_dsl::deleted_at.is_null())
+            .first::<Service_Envs>(conn)
+            .map_err(|_| ApiError::NotFound("Service environment not found".to_string()))
+    })?;
 
-    // Query the specific environment for the service
-    let env_item = service_envs_dsl::service_envs
-        .filter(service_envs_dsl::parent_id.eq(service_item.id))
-        .filter(service_envs_dsl::env.eq(env))
-        .first::<Service_Envs>(&mut conn)
-        .map_err(|_| rocket::http::Status::NotFound)?;
+    let spec = resolve_document(blobs, Some(env_item.spec))
+        .await?
+        .unwrap_or_default();
 
     // Transform `ServiceEnvs` into `ServicesEnvResponse`
     let env_response = ServicesEnvResponse {
-        spec: env_item.spec,
+        spec,
         base_url: env_item.base_url,
+        base_url_ws: env_item.base_url_ws,
     };
 
     Ok(env_response)
@@ -842,39 +1248,64 @@ fn fetch_service_and_env_by_id(
 
 #[openapi]
 #[get("/services-and-envs/<org_id>/<service_identifier>/<env>")]
-pub fn get_service_and_env_by_id(
+pub async fn get_service_and_env_by_id(
     org_id: String,
     service_identifier: String,
     env: String,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    claims: APIClaims,
-) -> Result<Json<ServicesEnvResponse>, rocket::http::Status> {
-    let env_response = fetch_service_and_env_by_id(rdb, &org_id, &service_identifier, &env)?;
+    claims: ActiveAPIClaims,
+    blobs: Option<&State<Database>>,
+) -> Result<Json<ServicesEnvResponse>, ApiError> {
+    run_blocking(rdb, |conn| claims.authorize(conn, &org_id, SCOPE_READ))?;
+    let env_response = fetch_service_and_env_by_id(
+        rdb,
+        blobs.map(|b| b.inner()),
+        &org_id,
+        &service_identifier,
+        &env,
+    )
+    .await?;
     Ok(Json(env_response))
 }
 
 #[openapi]
 #[get("/public/services-and-envs/<org_id>/<service_identifier>/<env>")]
-pub fn get_service_and_env_by_id_public(
+pub async fn get_service_and_env_by_id_public(
     org_id: String,
     service_identifier: String,
     env: String,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-) -> Result<Json<ServicesEnvResponse>, rocket::http::Status> {
-    let env_response = fetch_service_and_env_by_id(rdb, &org_id, &service_identifier, &env)?;
+    blobs: Option<&State<Database>>,
+) -> Result<Json<ServicesEnvResponse>, ApiError> {
+    let env_response = fetch_service_and_env_by_id(
+        rdb,
+        blobs.map(|b| b.inner()),
+        &org_id,
+        &service_identifier,
+        &env,
+    )
+    .await?;
     Ok(Json(env_response))
 }
 
 #[openapi]
 #[get("/user-land/services-and-envs/<org_id>/<service_identifier>/<env>")]
-pub fn get_service_and_env_by_id_user_land(
+pub async fn get_service_and_env_by_id_user_land(
     org_id: String,
     service_identifier: String,
     env: String,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     claims: Claims,
-) -> Result<Json<ServicesEnvResponse>, rocket::http::Status> {
-    let env_response = fetch_service_and_env_by_id(rdb, &org_id, &service_identifier, &env)?;
+    blobs: Option<&State<Database>>,
+) -> Result<Json<ServicesEnvResponse>, ApiError> {
+    let env_response = fetch_service_and_env_by_id(
+        rdb,
+        blobs.map(|b| b.inner()),
+        &org_id,
+        &service_identifier,
+        &env,
+    )
+    .await?;
     Ok(Json(env_response))
 }
 
@@ -884,374 +1315,602 @@ pub fn get_service_by_id(
     service_identifier: String,
     org_id: String,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    claims: APIClaims,
-) -> Result<Json<ServiceResponse>, rocket::http::Status> {
+    claims: ActiveAPIClaims,
+) -> Result<Json<ServiceResponse>, ApiError> {
     use crate::models::schema::schema::service::dsl::*;
 
-    let mut conn = rdb
-        .get()
-        .map_err(|_| rocket::http::Status::ServiceUnavailable)?;
-
-    // Query the service by ID and ensure it belongs to one of the user's groups
-    let service_item = service
-        .filter(organization_id.eq(org_id.clone()))
-        .filter(identifier.eq(service_identifier))
-        .first::<Service>(&mut conn)
-        .map_err(|_| rocket::http::Status::NotFound)?;
-
-    let dependencies: Vec<String> =
-        serde_json::from_str(&service_item.dependencies_json.unwrap()).unwrap();
-
-    let tables: Vec<String> = serde_json::from_str(&service_item.tables_json.unwrap()).unwrap();
-
-    let response = ServiceResponse {
-        id: service_item.id,
-        identifier: service_item.identifier,
-        group_id: service_item.group_id,
-        db_schema_id: service_item.db_schema_id.unwrap_or(String::from("")),
-        dependencies: dependencies,
-        tables: tables,
-        organization_id: service_item.organization_id.unwrap_or(String::from("")),
-        description: service_item.description.unwrap_or(String::from("")),
-        repo_origin: service_item.repo_origin,
-    };
+    run_blocking(rdb, |conn| {
+        claims.authorize(conn, &org_id, SCOPE_READ)?;
+
+        // Query the service by ID and ensure it belongs to one of the user's groups
+        let service_item = service
+            .filter(organization_id.eq(org_id.clone()))
+            .filter(identifier.eq(service_identifier))
+            .filter(deleted_at.is_null())
+            .first::<Service>(conn)
+            .map_err(|_| ApiError::NotFound("Service not found".to_string()))?;
+
+        let dependencies = parse_identifiers(service_item.dependencies_json)?;
+
+        let tables = parse_identifiers(service_item.tables_json)?;
+
+        let response = ServiceResponse {
+            id: service_item.id,
+            identifier: service_item.identifier,
+            group_id: service_item.group_id,
+            db_schema_id: service_item.db_schema_id.unwrap_or(String::from("")),
+            dependencies: dependencies,
+            tables: tables,
+            organization_id: service_item.organization_id.unwrap_or(String::from("")),
+            description: service_item.description.unwrap_or(String::from("")),
+            repo_origin: service_item.repo_origin,
+        };
 
-    Ok(Json(response))
+        Ok(Json(response))
+    })
 }
 
-#[openapi()]
-#[post("/create_or_update_package", data = "<package_request>")]
-pub async fn create_or_update_package(
-    package_request: Json<CreateOrUpdatePackageRequest>,
-    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    iam_service_config: IAMService_config,
-    _claims: APIClaims,
-) -> Result<status::Created<Json<CreateOrUpdatePackageResponse>>, status::Custom<String>> {
+/// Same as `upsert_service` for packages and their per environment version
+pub fn upsert_package(
+    conn: &mut PgConnection,
+    package_request: &CreateOrUpdatePackageRequest,
+) -> Result<i64, ApiError> {
     use crate::models::schema::schema::package::dsl::*;
     use crate::models::schema::schema::package_env::dsl as package_env_dsl;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
+    let new_package = PackageInsertable {
+        identifier: package_request.identifier.clone(),
+        package_type: package_request.package_type.clone(),
+        lang: package_request.lang.clone(),
+        created_at: Some(Utc::now()),
+        updated_at: Utc::now(),
+        group_id: None, // Use the group_id from IAM service response
+        description: Some(package_request.description.clone()),
+        organization_id: Some(package_request.organization_id.clone()),
+        dependencies_json: Some(serde_json::to_string(&package_request.dependencies).unwrap()),
+        repo_origin: package_request.repo_origin.clone(),
+        quick_links: package_request
+            .quick_links
+            .as_deref()
+            .and_then(stored_quick_links),
+    };
+
+    let package_id = diesel::insert_into(package)
+        .values(&new_package)
+        .on_conflict(identifier)
+        .do_update()
+        .set((
+            organization_id.eq(excluded(organization_id)),
+            package_type.eq(excluded(package_type)),
+            lang.eq(excluded(lang)),
+            updated_at.eq(excluded(updated_at)),
+            dependencies_json.eq(excluded(dependencies_json)),
+            description.eq(excluded(description)),
+            repo_origin.eq(excluded(repo_origin)),
+            quick_links.eq(excluded(quick_links)),
+            deleted_at.eq(None::<DateTime<Utc>>),
+        ))
+        // Soft deleted rows still hold their identifier, another workspace cannot take them over
+        .filter(
+            organization_id
+                .is_null()
+                .or(organization_id.eq(excluded(organization_id))),
         )
-    })?;
+        .returning(id)
+        .get_result::<i64>(conn)
+        .optional()
+        .map_err(|_| ApiError::Internal("Error upserting package".to_string()))?
+        .ok_or_else(|| identifier_taken("Package"))?;
 
-    let package_identifier = &package_request.identifier;
+    let previous_version = package_env_dsl::package_env
+        .filter(package_env_dsl::parent_id.eq(package_id))
+        .filter(package_env_dsl::env.eq(&package_request.env))
+        .filter(package_env_dsl::deleted_at.is_null())
+        .select(package_env_dsl::version)
+        .first::<String>(conn)
+        .optional()?;
 
-    // Check if the package exists
-    let existing_package = package
-        .filter(identifier.eq(package_identifier))
-        .first::<Package>(&mut conn)
-        .optional()
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving package".to_string(),
-            )
-        })?;
+    let new_env = Package_EnvInsertable {
+        parent_id: package_id,
+        env: package_request.env.clone(),
+        version: package_request.version.clone(),
+        pipeline_status: None,
+        pipeline_changed_at: None,
+        last_passed_at: None,
+    };
 
-    let package_id = if let Some(p) = existing_package {
-        diesel::update(package.filter(id.eq(p.id)))
-            .set((
-                package_type.eq(&package_request.package_type),
-                lang.eq(&package_request.lang),
-                // version.eq(&package_request.version),
-                updated_at.eq(Utc::now()),
-                dependencies_json.eq(Some(
-                    serde_json::to_string(&package_request.dependencies).unwrap(),
-                )),
-                description.eq(&package_request.description),
-                repo_origin.eq(&package_request.repo_origin),
-                quick_links.eq(&package_request.quick_links),
-            ))
-            .execute(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Error updating package".to_string(),
-                )
-            })?;
+    diesel::insert_into(package_env_dsl::package_env)
+        .values(&new_env)
+        .on_conflict((package_env_dsl::parent_id, package_env_dsl::env))
+        .do_update()
+        .set((
+            package_env_dsl::version.eq(excluded(package_env_dsl::version)),
+            package_env_dsl::deleted_at.eq(None::<DateTime<Utc>>),
+        ))
+        .execute(conn)
+        .map_err(|_| ApiError::Internal("Error upserting package environment".to_string()))?;
+
+    outbox::enqueue_catalog(
+        conn,
+        &package_request.organization_id,
+        "package_env",
+        &format!("{}/{}", package_request.identifier, package_request.env),
+        "published",
+    )?;
+    if previous_version.as_deref() != Some(package_request.version.as_str()) {
+        webhooks::enqueue(
+            conn,
+            &package_request.organization_id,
+            webhooks::PACKAGE_VERSION_PUBLISHED,
+            json!({
+                "identifier": package_request.identifier,
+                "env": package_request.env,
+                "version": package_request.version,
+                "previous_version": previous_version,
+            }),
+        )?;
+    }
 
-        p.id
-    } else {
-        let new_package = PackageInsertable {
-            identifier: package_identifier.clone(),
-            package_type: package_request.package_type.clone(),
-            lang: package_request.lang.clone(),
-            created_at: Some(Utc::now()),
-            updated_at: Utc::now(),
-            group_id: None, // Use the group_id from IAM service response
-            description: Some(package_request.description.clone()),
-            organization_id: Some(package_request.organization_id.clone()),
-            dependencies_json: Some(serde_json::to_string(&package_request.dependencies).unwrap()),
-            repo_origin: package_request.repo_origin.clone(),
-            quick_links: package_request.quick_links.clone(),
-        };
+    Ok(package_id)
+}
 
-        diesel::insert_into(package)
-            .values(&new_package)
-            .returning(id)
-            .get_result::<i64>(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Error creating package".to_string(),
-                )
-            })?
-    };
+#[openapi()]
+#[post("/create_or_update_package", data = "<package_request>")]
+pub async fn create_or_update_package(
+    mut package_request: Json<CreateOrUpdatePackageRequest>,
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    iam_service_config: IAMService_config,
+    claims: ActiveAPIClaims,
+    idempotency_key: IdempotencyKey,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<status::Created<Json<CreateOrUpdatePackageResponse>>, ApiError> {
+    validate_quick_links(package_request.quick_links.as_deref().unwrap_or_default())?;
+
+    run_blocking(rdb, |conn| {
+        package_request.organization_id = canonical_slug(conn, &package_request.organization_id)?;
+        claims.authorize(conn, &package_request.organization_id, SCOPE_WRITE)?;
+        ensure_active(conn, &package_request.organization_id)?;
+
+        let key = idempotency_key.0.as_deref();
+        let key_scope = format!("packages:{}", claims.sub);
+        let hash = request_hash(&*package_request);
+
+        let response = conn.transaction::<_, ApiError, _>(|conn| {
+            if let Some(replayed) = idempotency::claim(conn, key, &key_scope, &hash)? {
+                return Ok(replayed);
+            }
 
-    // Update or create the environment in the Package_Env table
-    let existing_env = package_env_dsl::package_env
-        .filter(package_env_dsl::parent_id.eq(package_id))
-        .filter(package_env_dsl::env.eq(&package_request.env))
-        .first::<Package_Env>(&mut conn)
-        .optional()
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving package environment".to_string(),
-            )
-        })?;
+            let response = CreateOrUpdatePackageResponse {
+                message: "Package created or updated successfully".to_string(),
+                package_id: upsert_package(conn, &package_request)?,
+            };
 
-    if let Some(_) = existing_env {
-        // Update the existing environment
-        diesel::update(
-            package_env_dsl::package_env
-                .filter(package_env_dsl::parent_id.eq(package_id))
-                .filter(package_env_dsl::env.eq(&package_request.env)),
-        )
-        .set(package_env_dsl::version.eq(&package_request.version))
-        .execute(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error updating package environment".to_string(),
-            )
+            idempotency::complete(conn, key, &key_scope, &response)?;
+            Ok(response)
         })?;
-    } else {
-        // Create a new environment
-        let new_env = Package_EnvInsertable {
-            parent_id: package_id,
-            env: package_request.env.clone(),
-            version: package_request.version.clone(),
-            pipeline_status: None,
-        };
 
-        diesel::insert_into(package_env_dsl::package_env)
-            .values(&new_env)
-            .execute(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Error creating package environment".to_string(),
-                )
-            })?;
-    }
-
-    let response = CreateOrUpdatePackageResponse {
-        message: "Package created or updated successfully".to_string(),
-        package_id,
-    };
+        invalidate(cache.map(|c| c.inner()), &package_request.organization_id);
 
-    Ok(status::Created::new("/package").body(Json(response)))
+        Ok(status::Created::new("/package").body(Json(response)))
+    })
 }
 
 async fn fetch_user_packages(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    cache: Option<&RedisPoolState>,
     env: &str,
     org_id: &str,
-) -> Result<Vec<PackageResponse>, status::Custom<String>> {
+    cursor: Option<&str>,
+    limit: Option<i64>,
+    filters: &PackageListQuery,
+) -> Result<Page<Projected<PackageResponse>>, ApiError> {
     use crate::models::schema::schema::package::dsl::*;
     use crate::models::schema::schema::package_env::dsl as package_env_dsl;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
+    let page_request = PageRequest::parse(cursor, limit)?;
+    let sort = Sort::parse(filters.sort.as_deref())?;
+    let filter_hash = content_hash(
+        &serde_json::to_string(&(&filters.package_type, &filters.lang)).unwrap_or_default(),
+    );
+    let cache_key = format!(
+        "packages:{}:{}:{}:{}",
+        env,
+        filter_hash,
+        sort.cache_key(),
+        page_request.cache_key()
+    );
+
+    let page = cached(cache, org_id, &cache_key, || {
+        run_blocking(rdb, |conn| {
+            // Packages of the workspace released to `env`, shared by the page and the total
+            let filtered = || {
+                let mut query = package
+                    .inner_join(package_env_dsl::package_env.on(package_env_dsl::parent_id.eq(id)))
+                    .filter(package_env_dsl::env.eq(env))
+                    .filter(organization_id.eq(org_id))
+                    .filter(deleted_at.is_null())
+                    .filter(package_env_dsl::deleted_at.is_null())
+                    .into_boxed();
+                if let Some(value) = &filters.package_type {
+                    query = query.filter(package_type.eq(value));
+                }
+                if let Some(value) = &filters.lang {
+                    query = query.filter(lang.eq(value));
+                }
+                query
+            };
 
-    // Get all packages associated with the group_ids
-    let results = package
-        .inner_join(package_env_dsl::package_env.on(package_env_dsl::parent_id.eq(id)))
-        .filter(package_env_dsl::env.eq(env))
-        .filter(organization_id.eq(org_id))
-        .select((
-            package::all_columns(),
-            package_env_dsl::version,
-            package_env_dsl::pipeline_status,
-        ))
-        .load::<(Package, String, Option<String>)>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving packages".to_string(),
-            )
-        })?;
+            let mut query = match (sort.key, sort.descending) {
+                (SortKey::Id, false) => filtered().order(id.asc()),
+                (SortKey::Id, true) => filtered().order(id.desc()),
+                (SortKey::Identifier, false) => filtered().order((identifier.asc(), id.asc())),
+                (SortKey::Identifier, true) => filtered().order((identifier.desc(), id.desc())),
+                (SortKey::UpdatedAt, false) => filtered().order((updated_at.asc(), id.asc())),
+                (SortKey::UpdatedAt, true) => filtered().order((updated_at.desc(), id.desc())),
+            };
+            if let Some(after_id) = page_request.after_id {
+                query = match (sort.key, sort.descending) {
+                    (SortKey::Id, false) => query.filter(id.gt(after_id)),
+                    (SortKey::Id, true) => query.filter(id.lt(after_id)),
+                    (SortKey::Identifier, false) => {
+                        let key = page_request.sort_key()?.to_string();
+                        query.filter(
+                            identifier
+                                .gt(key.clone())
+                                .or(identifier.eq(key).and(id.gt(after_id))),
+                        )
+                    }
+                    (SortKey::Identifier, true) => {
+                        let key = page_request.sort_key()?.to_string();
+                        query.filter(
+                            identifier
+                                .lt(key.clone())
+                                .or(identifier.eq(key).and(id.lt(after_id))),
+                        )
+                    }
+                    (SortKey::UpdatedAt, false) => {
+                        let key = page_request.sort_timestamp()?;
+                        query.filter(
+                            updated_at
+                                .gt(key)
+                                .or(updated_at.eq(key).and(id.gt(after_id))),
+                        )
+                    }
+                    (SortKey::UpdatedAt, true) => {
+                        let key = page_request.sort_timestamp()?;
+                        query.filter(
+                            updated_at
+                                .lt(key)
+                                .or(updated_at.eq(key).and(id.lt(after_id))),
+                        )
+                    }
+                };
+            }
 
-    let package_responses: Vec<PackageResponse> = results
-        .into_iter()
-        .map(|(p, version, pipeline_status)| PackageResponse {
-            identifier: p.identifier,
-            package_type: p.package_type,
-            lang: p.lang,
-            updated_at: p.updated_at,
-            description: p.description.unwrap_or(String::from("")),
-            organization_id: p.organization_id.unwrap_or(String::from("")),
-            dependencies: serde_json::from_str(&p.dependencies_json.unwrap_or(String::from("[]")))
-                .unwrap(),
-            version, // Include the version from the package_env table
-            pipeline_status,
-            repo_origin: p.repo_origin,
-            quick_links: p.quick_links,
+            let results = query
+                .select((
+                    package::all_columns(),
+                    package_env_dsl::version,
+                    package_env_dsl::pipeline_status,
+                ))
+                .limit(page_request.fetch_limit())
+                .load::<(Package, String, Option<String>)>(conn)
+                .map_err(|_| ApiError::Internal("Error retrieving packages".to_string()))?;
+
+            let total = filtered()
+                .count()
+                .get_result::<i64>(conn)
+                .map_err(|_| ApiError::Internal("Error counting packages".to_string()))?;
+
+            let package_responses = page_request
+                .page(results, total, |(p, _, _)| {
+                    (sort.cursor_key(&p.identifier, p.updated_at), p.id)
+                })
+                .try_map(|(p, version, pipeline_status)| {
+                    Ok::<_, ApiError>(PackageResponse {
+                        identifier: p.identifier,
+                        package_type: p.package_type,
+                        lang: p.lang,
+                        updated_at: p.updated_at,
+                        description: p.description.unwrap_or(String::from("")),
+                        organization_id: p.organization_id.unwrap_or(String::from("")),
+                        dependencies: parse_identifiers(p.dependencies_json)?,
+                        version, // Include the version from the package_env table
+                        pipeline_status,
+                        repo_origin: p.repo_origin,
+                        quick_links: parse_quick_links(p.quick_links.as_deref()),
+                    })
+                })?;
+
+            Ok(package_responses)
         })
-        .collect();
+    })?;
 
-    Ok(package_responses)
+    project(page, filters.fields.as_deref())
 }
 
 #[openapi()]
-#[get("/packages/<org_id>/<env>")]
+#[get("/packages/<org_id>/<env>?<cursor>&<limit>&<filters..>")]
 pub async fn get_user_packages(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    _claims: APIClaims,
+    claims: ActiveAPIClaims,
     env: String,
     org_id: String,
-) -> Result<Json<Vec<PackageResponse>>, status::Custom<String>> {
-    let package_responses = fetch_user_packages(rdb, &env, &org_id).await?;
+    cursor: Option<String>,
+    limit: Option<i64>,
+    filters: PackageListQuery,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<Page<Projected<PackageResponse>>>, ApiError> {
+    run_blocking(rdb, |conn| claims.authorize(conn, &org_id, SCOPE_READ))?;
+    let package_responses = fetch_user_packages(
+        rdb,
+        cache.map(|c| c.inner()),
+        &env,
+        &org_id,
+        cursor.as_deref(),
+        limit,
+        &filters,
+    )
+    .await?;
     Ok(Json(package_responses))
 }
 
 #[openapi()]
-#[get("/user-land/packages/<org_id>/<env>")]
+#[get("/user-land/packages/<org_id>/<env>?<cursor>&<limit>&<filters..>")]
 pub async fn get_user_packages_user_land(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     _claims: Claims,
     env: String,
     org_id: String,
-) -> Result<Json<Vec<PackageResponse>>, status::Custom<String>> {
-    let package_responses = fetch_user_packages(rdb, &env, &org_id).await?;
+    cursor: Option<String>,
+    limit: Option<i64>,
+    filters: PackageListQuery,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<Page<Projected<PackageResponse>>>, ApiError> {
+    let package_responses = fetch_user_packages(
+        rdb,
+        cache.map(|c| c.inner()),
+        &env,
+        &org_id,
+        cursor.as_deref(),
+        limit,
+        &filters,
+    )
+    .await?;
     Ok(Json(package_responses))
 }
 
 #[openapi()]
-#[get("/public/packages/<org_id>/<env>")]
+#[get("/public/packages/<org_id>/<env>?<cursor>&<limit>&<filters..>")]
 pub async fn get_user_packages_public(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     env: String,
     org_id: String,
-) -> Result<Json<Vec<PackageResponse>>, status::Custom<String>> {
-    let package_responses = fetch_user_packages(rdb, &env, &org_id).await?;
+    cursor: Option<String>,
+    limit: Option<i64>,
+    filters: PackageListQuery,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<Page<Projected<PackageResponse>>>, ApiError> {
+    let package_responses = fetch_user_packages(
+        rdb,
+        cache.map(|c| c.inner()),
+        &env,
+        &org_id,
+        cursor.as_deref(),
+        limit,
+        &filters,
+    )
+    .await?;
     Ok(Json(package_responses))
 }
+/// Listing entry of a schema, `branch` is absent when the schema has no branch for the env and
+/// `data` is the resolved document the table names are read from
+pub fn assemble_dbschema(
+    db_schema_: Dbschema,
+    branch: Option<Dbschema_Branch>,
+    data: Option<String>,
+) -> GetDbschemaAndTablesResponse {
+    use serde_json::Value;
+
+    let tables: Vec<String> = match data {
+        Some(data_str) => match serde_json::from_str::<Value>(&data_str) {
+            Ok(Value::Array(array)) => array
+                .into_iter()
+                .filter_map(|element| {
+                    element
+                        .get("data")
+                        .and_then(|d| d.get("name"))
+                        .and_then(|n| n.as_str().map(|s| s.to_string()))
+                })
+                .collect(),
+            _ => Vec::new(),
+        },
+        None => Vec::new(),
+    };
+
+    let (version, pipeline_status) = match branch {
+        Some(branch) => (branch.version, branch.pipeline_status),
+        None => (None, None),
+    };
+
+    GetDbschemaAndTablesResponse {
+        id: db_schema_.id,
+        name: db_schema_.name,
+        description: db_schema_.description,
+        version,
+        updated_at: db_schema_.updated_at,
+        identifier: db_schema_.identifier,
+        organization_id: db_schema_.organization_id.unwrap_or_default(),
+        tables,
+        pipeline_status,
+        repo_origin: db_schema_.repo_origin,
+        db_type: Some(db_schema_.db_type),
+        quick_links: parse_quick_links(db_schema_.quick_links.as_deref()),
+    }
+}
+
 async fn fetch_dbschemas_and_tables(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    cache: Option<&RedisPoolState>,
+    blobs: Option<&Database>,
     env: &str,
     org_id: &str,
-) -> Result<Vec<GetDbschemaAndTablesResponse>, status::Custom<String>> {
+    cursor: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Page<GetDbschemaAndTablesResponse>, ApiError> {
     use crate::models::schema::schema::dbschema::dsl::*;
-    use crate::models::schema::schema::dbschema_branch::dsl::*;
-    use serde_json::Value;
-
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
-
-    let query = dbschema.filter(organization_id.eq(org_id)).into_boxed();
-
-    let results = query.load::<Dbschema>(&mut conn).map_err(|_| {
-        status::Custom(
-            Status::InternalServerError,
-            "Error retrieving dbschemas".to_string(),
-        )
-    })?;
+    use crate::models::schema::schema::dbschema_branch::dsl as branch_dsl;
 
-    let response = results
-        .into_iter()
-        .map(|db_schema_| {
-            // Attempt to get the main branch data
-            let branch = dbschema_branch
-                .filter(parent_id.eq(db_schema_.id).and(branch_name.eq(env)))
-                .first::<Dbschema_Branch>(&mut conn)
-                .ok();
+    let page_request = PageRequest::parse(cursor, limit)?;
+
+    cached_async(
+        cache,
+        org_id,
+        &format!("dbschemas-and-tables:{}:{}", env, page_request.cache_key()),
+        || async {
+            let schemas_with_branches = run_blocking(rdb, |conn| {
+                let mut query = dbschema
+                    .filter(organization_id.eq(org_id))
+                    .filter(deleted_at.is_null())
+                    .into_boxed();
+                if let Some(after_id) = page_request.after_id {
+                    query = query.filter(id.gt(after_id));
+                }
 
-            let branch_data = branch.clone().unwrap().data;
+                let results = query
+                    .order(id.asc())
+                    .limit(page_request.fetch_limit())
+                    .load::<Dbschema>(conn)
+                    .map_err(|_| ApiError::Internal("Error retrieving dbschemas".to_string()))?;
+
+                let total = dbschema
+                    .filter(organization_id.eq(org_id))
+                    .filter(deleted_at.is_null())
+                    .count()
+                    .get_result::<i64>(conn)
+                    .map_err(|_| ApiError::Internal("Error counting dbschemas".to_string()))?;
+
+                let page = page_request.page(results, total, |db_schema_| (None, db_schema_.id));
+
+                // The `env` branch of every schema on the page in one query
+                let branches = Dbschema_Branch::belonging_to(&page.items)
+                    .filter(branch_dsl::branch_name.eq(env))
+                    .filter(branch_dsl::deleted_at.is_null())
+                    .load::<Dbschema_Branch>(conn)
+                    .map_err(|_| {
+                        ApiError::Internal("Error retrieving dbschema branches".to_string())
+                    })?;
+                let grouped_branches = branches.grouped_by(&page.items);
+
+                Ok(Page {
+                    items: page
+                        .items
+                        .into_iter()
+                        .zip(grouped_branches.into_iter().map(|b| b.into_iter().next()))
+                        .collect::<Vec<_>>(),
+                    next_cursor: page.next_cursor,
+                    total: page.total,
+                })
+            })?;
 
-            // Use the main branch data if available, otherwise fallback to the db_schema_ data
-            let data_to_use = branch_data.as_deref().or(db_schema_.data.as_deref());
+            let mut response = Vec::new();
+            for (db_schema_, branch) in schemas_with_branches.items {
+                let branch_data = match &branch {
+                    Some(branch) => resolve_document(blobs, branch.data.clone()).await?,
+                    None => None,
+                };
 
-            let tables: Vec<String> = match data_to_use {
-                Some(data_str) => match serde_json::from_str::<Value>(data_str) {
-                    Ok(Value::Array(array)) => array
-                        .into_iter()
-                        .filter_map(|element| {
-                            element
-                                .get("data")
-                                .and_then(|d| d.get("name"))
-                                .and_then(|n| n.as_str().map(|s| s.to_string()))
-                        })
-                        .collect(),
-                    _ => Vec::new(),
-                },
-                None => Vec::new(),
-            };
+                // Use the branch data if available, otherwise fallback to the db_schema_ data
+                let data_to_use = match branch_data {
+                    Some(branch_data) => Some(branch_data),
+                    None => resolve_document(blobs, db_schema_.data.clone()).await?,
+                };
 
-            GetDbschemaAndTablesResponse {
-                id: db_schema_.id,
-                name: db_schema_.name,
-                description: db_schema_.description,
-                version: branch.clone().unwrap().version,
-                updated_at: db_schema_.updated_at,
-                identifier: db_schema_.identifier,
-                organization_id: db_schema_.organization_id.unwrap(),
-                tables,
-                pipeline_status: branch.clone().unwrap().pipeline_status,
-                repo_origin: db_schema_.repo_origin,
-                db_type: Some(db_schema_.db_type),
-                quick_links: db_schema_.quick_links,
+                response.push(assemble_dbschema(db_schema_, branch, data_to_use));
             }
-        })
-        .collect();
 
-    Ok(response)
+            Ok::<_, ApiError>(Page {
+                items: response,
+                next_cursor: schemas_with_branches.next_cursor,
+                total: schemas_with_branches.total,
+            })
+        },
+    )
+    .await
 }
 
 #[openapi()]
-#[get("/dbschemas-and-tables/<org_id>/<env>")]
+#[get("/dbschemas-and-tables/<org_id>/<env>?<cursor>&<limit>")]
 pub async fn get_dbschemas_and_tables(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     env: String,
     org_id: String,
-    _claims: APIClaims,
-) -> Result<Json<Vec<GetDbschemaAndTablesResponse>>, status::Custom<String>> {
-    let dbschemas = fetch_dbschemas_and_tables(rdb, &env, &org_id).await?;
+    claims: ActiveAPIClaims,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    cache: Option<&State<RedisPoolState>>,
+    blobs: Option<&State<Database>>,
+) -> Result<Json<Page<GetDbschemaAndTablesResponse>>, ApiError> {
+    run_blocking(rdb, |conn| claims.authorize(conn, &org_id, SCOPE_READ))?;
+    let dbschemas = fetch_dbschemas_and_tables(
+        rdb,
+        cache.map(|c| c.inner()),
+        blobs.map(|b| b.inner()),
+        &env,
+        &org_id,
+        cursor.as_deref(),
+        limit,
+    )
+    .await?;
     Ok(Json(dbschemas))
 }
 
 #[openapi()]
-#[get("/user-land/dbschemas-and-tables/<org_id>/<env>")]
+#[get("/user-land/dbschemas-and-tables/<org_id>/<env>?<cursor>&<limit>")]
 pub async fn get_dbschemas_and_tables_user_land(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     env: String,
     org_id: String,
     _claims: Claims,
-) -> Result<Json<Vec<GetDbschemaAndTablesResponse>>, status::Custom<String>> {
-    let dbschemas = fetch_dbschemas_and_tables(rdb, &env, &org_id).await?;
+    cursor: Option<String>,
+    limit: Option<i64>,
+    cache: Option<&State<RedisPoolState>>,
+    blobs: Option<&State<Database>>,
+) -> Result<Json<Page<GetDbschemaAndTablesResponse>>, ApiError> {
+    let dbschemas = fetch_dbschemas_and_tables(
+        rdb,
+        cache.map(|c| c.inner()),
+        blobs.map(|b| b.inner()),
+        &env,
+        &org_id,
+        cursor.as_deref(),
+        limit,
+    )
+    .await?;
     Ok(Json(dbschemas))
 }
 
 #[openapi()]
-#[get("/public/dbschemas-and-tables/<org_id>/<env>")]
+#[get("/public/dbschemas-and-tables/<org_id>/<env>?<cursor>&<limit>")]
 pub async fn get_dbschemas_and_tables_public(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     env: String,
     org_id: String,
-) -> Result<Json<Vec<GetDbschemaAndTablesResponse>>, status::Custom<String>> {
-    let dbschemas = fetch_dbschemas_and_tables(rdb, &env, &org_id).await?;
+    cursor: Option<String>,
+    limit: Option<i64>,
+    cache: Option<&State<RedisPoolState>>,
+    blobs: Option<&State<Database>>,
+) -> Result<Json<Page<GetDbschemaAndTablesResponse>>, ApiError> {
+    let dbschemas = fetch_dbschemas_and_tables(
+        rdb,
+        cache.map(|c| c.inner()),
+        blobs.map(|b| b.inner()),
+        &env,
+        &org_id,
+        cursor.as_deref(),
+        limit,
+    )
+    .await?;
     Ok(Json(dbschemas))
 }
 
@@ -1260,166 +1919,118 @@ pub async fn get_dbschemas_and_tables_public(
 pub async fn update_pipeline_status(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     status_update: Json<PipelineStatusUpdateRequest>,
-    _claims: APIClaims,
-    notification_config: NotificationService_api_config,
-) -> Result<status::Custom<String>, status::Custom<String>> {
+    claims: ActiveAPIClaims,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
     use crate::models::schema::schema::dbschema::dsl as dbschema_dsl;
-    use crate::models::schema::schema::dbschema_branch::dsl as dbschema_branch_dsl;
-    use crate::models::schema::schema::organization::dsl as org_dsl;
     use crate::models::schema::schema::package::dsl as package_dsl;
-    use crate::models::schema::schema::package_env::dsl as package_env_dsl;
     use crate::models::schema::schema::service::dsl as service_dsl;
-    use crate::models::schema::schema::service_envs::dsl as service_envs_dsl;
-
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
 
     let update_type = status_update.update_type.clone();
     let env = status_update.env.clone();
     let status = status_update.status.clone();
-    let org_id = status_update.org_id.clone();
     let identifier = status_update.identifier.clone();
 
-    match update_type.as_str() {
-        "schema" => {
-            // Retrieve the parent ID from the dbschema table
-            let parent_id = dbschema_dsl::dbschema
-                .filter(dbschema_dsl::identifier.eq(&identifier))
-                .filter(dbschema_dsl::organization_id.eq(&org_id))
-                .select(dbschema_dsl::id)
-                .first::<i64>(&mut conn)
-                .map_err(|_| status::Custom(Status::NotFound, "Schema not found".to_string()))?;
-
-            // Update the pipeline status in the dbschema_branch table
-            diesel::update(
-                dbschema_branch_dsl::dbschema_branch
-                    .filter(dbschema_branch_dsl::parent_id.eq(parent_id))
-                    .filter(dbschema_branch_dsl::branch_name.eq(&env)),
-            )
-            .set(dbschema_branch_dsl::pipeline_status.eq(status.clone()))
-            .execute(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Failed to update schema pipeline status".to_string(),
-                )
-            })?;
-        }
-        "package" => {
-            // Retrieve the parent ID from the package table
-            let parent_id = package_dsl::package
-                .filter(package_dsl::identifier.eq(&identifier))
-                .filter(package_dsl::organization_id.eq(&org_id))
-                .select(package_dsl::id)
-                .first::<i64>(&mut conn)
-                .map_err(|_| status::Custom(Status::NotFound, "Package not found".to_string()))?;
-
-            // Update the pipeline status in the package_env table
-            diesel::update(
-                package_env_dsl::package_env
-                    .filter(package_env_dsl::parent_id.eq(parent_id))
-                    .filter(package_env_dsl::env.eq(&env)),
-            )
-            .set(package_env_dsl::pipeline_status.eq(status.clone()))
-            .execute(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Failed to update package pipeline status".to_string(),
-                )
-            })?;
-        }
-        "service" => {
-            // Retrieve the parent ID from the service table
-            let parent_id = service_dsl::service
-                .filter(service_dsl::identifier.eq(&identifier))
-                .filter(service_dsl::organization_id.eq(&org_id))
-                .select(service_dsl::id)
-                .first::<i64>(&mut conn)
-                .map_err(|e| {
-                    println!("{:?}", e);
-                    status::Custom(Status::NotFound, "Service not found".to_string())
-                })?;
-
-            // Update the pipeline status in the service_envs table
-            diesel::update(
-                service_envs_dsl::service_envs
-                    .filter(service_envs_dsl::parent_id.eq(parent_id))
-                    .filter(service_envs_dsl::env.eq(&env)),
-            )
-            .set(service_envs_dsl::pipeline_status.eq(status.clone()))
-            .execute(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Failed to update service pipeline status".to_string(),
-                )
-            })?;
-        }
-        _ => {
-            return Err(status::Custom(
-                Status::BadRequest,
-                "Invalid update_type provided".to_string(),
-            ));
-        }
-    }
-
-    let group_id = org_dsl::organization
-        .filter(org_dsl::slug.eq(&org_id))
-        .select(org_dsl::group_id)
-        .first::<String>(&mut conn)
-        .map_err(|_| status::Custom(Status::NotFound, "Organization not found".to_string()))?;
+    run_blocking(rdb, |conn| {
+        let org_id = canonical_slug(conn, &status_update.org_id)?;
+        claims.authorize(conn, &org_id, SCOPE_PIPELINE)?;
+        ensure_active(conn, &org_id)?;
+
+        // Members are notified by the outbox relay once the status is committed
+        let update_failed =
+            |what: &str| ApiError::Internal(format!("Failed to update {} pipeline status", what));
+
+        conn.transaction::<_, ApiError, _>(|conn| {
+            match update_type.as_str() {
+                "schema" => {
+                    // Retrieve the parent ID from the dbschema table
+                    let parent_id = dbschema_dsl::dbschema
+                        .filter(dbschema_dsl::identifier.eq(&identifier))
+                        .filter(dbschema_dsl::organization_id.eq(&org_id))
+                        .filter(dbschema_dsl::deleted_at.is_null())
+                        .select(dbschema_dsl::id)
+                        .first::<i64>(conn)
+                        .map_err(|_| ApiError::NotFound("Schema not found".to_string()))?;
+
+                    // Update the pipeline status in the dbschema_branch table
+                    pipeline::set_dbschema_pipeline(conn, parent_id, &env, &status)
+                        .map_err(|_| update_failed("schema"))?;
+                }
+                "package" => {
+                    // Retrieve the parent ID from the package table
+                    let parent_id = package_dsl::package
+                        .filter(package_dsl::identifier.eq(&identifier))
+                        .filter(package_dsl::organization_id.eq(&org_id))
+                        .filter(package_dsl::deleted_at.is_null())
+                        .select(package_dsl::id)
+                        .first::<i64>(conn)
+                        .map_err(|_| ApiError::NotFound("Package not found".to_string()))?;
+
+                    // Update the pipeline status in the package_env table
+                    pipeline::set_package_pipeline(conn, parent_id, &env, &status)
+                        .map_err(|_| update_failed("package"))?;
+                }
+                "service" => {
+                    // Retrieve the parent ID from the service table
+                    let parent_id = service_dsl::service
+                        .filter(service_dsl::identifier.eq(&identifier))
+                        .filter(service_dsl::organization_id.eq(&org_id))
+                        .filter(service_dsl::deleted_at.is_null())
+                        .select(service_dsl::id)
+                        .first::<i64>(conn)
+                        .map_err(|error| {
+                            tracing::warn!(%identifier, ?error, "Unknown service");
+                            ApiError::NotFound("Service not found".to_string())
+                        })?;
+
+                    // Update the pipeline status in the service_envs table
+                    pipeline::set_service_pipeline(conn, parent_id, &env, &status)
+                        .map_err(|_| update_failed("service"))?;
+                }
+                _ => {
+                    return Err(ApiError::BadRequest(
+                        "Invalid update_type provided".to_string(),
+                    ));
+                }
+            }
 
-    let msg = RealtimeMessage {
-        topic: "pipeline-update".to_string(),
-        payload: serde_json::to_string(
-            &json!({"org_id" : org_id , "identifier" : identifier, "status" : status.clone() }),
-        )
-        .unwrap(),
-    };
+            outbox::enqueue(
+                conn,
+                &org_id,
+                outbox::PIPELINE_UPDATE,
+                json!({"org_id": org_id, "identifier": identifier, "status": status}),
+            )?;
+            if status == pipeline::FAILED {
+                webhooks::enqueue(
+                    conn,
+                    &org_id,
+                    webhooks::PIPELINE_FAILED,
+                    json!({"kind": update_type, "identifier": identifier, "env": env}),
+                )?;
+            }
+            Ok(())
+        })?;
 
-    match publish_message_to_group_api_land(
-        &notification_config.0,
-        PublishMessageToGroupApiLandParams {
-            group_id,
-            publish_request: PublishRequest {
-                message: msg.to_string(),
-                prefix: "dev_portal".to_string(),
-                pub_type: PublishType::Members,
-            },
-        },
-    )
-    .await
-    {
-        Ok(_) => {
-            println!("Notification sent on WS")
-        }
-        Err(e) => {
-            return Err(status::Custom(
-                Status::InternalServerError,
-                format!("Failed to publish message: {:?}", e),
-            ));
-        }
-    }
+        invalidate(cache.map(|c| c.inner()), &org_id);
 
-    Ok(status::Custom(Status::Ok, "Notified all users".to_string()))
+        Ok(Json(MessageResponse {
+            message: "Pipeline status updated".to_string(),
+        }))
+    })
 }
 
 use crate::models::schema::Organization;
 use crate::models::schema::OrganizationInsertable;
 
+use deunicode::deunicode;
 use regex::Regex;
 
-fn to_slug(input: &str) -> String {
-    // Lowercase the input string
-    let mut slug = input.to_lowercase();
+pub fn to_slug(input: &str) -> String {
+    // Transliterate to ASCII and lowercase, "Équipe 42" becomes "equipe 42"
+    let mut slug = deunicode(input).to_lowercase();
 
-    // Remove everything except alphabets a-z and spaces
-    let re = Regex::new(r"[^a-z\s]").unwrap();
+    // Remove everything except alphabets a-z, digits and spaces
+    let re = Regex::new(r"[^a-z0-9\s]").unwrap();
     slug = re.replace_all(&slug, " ").to_string();
 
     // Trim the string
@@ -1439,44 +2050,33 @@ pub async fn create_organization(
     create_request: Json<CreateOrganizationRequest>,
     claims: Claims,
     iam_service_config: IAMService_config,
-) -> Result<status::Created<Json<CreateOrganizationResponse>>, status::Custom<String>> {
+) -> Result<status::Created<Json<CreateOrganizationResponse>>, ApiError> {
     use crate::models::schema::schema::organization::dsl::*;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
-
     let org_slug = to_slug(&create_request.name);
+    if org_slug.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Workspace name must contain letters or digits".to_string(),
+        ));
+    }
 
-    // Check if organization with the slug already exists
-    let existing_org = organization
-        .filter(slug.eq(&org_slug))
-        .first::<Organization>(&mut conn)
-        .optional()
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error checking existing organization".to_string(),
-            )
-        })?;
-
-    if let Some(_) = existing_org {
-        return Err(status::Custom(
-            Status::Conflict,
+    // Check if organization with the slug already exists, or used to before a slug change
+    if run_blocking(rdb, |conn| slug_taken(conn, &org_slug))? {
+        return Err(ApiError::Conflict(
             "Workspace ID is already taken".to_string(),
         ));
     }
 
     let group_uuid = Uuid::new_v4().to_string();
 
-    match identity_create_group(
-        &iam_service_config.0,
-        IdentityCreateGroupParams {
-            create_group_request: CreateGroupRequest::new(group_uuid.clone()),
-        },
+    match iam_call(
+        "create_group",
+        identity_create_group(
+            &iam_service_config.0,
+            IdentityCreateGroupParams {
+                create_group_request: CreateGroupRequest::new(group_uuid.clone()),
+            },
+        ),
     )
     .await
     {
@@ -1493,15 +2093,18 @@ pub async fn create_organization(
                 version: None,
             };
 
-            let created_organization: Organization = diesel::insert_into(organization)
-                .values(&new_organization)
-                .get_result::<Organization>(&mut conn)
-                .map_err(|_| {
-                    status::Custom(
-                        Status::InternalServerError,
-                        "Error inserting new organization".to_string(),
-                    )
-                })?;
+            let created_organization = run_blocking(rdb, |conn| {
+                conn.transaction::<_, ApiError, _>(|conn| {
+                    let created = diesel::insert_into(organization)
+                        .values(&new_organization)
+                        .get_result::<Organization>(conn)
+                        .map_err(|_| {
+                            ApiError::Internal("Error inserting new organization".to_string())
+                        })?;
+                    outbox::enqueue_workspace(conn, &created.slug, "created")?;
+                    Ok(created)
+                })
+            })?;
 
             Ok(
                 status::Created::new("/organization").body(Json(CreateOrganizationResponse {
@@ -1510,119 +2113,164 @@ pub async fn create_organization(
                 })),
             )
         }
-        Err(e) => {
-            println!("{:?}", e);
-            Err(status::Custom(
-                Status::InternalServerError,
+        Err(error) => {
+            tracing::error!(?error, "Failed to create group in IAM service");
+            Err(ApiError::Upstream(
                 "Failed to create group in IAM service".to_string(),
             ))
         }
     }
 }
 
+// `is_admin` depends on the caller rather than the workspace so it is left out of the revision
+fn workspace_etag(workspace: &WorkspaceDetailResponse) -> String {
+    etag(&(
+        &workspace.name,
+        &workspace.block_positions,
+        workspace.is_active,
+    ))
+}
+
+/// Stores the canvas layout, workspace owners and members can edit it. Blocks of entities that
+/// no longer exist are dropped
 #[openapi()]
 #[post("/update-block-positions/<org_id>", data = "<block_positions>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn update_block_positions(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     org_id: String,
-    block_positions: String,
-) -> Result<status::Accepted<String>, status::Custom<String>> {
+    block_positions: Json<CanvasLayout>,
+    _claims: Claims,
+    groups: GroupMemberships,
+    groups_owned: GroupOwnerships,
+    if_match: IfMatch,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<ETagged<status::Accepted<String>>, ApiError> {
     use crate::models::schema::schema::organization::dsl::*;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
+    let mut layout = block_positions.into_inner();
+    validate_layout(&layout)?;
 
-    // Check if the organization exists
-    let existing_org = organization
-        .filter(slug.eq(org_id.clone()))
-        .first::<Organization>(&mut conn)
-        .optional()
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error checking organization existence".to_string(),
-            )
-        })?;
+    run_blocking(rdb, |conn| {
+        member_organization(conn, &org_id, &groups.0, &groups_owned.0)?;
+        ensure_active(conn, &org_id)?;
+
+        let revision = conn.transaction::<_, ApiError, _>(|conn| {
+            let org = organization
+                .filter(slug.eq(&org_id))
+                .filter(deleted_at.is_null())
+                .for_update()
+                .first::<Organization>(conn)
+                .optional()
+                .map_err(|_| {
+                    ApiError::Internal("Error checking organization existence".to_string())
+                })?
+                .ok_or_else(|| ApiError::NotFound("Organization not found".to_string()))?;
+
+            let live = canvas_block_ids(conn, &org_id)?;
+            let mut current = parse_layout(org.blocks_positions.as_deref());
+            current.prune(&live);
+
+            let mut workspace = WorkspaceDetailResponse {
+                name: org.name,
+                block_positions: current,
+                is_active: org.is_active,
+                is_admin: false,
+            };
+            if_match.verify(&workspace_etag(&workspace))?;
 
-    if let Some(mut org) = existing_org {
-        // Update block_positions
-        org.blocks_positions = Some(block_positions);
+            layout.prune(&live);
+            diesel::update(organization.filter(slug.eq(&org_id)))
+                .set(blocks_positions.eq(stored_layout(&layout)))
+                .execute(conn)
+                .map_err(|_| ApiError::Internal("Error updating block positions".to_string()))?;
+            outbox::enqueue_workspace(conn, &org_id, "block_positions_updated")?;
 
-        diesel::update(organization.filter(slug.eq(org_id)))
-            .set(blocks_positions.eq(org.blocks_positions))
-            .execute(&mut conn)
-            .map_err(|_| {
-                status::Custom(
-                    Status::InternalServerError,
-                    "Error updating block positions".to_string(),
-                )
-            })?;
+            workspace.block_positions = layout;
+            Ok(workspace_etag(&workspace))
+        })?;
 
-        Ok(status::Accepted(
-            "Block positions updated successfully".to_string(),
-        ))
-    } else {
-        Err(status::Custom(
-            Status::NotFound,
-            "Organization not found".to_string(),
+        invalidate(cache.map(|c| c.inner()), &org_id);
+
+        Ok(ETagged::Body(
+            revision,
+            status::Accepted("Block positions updated successfully".to_string()),
         ))
-    }
+    })
 }
 
 #[openapi()]
-#[get("/get-workspaces")]
+#[get("/get-workspaces?<cursor>&<limit>")]
 pub async fn get_workspaces(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     _claims: Claims,
     groups_owned: GroupOwnerships,
     groups: GroupMemberships,
-) -> Result<Json<Vec<WorkspaceSummaryResponse>>, status::Custom<String>> {
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Json<Page<WorkspaceSummaryResponse>>, ApiError> {
     use crate::models::schema::schema::organization::dsl::*;
-    println!("Handler invoked");
-    let mut conn: diesel::r2d2::PooledConnection<ConnectionManager<PgConnection>> =
-        rdb.get().map_err(|_| {
-            status::Custom(
-                Status::ServiceUnavailable,
-                "Failed to get DB connection".to_string(),
-            )
-        })?;
 
-    let ownerships: Vec<String> = groups_owned.0;
-    let memberships: Vec<String> = groups.0;
+    let page_request = PageRequest::parse(cursor.as_deref(), limit)?;
 
-    let workspaces: Vec<WorkspaceSummaryResponse> = organization
-        .filter(group_id.eq_any(memberships))
-        .select((
-            slug,
-            name,
-            is_active,
-            group_id,
-            infra_repo_origin,
-            quick_links,
-            version,
-        ))
-        .load::<(
-            String,
-            Option<String>,
-            bool,
-            String,
-            Option<String>,
-            Option<String>,
-            Option<String>,
-        )>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving workspaces".to_string(),
-            )
-        })?
-        .into_iter()
-        .map(
-            |(_slug, _name, _is_active, _group_id, _infra_repo_origin, _quick_links, _version)| {
+    run_blocking(rdb, |conn| {
+        let ownerships: Vec<String> = groups_owned.0;
+        let memberships: Vec<String> = groups.0;
+
+        let mut query = organization
+            .filter(group_id.eq_any(&memberships))
+            .filter(deleted_at.is_null())
+            .into_boxed();
+        if let Some(after_id) = page_request.after_id {
+            query = query.filter(id.gt(after_id));
+        }
+
+        let rows = query
+            .order(id.asc())
+            .limit(page_request.fetch_limit())
+            .select((
+                id,
+                slug,
+                name,
+                is_active,
+                group_id,
+                infra_repo_origin,
+                quick_links,
+                version,
+                purge_after,
+            ))
+            .load::<(
+                i64,
+                String,
+                Option<String>,
+                bool,
+                String,
+                Option<String>,
+                Option<String>,
+                Option<String>,
+                Option<DateTime<Utc>>,
+            )>(conn)
+            .map_err(|_| ApiError::Internal("Error retrieving workspaces".to_string()))?;
+
+        let total = organization
+            .filter(group_id.eq_any(&memberships))
+            .filter(deleted_at.is_null())
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|_| ApiError::Internal("Error counting workspaces".to_string()))?;
+
+        let workspaces = page_request.page(rows, total, |row| (None, row.0)).map(
+            |(
+                _id,
+                _slug,
+                _name,
+                _is_active,
+                _group_id,
+                _infra_repo_origin,
+                _quick_links,
+                _version,
+                _purge_after,
+            )| {
                 WorkspaceSummaryResponse {
                     slug: _slug,
                     name: _name,
@@ -1630,56 +2278,62 @@ pub async fn get_workspaces(
                     is_admin: ownerships.contains(&_group_id),
                     group_id: _group_id,
                     infra_repo_origin: _infra_repo_origin,
-                    quick_links: _quick_links,
+                    quick_links: parse_quick_links(_quick_links.as_deref()),
                     version: _version,
+                    purge_after: _purge_after,
                 }
             },
-        )
-        .collect();
+        );
 
-    Ok(Json(workspaces))
+        Ok(Json(workspaces))
+    })
 }
 
 async fn fetch_workspace(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    cache: Option<&RedisPoolState>,
     org_id: &str,
     is_admin: Option<&Vec<String>>,
-) -> Result<WorkspaceDetailResponse, status::Custom<String>> {
+) -> Result<WorkspaceDetailResponse, ApiError> {
     use crate::models::schema::schema::organization::dsl::*;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
+    // The admin flag depends on the caller so only the group is cached alongside the workspace
+    let (mut workspace_detail, workspace_group_id) = cached::<
+        (WorkspaceDetailResponse, String),
+        ApiError,
+        _,
+    >(cache, org_id, "workspace", || {
+        run_blocking(rdb, |conn| {
+            let workspace = organization
+                .filter(slug.eq(org_id))
+                .filter(deleted_at.is_null())
+                .select((name, blocks_positions, is_active, group_id))
+                .first::<(Option<String>, Option<String>, bool, String)>(conn)
+                .optional()
+                .map_err(|_| ApiError::Internal("Error retrieving workspace".to_string()))?;
+
+            if let Some((_name, _block_positions, _is_active, _group_id)) = workspace {
+                let mut layout = parse_layout(_block_positions.as_deref());
+                layout.prune(&canvas_block_ids(conn, org_id)?);
+
+                Ok((
+                    WorkspaceDetailResponse {
+                        name: _name,
+                        block_positions: layout,
+                        is_active: _is_active,
+                        is_admin: false,
+                    },
+                    _group_id,
+                ))
+            } else {
+                Err(ApiError::NotFound("Workspace not found".to_string()))
+            }
+        })
     })?;
 
-    let workspace = organization
-        .filter(slug.eq(org_id))
-        .select((name, blocks_positions, is_active, group_id))
-        .first::<(Option<String>, Option<String>, bool, String)>(&mut conn)
-        .optional()
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving workspace".to_string(),
-            )
-        })?;
-
-    if let Some((_name, _block_positions, _is_active, _group_id)) = workspace {
-        let is_admin_flag = is_admin.map_or(false, |ownerships| ownerships.contains(&_group_id));
-        Ok(WorkspaceDetailResponse {
-            name: _name,
-            block_positions: _block_positions,
-            is_active: _is_active,
-            is_admin: is_admin_flag,
-        })
-    } else {
-        Err(status::Custom(
-            Status::NotFound,
-            "Workspace not found".to_string(),
-        ))
-    }
+    workspace_detail.is_admin =
+        is_admin.map_or(false, |ownerships| ownerships.contains(&workspace_group_id));
+    Ok(workspace_detail)
 }
 
 #[openapi()]
@@ -1688,9 +2342,22 @@ pub async fn get_workspace(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     org_id: String,
     groups_owned: GroupOwnerships,
-) -> Result<Json<WorkspaceDetailResponse>, status::Custom<String>> {
-    let workspace_detail = fetch_workspace(rdb, &org_id, Some(&groups_owned.0)).await?;
-    Ok(Json(workspace_detail))
+    cache: Option<&State<RedisPoolState>>,
+    if_none_match: IfNoneMatch,
+) -> Result<ETagged<Json<WorkspaceDetailResponse>>, ApiError> {
+    let workspace_detail = fetch_workspace(
+        rdb,
+        cache.map(|c| c.inner()),
+        &org_id,
+        Some(&groups_owned.0),
+    )
+    .await?;
+
+    let revision = workspace_etag(&workspace_detail);
+    if if_none_match.matches(&revision) {
+        return Ok(ETagged::NotModified(revision));
+    }
+    Ok(ETagged::Body(revision, Json(workspace_detail)))
 }
 
 #[openapi()]
@@ -1698,9 +2365,16 @@ pub async fn get_workspace(
 pub async fn get_workspace_public(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     org_id: String,
-) -> Result<Json<WorkspaceDetailResponse>, status::Custom<String>> {
-    let workspace_detail = fetch_workspace(rdb, &org_id, None).await?;
-    Ok(Json(workspace_detail))
+    cache: Option<&State<RedisPoolState>>,
+    if_none_match: IfNoneMatch,
+) -> Result<ETagged<Json<WorkspaceDetailResponse>>, ApiError> {
+    let workspace_detail = fetch_workspace(rdb, cache.map(|c| c.inner()), &org_id, None).await?;
+
+    let revision = workspace_etag(&workspace_detail);
+    if if_none_match.matches(&revision) {
+        return Ok(ETagged::NotModified(revision));
+    }
+    Ok(ETagged::Body(revision, Json(workspace_detail)))
 }
 
 #[openapi()]
@@ -1709,65 +2383,63 @@ pub async fn get_workspace_details(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     groups_owned: GroupOwnerships,
     org_id: String,
-) -> Result<Json<WorkspaceSummaryResponse>, status::Custom<String>> {
+) -> Result<Json<WorkspaceSummaryResponse>, ApiError> {
     use crate::models::schema::schema::organization::dsl::*;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
-
-    let ownerships: Vec<String> = groups_owned.0;
-
-    let workspace = organization
-        .filter(slug.eq(&org_id))
-        .filter(group_id.eq_any(&ownerships))
-        .select((
-            slug,
-            name,
-            is_active,
-            group_id,
-            infra_repo_origin,
-            quick_links,
-            version,
-        ))
-        .first::<(
-            String,
-            Option<String>,
-            bool,
-            String,
-            Option<String>,
-            Option<String>,
-            Option<String>,
-        )>(&mut conn)
-        .optional()
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving workspace".to_string(),
-            )
-        })?;
-
-    match workspace {
-        Some((_slug, _name, _is_active, _group_id, _infra_repo_origin, _quick_links, _version)) => {
-            Ok(Json(WorkspaceSummaryResponse {
+    run_blocking(rdb, |conn| {
+        let ownerships: Vec<String> = groups_owned.0;
+
+        let workspace = organization
+            .filter(slug.eq(&org_id))
+            .filter(group_id.eq_any(&ownerships))
+            .filter(deleted_at.is_null())
+            .select((
+                slug,
+                name,
+                is_active,
+                group_id,
+                infra_repo_origin,
+                quick_links,
+                version,
+                purge_after,
+            ))
+            .first::<(
+                String,
+                Option<String>,
+                bool,
+                String,
+                Option<String>,
+                Option<String>,
+                Option<String>,
+                Option<DateTime<Utc>>,
+            )>(conn)
+            .optional()
+            .map_err(|_| ApiError::Internal("Error retrieving workspace".to_string()))?;
+
+        match workspace {
+            Some((
+                _slug,
+                _name,
+                _is_active,
+                _group_id,
+                _infra_repo_origin,
+                _quick_links,
+                _version,
+                _purge_after,
+            )) => Ok(Json(WorkspaceSummaryResponse {
                 slug: _slug,
                 name: _name,
                 is_active: _is_active,
                 group_id: _group_id,
                 is_admin: true,
                 infra_repo_origin: _infra_repo_origin,
-                quick_links: _quick_links,
+                quick_links: parse_quick_links(_quick_links.as_deref()),
                 version: _version,
-            }))
+                purge_after: _purge_after,
+            })),
+            None => Err(ApiError::NotFound("Workspace not found".to_string())),
         }
-        None => Err(status::Custom(
-            Status::NotFound,
-            "Workspace not found".to_string(),
-        )),
-    }
+    })
 }
 
 use crate::routes::MessageResponse;
@@ -1778,68 +2450,163 @@ pub async fn delete_workspace(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     org_id: String,
     groups_owned: GroupOwnerships,
-) -> Result<Json<MessageResponse>, rocket::http::Status> {
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<MessageResponse>, ApiError> {
     use crate::models::schema::schema::organization::dsl::*;
 
-    let mut conn = rdb.get().map_err(|_| Status::ServiceUnavailable)?;
+    run_blocking(rdb, |conn| {
+        let ownerships: Vec<String> = groups_owned.0;
+
+        // Fetch the workspace to ensure it exists and check if the user has permission to delete it
+        let workspace = organization
+            .filter(slug.eq(org_id.clone()))
+            .filter(deleted_at.is_null())
+            .select(group_id)
+            .first::<String>(conn)
+            .optional()
+            .map_err(|_| ApiError::Internal("Error retrieving workspace".to_string()))?;
+
+        if let Some(_group_id) = workspace {
+            // Check if the user owns the group to which the workspace belongs
+            if ownerships.contains(&_group_id) {
+                // Soft delete, the workspace can be restored until the retention window passes
+                conn.transaction(|conn| {
+                    soft_delete::delete_workspace(conn, &org_id, Utc::now())?;
+                    outbox::enqueue_workspace(conn, &org_id, "deleted")
+                })
+                .map_err(|_| ApiError::Internal("Error deleting workspace".to_string()))?;
 
-    let ownerships: Vec<String> = groups_owned.0;
+                invalidate(cache.map(|c| c.inner()), &org_id);
 
-    // Fetch the workspace to ensure it exists and check if the user has permission to delete it
-    let workspace = organization
-        .filter(slug.eq(org_id.clone()))
-        .select(group_id)
-        .first::<String>(&mut conn)
-        .optional()
-        .map_err(|_| Status::InternalServerError)?;
-
-    if let Some(_group_id) = workspace {
-        // Check if the user owns the group to which the workspace belongs
-        if ownerships.contains(&_group_id) {
-            // Proceed to delete the workspace
-            diesel::delete(organization.filter(slug.eq(org_id)))
-                .execute(&mut conn)
-                .map_err(|_| Status::InternalServerError)?;
-
-            Ok(Json(MessageResponse {
-                message: "Workspace successfully deleted".to_string(),
-            }))
+                Ok(Json(MessageResponse {
+                    message: "Workspace successfully deleted".to_string(),
+                }))
+            } else {
+                Err(ApiError::Forbidden(
+                    "Permission Denied: Only workspace owners can delete it.".to_string(),
+                ))
+            }
         } else {
-            Err(Status::Forbidden)
+            Err(ApiError::NotFound("Workspace not found".to_string()))
         }
-    } else {
-        Err(Status::NotFound)
-    }
+    })
+}
+
+/// Days a deactivated workspace is kept before it is purged along with its IAM group
+fn workspace_purge_after() -> chrono::Duration {
+    chrono::Duration::days(
+        std::env::var("WORKSPACE_PURGE_AFTER_DAYS")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(30),
+    )
 }
 
 #[openapi()]
-#[get("/version/<org_id>/<package_name>")]
-pub async fn get_package_version_plain_text(
+#[post("/manage-workspace/<org_id>/deactivate")]
+pub async fn deactivate_workspace(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     org_id: String,
-    package_name: String,
-) -> Result<String, status::Custom<String>> {
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<WorkspaceLifecycleResponse>, ApiError> {
+    use crate::models::schema::schema::organization::dsl::*;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+
+        // Deactivating again keeps the purge date set the first time
+        let scheduled_purge = org
+            .purge_after
+            .unwrap_or_else(|| Utc::now() + workspace_purge_after());
+
+        conn.transaction(|conn| {
+            diesel::update(organization.find(org.id))
+                .set((is_active.eq(false), purge_after.eq(Some(scheduled_purge))))
+                .execute(conn)?;
+            outbox::enqueue_workspace(conn, &org_id, "deactivated")
+        })
+        .map_err(|_| ApiError::Internal("Error deactivating workspace".to_string()))?;
+
+        invalidate(cache.map(|c| c.inner()), &org_id);
+
+        Ok(Json(WorkspaceLifecycleResponse {
+            message: "Workspace deactivated, it is read-only until reactivated".to_string(),
+            is_active: false,
+            purge_after: Some(scheduled_purge),
+        }))
+    })
+}
+
+#[openapi()]
+#[post("/manage-workspace/<org_id>/reactivate")]
+pub async fn reactivate_workspace(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<WorkspaceLifecycleResponse>, ApiError> {
+    use crate::models::schema::schema::organization::dsl::*;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+
+        conn.transaction(|conn| {
+            diesel::update(organization.find(org.id))
+                .set((is_active.eq(true), purge_after.eq(None::<DateTime<Utc>>)))
+                .execute(conn)?;
+            outbox::enqueue_workspace(conn, &org_id, "reactivated")
+        })
+        .map_err(|_| ApiError::Internal("Error reactivating workspace".to_string()))?;
+
+        invalidate(cache.map(|c| c.inner()), &org_id);
+
+        Ok(Json(WorkspaceLifecycleResponse {
+            message: "Workspace reactivated".to_string(),
+            is_active: true,
+            purge_after: None,
+        }))
+    })
+}
+
+fn fetch_package_version(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    cache: Option<&RedisPoolState>,
+    org_id: &str,
+    package_name: &str,
+) -> Result<String, ApiError> {
     use crate::models::schema::schema::package::dsl::*;
     use crate::models::schema::schema::package_env::dsl as package_env_dsl;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
+    cached(cache, org_id, &format!("version:{}", package_name), || {
+        run_blocking(rdb, |conn| {
+            // Fetch the latest version of the package for the given org_id and package_name
+            package
+                .inner_join(package_env_dsl::package_env.on(package_env_dsl::parent_id.eq(id)))
+                .filter(organization_id.eq(org_id))
+                .filter(identifier.eq(package_name))
+                .filter(deleted_at.is_null())
+                .filter(package_env_dsl::deleted_at.is_null())
+                .order_by(package_env_dsl::version.desc()) // Order by version descending to get the latest version
+                .select(package_env_dsl::version)
+                .first::<String>(conn)
+                .map_err(|_| ApiError::NotFound("Package or version not found".to_string()))
+        })
+    })
+}
 
-    // Fetch the latest version of the package for the given org_id and package_name
-    let version_result = package
-        .inner_join(package_env_dsl::package_env.on(package_env_dsl::parent_id.eq(id)))
-        .filter(organization_id.eq(org_id))
-        .filter(identifier.eq(package_name))
-        .order_by(package_env_dsl::version.desc()) // Order by version descending to get the latest version
-        .select(package_env_dsl::version)
-        .first::<String>(&mut conn)
-        .map_err(|_| {
-            status::Custom(Status::NotFound, "Package or version not found".to_string())
-        })?;
+#[openapi()]
+#[get("/version/<org_id>/<package_name>")]
+pub async fn get_package_version_plain_text(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    package_name: String,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<String, ApiError> {
+    let version_result =
+        fetch_package_version(rdb, cache.map(|c| c.inner()), &org_id, &package_name)?;
 
     // Return the version as plain text
     Ok(version_result)
@@ -1851,30 +2618,11 @@ pub async fn get_package_version(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     org_id: String,
     package_name: String,
-) -> Result<Json<VersionResponse>, status::Custom<String>> {
-    use crate::models::schema::schema::package::dsl::*;
-    use crate::models::schema::schema::package_env::dsl as package_env_dsl;
-
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
-
-    // Fetch the latest version of the package for the given org_id and package_name
-    let version_result = package
-        .inner_join(package_env_dsl::package_env.on(package_env_dsl::parent_id.eq(id)))
-        .filter(organization_id.eq(org_id))
-        .filter(identifier.eq(package_name))
-        .order_by(package_env_dsl::version.desc()) // Order by version descending to get the latest version
-        .select(package_env_dsl::version)
-        .first::<String>(&mut conn)
-        .map_err(|_| {
-            status::Custom(Status::NotFound, "Package or version not found".to_string())
-        })?;
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<VersionResponse>, ApiError> {
+    let version_result =
+        fetch_package_version(rdb, cache.map(|c| c.inner()), &org_id, &package_name)?;
 
-    // Return the version as plain text
     Ok(Json(VersionResponse {
         version: version_result,
     }))
@@ -1891,153 +2639,212 @@ pub fn update_db_pipeline(
     branch_name: String,
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     update_db_pipeline_request: Json<UpdateDbPipelineRequest>,
-    _claims: APIClaims,
-) -> Result<Json<Dbschema>, status::Custom<String>> {
+    claims: ActiveAPIClaims,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<Dbschema>, ApiError> {
     use crate::models::schema::schema::dbschema::dsl::*;
-    use crate::models::schema::schema::dbschema_branch::dsl as dbschema_branch_dsl;
-
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Failed to get DB connection".to_string(),
-        )
-    })?;
 
-    let updated_dbschema = dbschema
-        .filter(name.eq(schema_name.clone()))
-        .filter(organization_id.eq(org_id.clone()))
-        .first::<Dbschema>(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Error retrieving updated dbschema".to_string(),
-            )
-        })?;
+    run_blocking(rdb, |conn| {
+        claims.authorize(conn, &org_id, SCOPE_PIPELINE)?;
+        ensure_active(conn, &org_id)?;
+
+        let updated_dbschema = dbschema
+            .filter(name.eq(schema_name.clone()))
+            .filter(organization_id.eq(org_id.clone()))
+            .filter(deleted_at.is_null())
+            .first::<Dbschema>(conn)
+            .map_err(|_| ApiError::Internal("Error retrieving updated dbschema".to_string()))?;
+
+        let status = &update_db_pipeline_request.status;
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            pipeline::set_dbschema_pipeline(conn, updated_dbschema.id, &branch_name, status)?;
+            outbox::enqueue(
+                conn,
+                &org_id,
+                outbox::PIPELINE_UPDATE,
+                json!({
+                    "org_id": org_id,
+                    "identifier": updated_dbschema.identifier,
+                    "status": status
+                }),
+            )?;
+            if status == pipeline::FAILED {
+                webhooks::enqueue(
+                    conn,
+                    &org_id,
+                    webhooks::PIPELINE_FAILED,
+                    json!({
+                        "kind": "schema",
+                        "identifier": updated_dbschema.identifier,
+                        "env": branch_name
+                    }),
+                )?;
+            }
+            Ok(())
+        })
+        .map_err(|_| ApiError::Internal("Failed to update schema pipeline".to_string()))?;
 
-    diesel::update(
-        dbschema_branch_dsl::dbschema_branch
-            .filter(dbschema_branch_dsl::parent_id.eq(updated_dbschema.id))
-            .filter(dbschema_branch_dsl::branch_name.eq(&branch_name)),
-    )
-    .set(dbschema_branch_dsl::pipeline_status.eq(update_db_pipeline_request.status.clone()))
-    .execute(&mut conn)
-    .map_err(|_| {
-        status::Custom(
-            Status::InternalServerError,
-            "Failed to update schema pipeline".to_string(),
-        )
-    })?;
+        invalidate(cache.map(|c| c.inner()), &org_id);
 
-    Ok(Json(updated_dbschema))
+        Ok(Json(updated_dbschema))
+    })
 }
 
 #[openapi]
-#[get("/templates")]
+#[get("/templates?<cursor>&<limit>")]
 pub fn get_all_templates(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    claims: APIClaims,
-) -> Result<Json<Vec<Templates>>, rocket::http::Status> {
+    claims: ActiveAPIClaims,
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Json<Page<Templates>>, ApiError> {
     use crate::models::schema::schema::templates::dsl::*;
 
-    let mut conn = rdb
-        .get()
-        .map_err(|_| rocket::http::Status::ServiceUnavailable)?;
+    claims.require_scope(SCOPE_READ)?;
+    let page_request = PageRequest::parse(cursor.as_deref(), limit)?;
 
-    // Query all templates
-    let template_list = templates
-        .load::<Templates>(&mut conn)
-        .map_err(|_| rocket::http::Status::InternalServerError)?;
+    run_blocking(rdb, |conn| {
+        let mut query = templates.into_boxed();
+        if let Some(after_id) = page_request.after_id {
+            query = query.filter(id.gt(after_id));
+        }
 
-    Ok(Json(template_list))
+        let template_list = query
+            .order(id.asc())
+            .limit(page_request.fetch_limit())
+            .load::<Templates>(conn)
+            .map_err(|_| ApiError::Internal("Error retrieving templates".to_string()))?;
+
+        let total = templates
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|_| ApiError::Internal("Error counting templates".to_string()))?;
+
+        Ok(Json(page_request.page(template_list, total, |template| {
+            (None, template.id)
+        })))
+    })
 }
 
 #[openapi]
 #[post("/create-snapshot", data = "<create_snapshot_request>")]
 pub async fn create_snapshot(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
-    claims: APIClaims,
-    create_snapshot_request: Json<CreateSnapshotRequest>,
-) -> Result<Json<MessageResponse>, status::Custom<String>> {
+    claims: ActiveAPIClaims,
+    mut create_snapshot_request: Json<CreateSnapshotRequest>,
+) -> Result<Json<MessageResponse>, ApiError> {
     use crate::models::schema::schema::organization::dsl as org_dsl;
     use crate::models::schema::schema::snapshots::dsl::*;
 
-    let mut conn = rdb.get().map_err(|_| {
-        status::Custom(
-            Status::ServiceUnavailable,
-            "Database unavailable".to_string(),
-        )
-    })?;
+    validate_quick_links(
+        create_snapshot_request
+            .quick_links
+            .as_deref()
+            .unwrap_or_default(),
+    )?;
+
+    run_blocking(rdb, |conn| {
+        create_snapshot_request.org_id = canonical_slug(conn, &create_snapshot_request.org_id)?;
+        claims.authorize(conn, &create_snapshot_request.org_id, SCOPE_WRITE)?;
+        ensure_active(conn, &create_snapshot_request.org_id)?;
+
+        let new_snapshot = SnapshotsInsertable {
+            version: create_snapshot_request.version.clone(),
+            created_at: Utc::now(), // Ensure NaiveDateTime is used
+            updated_at: Utc::now(),
+            organization_id: create_snapshot_request.org_id.clone(),
+        };
 
-    let new_snapshot = SnapshotsInsertable {
-        version: create_snapshot_request.version.clone(),
-        created_at: Utc::now(), // Ensure NaiveDateTime is used
-        updated_at: Utc::now(),
-        organization_id: create_snapshot_request.org_id.clone(),
-    };
+        conn.transaction::<_, ApiError, _>(|conn| {
+            diesel::insert_into(snapshots)
+                .values(&new_snapshot)
+                .execute(conn)
+                .map_err(|_| ApiError::Internal("Failed to create snapshot".to_string()))?;
 
-    diesel::insert_into(snapshots)
-        .values(&new_snapshot)
-        .execute(&mut conn)
-        .map_err(|_| {
-            status::Custom(
-                Status::InternalServerError,
-                "Failed to create snapshot".to_string(),
+            let updated_rows = diesel::update(
+                org_dsl::organization
+                    .filter(org_dsl::slug.eq(create_snapshot_request.org_id.clone()))
+                    .filter(org_dsl::deleted_at.is_null()),
             )
-        })?;
-
-    let updated_rows = diesel::update(
-        org_dsl::organization.filter(org_dsl::slug.eq(create_snapshot_request.org_id.clone())),
-    )
-    .set((
-        org_dsl::infra_repo_origin.eq(create_snapshot_request.infra_repo_origin.clone()),
-        org_dsl::quick_links.eq(create_snapshot_request.quick_links.clone()),
-        org_dsl::version.eq(create_snapshot_request.version.clone()),
-    )) // Assuming this field is in your organization table
-    .execute(&mut conn)
-    .map_err(|_| {
-        status::Custom(
-            Status::InternalServerError,
-            "Failed to update organization".to_string(),
-        )
-    })?;
+            .set((
+                org_dsl::infra_repo_origin.eq(create_snapshot_request.infra_repo_origin.clone()),
+                org_dsl::version.eq(create_snapshot_request.version.clone()),
+            )) // Assuming this field is in your organization table
+            .execute(conn)
+            .map_err(|_| ApiError::Internal("Failed to update organization".to_string()))?;
+
+            if updated_rows == 0 {
+                return Err(ApiError::NotFound("Organization not found".to_string()));
+            }
+            // Links added from the UI are kept when the snapshot does not carry any
+            if let Some(links) = &create_snapshot_request.quick_links {
+                diesel::update(
+                    org_dsl::organization.filter(org_dsl::slug.eq(&create_snapshot_request.org_id)),
+                )
+                .set(org_dsl::quick_links.eq(stored_quick_links(links)))
+                .execute(conn)?;
+            }
 
-    if updated_rows == 0 {
-        return Err(status::Custom(
-            Status::NotFound,
-            "Organization not found".to_string(),
-        ));
-    }
+            outbox::enqueue_catalog(
+                conn,
+                &create_snapshot_request.org_id,
+                "snapshot",
+                &create_snapshot_request.version,
+                "created",
+            )?;
+            webhooks::enqueue(
+                conn,
+                &create_snapshot_request.org_id,
+                webhooks::SNAPSHOT_CREATED,
+                json!({"version": create_snapshot_request.version}),
+            )?;
+            Ok(())
+        })?;
 
-    Ok(Json(MessageResponse {
-        message: "Snapshot record created and organization updated".to_string(),
-    }))
+        Ok(Json(MessageResponse {
+            message: "Snapshot record created and organization updated".to_string(),
+        }))
+    })
 }
 
 #[openapi]
-#[get("/get-snapshots/<org_id>")]
+#[get("/get-snapshots/<org_id>?<cursor>&<limit>")]
 pub async fn get_snapshots(
     rdb: &State<Pool<ConnectionManager<PgConnection>>>,
     org_id: String,
-) -> Result<Json<Vec<SnapshotsResponse>>, rocket::http::Status> {
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Json<Page<SnapshotsResponse>>, ApiError> {
     use crate::models::schema::schema::snapshots::dsl::*;
 
-    let mut conn = rdb
-        .get()
-        .map_err(|_| rocket::http::Status::ServiceUnavailable)?;
+    let page_request = PageRequest::parse(cursor.as_deref(), limit)?;
 
-    let db_snapshots = snapshots
-        .filter(organization_id.eq(org_id))
-        .load::<Snapshots>(&mut conn)
-        .map_err(|_| rocket::http::Status::InternalServerError)?;
-
-    // Map only the version and created_at fields
-    let response_snapshots: Vec<SnapshotsResponse> = db_snapshots
-        .into_iter()
-        .map(|snapshot| SnapshotsResponse {
-            version: snapshot.version,
-            created_at: snapshot.created_at,
-        })
-        .collect();
+    run_blocking(rdb, |conn| {
+        let mut query = snapshots.filter(organization_id.eq(&org_id)).into_boxed();
+        if let Some(after_id) = page_request.after_id {
+            query = query.filter(id.gt(after_id));
+        }
 
-    Ok(Json(response_snapshots))
+        let db_snapshots = query
+            .order(id.asc())
+            .limit(page_request.fetch_limit())
+            .load::<Snapshots>(conn)
+            .map_err(|_| ApiError::Internal("Error retrieving snapshots".to_string()))?;
+
+        let total = snapshots
+            .filter(organization_id.eq(&org_id))
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|_| ApiError::Internal("Error counting snapshots".to_string()))?;
+
+        // Map only the version and created_at fields
+        let response_snapshots = page_request
+            .page(db_snapshots, total, |snapshot| (None, snapshot.id))
+            .map(|snapshot| SnapshotsResponse {
+                version: snapshot.version,
+                created_at: snapshot.created_at,
+            });
+
+        Ok(Json(response_snapshots))
+    })
 }
@@ -1,8 +1,23 @@
+use crate::errors::ApiError;
+use crate::models::schema::Organization;
+use diesel::prelude::*;
 use ginger_shared_rs::rocket_models::MessageResponse;
 use rocket::serde::json::Json;
 use rocket_okapi::openapi;
 
+pub mod api_tokens;
+pub mod canvas;
+pub mod deletions;
+pub mod discovery;
+pub mod health;
+pub mod manifests;
 pub mod metadata;
+pub mod outbox;
+pub mod pagination;
+pub mod projection;
+pub mod quick_links;
+pub mod webhooks;
+pub mod workspaces;
 
 /// This is a description. <br />You can do simple html <br /> like <b>this<b/>
 #[openapi()]
@@ -12,3 +27,104 @@ pub fn index() -> Json<MessageResponse> {
         message: "Ok".to_string(),
     })
 }
+
+/// Fetches the workspace and makes sure the caller owns it
+pub fn owned_organization(
+    conn: &mut PgConnection,
+    org_id: &str,
+    ownerships: &[String],
+) -> Result<Organization, ApiError> {
+    use crate::models::schema::schema::organization::dsl::*;
+
+    let org = organization
+        .filter(slug.eq(org_id))
+        .filter(deleted_at.is_null())
+        .first::<Organization>(conn)
+        .optional()
+        .map_err(|_| ApiError::Internal("Error retrieving workspace".to_string()))?
+        .ok_or_else(|| ApiError::NotFound("Workspace not found".to_string()))?;
+
+    if !ownerships.contains(&org.group_id) {
+        return Err(ApiError::Forbidden(
+            "Permission Denied: Only workspace owners can do this.".to_string(),
+        ));
+    }
+
+    Ok(org)
+}
+
+/// Fetches the workspace and makes sure the caller is one of its members or owners
+pub fn member_organization(
+    conn: &mut PgConnection,
+    org_id: &str,
+    memberships: &[String],
+    ownerships: &[String],
+) -> Result<Organization, ApiError> {
+    use crate::models::schema::schema::organization;
+
+    let org = organization::table
+        .filter(organization::slug.eq(org_id))
+        .filter(organization::deleted_at.is_null())
+        .first::<Organization>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound("Workspace not found".to_string()))?;
+
+    if !memberships.contains(&org.group_id) && !ownerships.contains(&org.group_id) {
+        return Err(ApiError::Forbidden(
+            "Permission Denied: You are not a member of this workspace.".to_string(),
+        ));
+    }
+    Ok(org)
+}
+
+/// Deactivated workspaces are read-only until they are reactivated
+pub fn ensure_active(conn: &mut PgConnection, org_id: &str) -> Result<(), ApiError> {
+    use crate::models::schema::schema::organization::dsl::*;
+
+    let active = organization
+        .filter(slug.eq(org_id))
+        .filter(deleted_at.is_null())
+        .select(is_active)
+        .first::<bool>(conn)
+        .optional()
+        .map_err(|_| ApiError::Internal("Error retrieving workspace".to_string()))?;
+
+    match active {
+        Some(false) => Err(ApiError::Conflict(format!(
+            "Workspace {} is deactivated, reactivate it before making changes",
+            org_id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Slugs stay reserved after a slug change so old URLs keep resolving to the same workspace
+pub fn slug_taken(conn: &mut PgConnection, candidate: &str) -> Result<bool, ApiError> {
+    use crate::models::schema::schema::{organization, organization_slug_alias};
+
+    let in_use = diesel::select(diesel::dsl::exists(
+        organization::table.filter(organization::slug.eq(candidate)),
+    ))
+    .get_result::<bool>(conn)?;
+
+    let aliased = diesel::select(diesel::dsl::exists(
+        organization_slug_alias::table.filter(organization_slug_alias::old_slug.eq(candidate)),
+    ))
+    .get_result::<bool>(conn)?;
+
+    Ok(in_use || aliased)
+}
+
+/// Slug the workspace has now, callers may still hold one it had before a slug change
+pub fn canonical_slug(conn: &mut PgConnection, org_id: &str) -> Result<String, ApiError> {
+    use crate::models::schema::schema::{organization, organization_slug_alias};
+
+    let current = organization_slug_alias::table
+        .inner_join(organization::table)
+        .filter(organization_slug_alias::old_slug.eq(org_id))
+        .select(organization::slug)
+        .first::<String>(conn)
+        .optional()?;
+
+    Ok(current.unwrap_or_else(|| org_id.to_string()))
+}
@@ -0,0 +1,93 @@
+use crate::db::outbox;
+use crate::db::pool::run_blocking;
+use crate::errors::ApiError;
+use crate::middlewares::groups_owned::GroupOwnerships;
+use crate::models::response::{OutboxEventResponse, Page};
+use crate::models::schema::schema::outbox_event;
+use crate::models::schema::Outbox_Event;
+use crate::routes::owned_organization;
+use crate::routes::pagination::PageRequest;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use ginger_shared_rs::rocket_models::MessageResponse;
+use ginger_shared_rs::rocket_utils::Claims;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+
+fn to_response(event: Outbox_Event) -> OutboxEventResponse {
+    OutboxEventResponse {
+        id: event.id,
+        topic: event.topic,
+        payload: serde_json::from_str(&event.payload).unwrap_or(serde_json::Value::Null),
+        attempts: event.attempts,
+        last_error: event.last_error,
+        created_at: event.created_at,
+        dead_lettered_at: event.dead_lettered_at,
+    }
+}
+
+/// Events NotificationService kept rejecting, oldest first
+#[openapi()]
+#[get("/workspace/<org_id>/outbox/dead-letters?<cursor>&<limit>")]
+pub async fn get_dead_letters(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Json<Page<OutboxEventResponse>>, ApiError> {
+    let page_request = PageRequest::parse(cursor.as_deref(), limit)?;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        let dead_letters = || {
+            outbox_event::table
+                .filter(outbox_event::organization_id.eq(&org.slug))
+                .filter(outbox_event::dead_lettered_at.is_not_null())
+        };
+
+        let mut query = dead_letters().into_boxed();
+        if let Some(after_id) = page_request.after_id {
+            query = query.filter(outbox_event::id.gt(after_id));
+        }
+
+        let events = query
+            .order(outbox_event::id.asc())
+            .limit(page_request.fetch_limit())
+            .load::<Outbox_Event>(conn)?;
+        let total = dead_letters().count().get_result::<i64>(conn)?;
+
+        Ok(Json(
+            page_request
+                .page(events, total, |event| (None, event.id))
+                .map(to_response),
+        ))
+    })
+}
+
+/// Queues a dead lettered event for delivery again, with a fresh set of attempts
+#[openapi()]
+#[post("/workspace/<org_id>/outbox/dead-letters/<event_id>/retry")]
+pub async fn retry_dead_letter(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    event_id: i64,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+
+        if outbox::requeue(conn, &org.slug, event_id)? == 0 {
+            return Err(ApiError::NotFound(
+                "Dead lettered event not found".to_string(),
+            ));
+        }
+
+        Ok(Json(MessageResponse {
+            message: "Event queued for delivery".to_string(),
+        }))
+    })
+}
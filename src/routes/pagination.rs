@@ -0,0 +1,176 @@
+use crate::errors::ApiError;
+use crate::models::response::Page;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_PAGE_LIMIT: i64 = 20;
+const MAX_PAGE_LIMIT: i64 = 100;
+
+// Opaque to clients, hex encoded so it survives query strings untouched
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    id: i64,
+    key: Option<String>,
+}
+
+/// Keyset position parsed from `cursor` and `limit`, a page holds the rows ordered after
+/// `(key, id)` of the last row of the previous page
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub after_id: Option<i64>,
+    pub after_key: Option<String>,
+    pub limit: i64,
+}
+
+impl PageRequest {
+    pub fn parse(cursor: Option<&str>, limit: Option<i64>) -> Result<Self, ApiError> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "limit must be between 1 and {}",
+                MAX_PAGE_LIMIT
+            )));
+        }
+
+        let cursor = match cursor {
+            Some(cursor) => Some(
+                hex::decode(cursor)
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice::<Cursor>(&bytes).ok())
+                    .ok_or_else(|| ApiError::BadRequest("cursor is malformed".to_string()))?,
+            ),
+            None => None,
+        };
+
+        Ok(PageRequest {
+            after_id: cursor.as_ref().map(|c| c.id),
+            after_key: cursor.and_then(|c| c.key),
+            limit,
+        })
+    }
+
+    /// One extra row is fetched to know whether another page follows
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    /// Distinguishes cached pages of the same listing
+    pub fn cache_key(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.after_key.as_deref().unwrap_or(""),
+            self.after_id.unwrap_or(0),
+            self.limit
+        )
+    }
+
+    /// Sort key of the last row of the previous page, when the listing is not sorted by id
+    pub fn sort_key(&self) -> Result<&str, ApiError> {
+        self.after_key
+            .as_deref()
+            .ok_or_else(|| ApiError::BadRequest("cursor does not match the sort".to_string()))
+    }
+
+    pub fn sort_timestamp(&self) -> Result<DateTime<Utc>, ApiError> {
+        DateTime::parse_from_rfc3339(self.sort_key()?)
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+            .map_err(|_| ApiError::BadRequest("cursor does not match the sort".to_string()))
+    }
+
+    /// Builds the page from rows fetched with `fetch_limit`, `position` gives the sort key and
+    /// id the next page continues after
+    pub fn page<T>(
+        &self,
+        mut rows: Vec<T>,
+        total: i64,
+        position: impl Fn(&T) -> (Option<String>, i64),
+    ) -> Page<T> {
+        let has_more = rows.len() as i64 > self.limit;
+        rows.truncate(self.limit as usize);
+
+        let next_cursor = rows.last().filter(|_| has_more).map(|last| {
+            let (key, id) = position(last);
+            hex::encode(serde_json::to_vec(&Cursor { id, key }).unwrap_or_default())
+        });
+
+        Page {
+            items: rows,
+            next_cursor,
+            total,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Id,
+    Identifier,
+    UpdatedAt,
+}
+
+/// Order of a catalog listing, ties are broken by id in the same direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Sort {
+    /// Accepts `identifier` or `updated_at`, prefixed with `-` for descending, and defaults to
+    /// creation order
+    pub fn parse(sort: Option<&str>) -> Result<Self, ApiError> {
+        let Some(sort) = sort else {
+            return Ok(Sort {
+                key: SortKey::Id,
+                descending: false,
+            });
+        };
+
+        let (descending, column) = match sort.strip_prefix('-') {
+            Some(column) => (true, column),
+            None => (false, sort),
+        };
+        let key = match column {
+            "identifier" => SortKey::Identifier,
+            "updated_at" => SortKey::UpdatedAt,
+            _ => {
+                return Err(ApiError::UnprocessableEntity(
+                    "sort must be identifier or updated_at, optionally prefixed with -".to_string(),
+                ))
+            }
+        };
+
+        Ok(Sort { key, descending })
+    }
+
+    /// Cursor key of a row, read back with `PageRequest::sort_key` or `sort_timestamp`
+    pub fn cursor_key(&self, identifier: &str, updated_at: DateTime<Utc>) -> Option<String> {
+        match self.key {
+            SortKey::Id => None,
+            SortKey::Identifier => Some(identifier.to_string()),
+            SortKey::UpdatedAt => Some(updated_at.to_rfc3339()),
+        }
+    }
+
+    pub fn cache_key(&self) -> String {
+        format!("{:?}:{}", self.key, self.descending)
+    }
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            total: self.total,
+        }
+    }
+
+    pub fn try_map<U, E>(self, f: impl FnMut(T) -> Result<U, E>) -> Result<Page<U>, E> {
+        Ok(Page {
+            items: self.items.into_iter().map(f).collect::<Result<_, _>>()?,
+            next_cursor: self.next_cursor,
+            total: self.total,
+        })
+    }
+}
@@ -0,0 +1,90 @@
+use crate::errors::ApiError;
+use crate::models::response::Page;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Serialize, Serializer};
+use std::marker::PhantomData;
+
+/// List item reduced to the top level fields asked for with `fields=`, documented as the full
+/// item since clients can ask for any of them
+pub struct Projected<T> {
+    value: serde_json::Value,
+    item: PhantomData<T>,
+}
+
+impl<T> Serialize for Projected<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Projected<T> {
+    fn schema_name() -> String {
+        T::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        T::json_schema(gen)
+    }
+}
+
+// Top level fields of `T` as documented in the OpenAPI spec
+fn known_fields<T: JsonSchema>() -> Vec<String> {
+    SchemaGenerator::default()
+        .into_root_schema_for::<T>()
+        .schema
+        .object
+        .map(|object| object.properties.into_keys().collect())
+        .unwrap_or_default()
+}
+
+/// Parses a comma separated `fields` list, `None` keeps every field
+pub fn parse_fields<T: JsonSchema>(fields: Option<&str>) -> Result<Option<Vec<String>>, ApiError> {
+    let Some(fields) = fields else {
+        return Ok(None);
+    };
+
+    let known = known_fields::<T>();
+    let requested: Vec<String> = fields
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(str::to_string)
+        .collect();
+    if requested.is_empty() {
+        return Err(ApiError::UnprocessableEntity(
+            "fields must name at least one field".to_string(),
+        ));
+    }
+    if let Some(unknown) = requested.iter().find(|field| !known.contains(field)) {
+        return Err(ApiError::UnprocessableEntity(format!(
+            "Unknown field {}, expected any of {}",
+            unknown,
+            known.join(", ")
+        )));
+    }
+
+    Ok(Some(requested))
+}
+
+/// Applied after the cache so every projection of a listing shares the cached page
+pub fn project<T: Serialize + JsonSchema>(
+    page: Page<T>,
+    fields: Option<&str>,
+) -> Result<Page<Projected<T>>, ApiError> {
+    let fields = parse_fields::<T>(fields)?;
+
+    page.try_map(|item| {
+        let mut value = serde_json::to_value(&item)
+            .map_err(|_| ApiError::Internal("Error serializing list item".to_string()))?;
+        if let (Some(fields), Some(object)) = (&fields, value.as_object_mut()) {
+            object.retain(|key, _| fields.contains(key));
+        }
+
+        Ok(Projected {
+            value,
+            item: PhantomData,
+        })
+    })
+}
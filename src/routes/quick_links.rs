@@ -0,0 +1,376 @@
+use crate::db::outbox;
+use crate::db::pool::run_blocking;
+use crate::db::redis::{invalidate, RedisPoolState};
+use crate::errors::ApiError;
+use crate::middlewares::conditional::{etag, ETagged, IfMatch, IfNoneMatch};
+use crate::middlewares::groups::GroupMemberships;
+use crate::middlewares::groups_owned::GroupOwnerships;
+use crate::models::quick_link::{parse_quick_links, stored_quick_links, QuickLink};
+use crate::models::request::QuickLinkOwnerQuery;
+use crate::models::schema::schema::{dbschema, organization, package, service};
+use crate::routes::{ensure_active, member_organization};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use ginger_shared_rs::rocket_utils::Claims;
+use reqwest::Url;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+
+const MAX_QUICK_LINKS: usize = 20;
+const QUICK_LINK_LABEL_MAX_LENGTH: usize = 100;
+const QUICK_LINK_URL_MAX_LENGTH: usize = 1000;
+// Size of the smallest `quick_links` column
+const QUICK_LINKS_MAX_LENGTH: usize = 10000;
+
+/// react-icons the UI renders for quick links
+pub const KNOWN_ICONS: [&str; 24] = [
+    "FaBook",
+    "FaBug",
+    "FaChartLine",
+    "FaCloud",
+    "FaCodeBranch",
+    "FaDatabase",
+    "FaDocker",
+    "FaExternalLinkAlt",
+    "FaFileAlt",
+    "FaGithub",
+    "FaGitlab",
+    "FaGlobe",
+    "FaJira",
+    "FaKey",
+    "FaLink",
+    "FaLock",
+    "FaRegFileCode",
+    "FaRegPlayCircle",
+    "FaRocket",
+    "FaServer",
+    "FaSlack",
+    "FaTerminal",
+    "FaTools",
+    "FaUsers",
+];
+
+fn validate_quick_link(link: &QuickLink) -> Result<(), ApiError> {
+    let label = link.label.trim();
+    if label.is_empty() || label.chars().count() > QUICK_LINK_LABEL_MAX_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "Quick link labels must be between 1 and {} characters",
+            QUICK_LINK_LABEL_MAX_LENGTH
+        )));
+    }
+    if !KNOWN_ICONS.contains(&link.icon.as_str()) {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown icon {} for quick link {}, expected one of {}",
+            link.icon,
+            label,
+            KNOWN_ICONS.join(", ")
+        )));
+    }
+
+    let valid_url = link.link.len() <= QUICK_LINK_URL_MAX_LENGTH
+        && Url::parse(&link.link).map_or(false, |url| {
+            matches!(url.scheme(), "http" | "https") && url.host_str().is_some()
+        });
+    if !valid_url {
+        return Err(ApiError::BadRequest(format!(
+            "Quick link {} must point to an absolute http or https URL",
+            label
+        )));
+    }
+    Ok(())
+}
+
+/// Checks links before they are stored, whichever endpoint they come through
+pub fn validate_quick_links(links: &[QuickLink]) -> Result<(), ApiError> {
+    if links.len() > MAX_QUICK_LINKS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} quick links are allowed",
+            MAX_QUICK_LINKS
+        )));
+    }
+    links.iter().try_for_each(validate_quick_link)?;
+
+    if stored_quick_links(links).map_or(0, |stored| stored.len()) > QUICK_LINKS_MAX_LENGTH {
+        return Err(ApiError::BadRequest("Quick links are too long".to_string()));
+    }
+    Ok(())
+}
+
+enum LinkOwner {
+    Workspace,
+    Dbschema(String),
+    Service(String),
+    Package(String),
+}
+
+impl LinkOwner {
+    fn parse(query: QuickLinkOwnerQuery) -> Result<Self, ApiError> {
+        match (query.kind.as_deref(), query.identifier) {
+            (None | Some("workspace"), None) => Ok(LinkOwner::Workspace),
+            (Some("dbschema"), Some(identifier)) => Ok(LinkOwner::Dbschema(identifier)),
+            (Some("service"), Some(identifier)) => Ok(LinkOwner::Service(identifier)),
+            (Some("package"), Some(identifier)) => Ok(LinkOwner::Package(identifier)),
+            (Some("dbschema" | "service" | "package"), None) => Err(ApiError::BadRequest(
+                "identifier is required for this kind".to_string(),
+            )),
+            (None | Some("workspace"), Some(_)) => Err(ApiError::BadRequest(
+                "identifier is only used with a kind of dbschema, service or package".to_string(),
+            )),
+            (Some(kind), _) => Err(ApiError::BadRequest(format!(
+                "Unknown kind {}, expected workspace, dbschema, service or package",
+                kind
+            ))),
+        }
+    }
+
+    // Locks the row, edits are read-modify-write
+    fn load(&self, conn: &mut PgConnection, org_id: &str) -> Result<Option<String>, ApiError> {
+        let stored = match self {
+            LinkOwner::Workspace => organization::table
+                .filter(organization::slug.eq(org_id))
+                .filter(organization::deleted_at.is_null())
+                .select(organization::quick_links)
+                .for_update()
+                .first::<Option<String>>(conn)
+                .optional()?,
+            LinkOwner::Dbschema(identifier) => dbschema::table
+                .filter(dbschema::organization_id.eq(org_id))
+                .filter(dbschema::identifier.eq(identifier))
+                .filter(dbschema::deleted_at.is_null())
+                .select(dbschema::quick_links)
+                .for_update()
+                .first::<Option<String>>(conn)
+                .optional()?,
+            LinkOwner::Service(identifier) => service::table
+                .filter(service::organization_id.eq(org_id))
+                .filter(service::identifier.eq(identifier))
+                .filter(service::deleted_at.is_null())
+                .select(service::quick_links)
+                .for_update()
+                .first::<Option<String>>(conn)
+                .optional()?,
+            LinkOwner::Package(identifier) => package::table
+                .filter(package::organization_id.eq(org_id))
+                .filter(package::identifier.eq(identifier))
+                .filter(package::deleted_at.is_null())
+                .select(package::quick_links)
+                .for_update()
+                .first::<Option<String>>(conn)
+                .optional()?,
+        };
+        stored.ok_or_else(|| ApiError::NotFound(format!("{} not found", self.kind())))
+    }
+
+    fn save(
+        &self,
+        conn: &mut PgConnection,
+        org_id: &str,
+        stored: Option<String>,
+    ) -> QueryResult<()> {
+        match self {
+            LinkOwner::Workspace => {
+                diesel::update(organization::table.filter(organization::slug.eq(org_id)))
+                    .set(organization::quick_links.eq(stored))
+                    .execute(conn)?;
+                outbox::enqueue_workspace(conn, org_id, "quick_links_updated")
+            }
+            LinkOwner::Dbschema(identifier) => {
+                diesel::update(
+                    dbschema::table
+                        .filter(dbschema::organization_id.eq(org_id))
+                        .filter(dbschema::identifier.eq(identifier)),
+                )
+                .set(dbschema::quick_links.eq(stored))
+                .execute(conn)?;
+                outbox::enqueue_catalog(conn, org_id, self.kind(), identifier, "updated")
+            }
+            LinkOwner::Service(identifier) => {
+                diesel::update(
+                    service::table
+                        .filter(service::organization_id.eq(org_id))
+                        .filter(service::identifier.eq(identifier)),
+                )
+                .set(service::quick_links.eq(stored))
+                .execute(conn)?;
+                outbox::enqueue_catalog(conn, org_id, self.kind(), identifier, "updated")
+            }
+            LinkOwner::Package(identifier) => {
+                diesel::update(
+                    package::table
+                        .filter(package::organization_id.eq(org_id))
+                        .filter(package::identifier.eq(identifier)),
+                )
+                .set(package::quick_links.eq(stored))
+                .execute(conn)?;
+                outbox::enqueue_catalog(conn, org_id, self.kind(), identifier, "updated")
+            }
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            LinkOwner::Workspace => "workspace",
+            LinkOwner::Dbschema(_) => "dbschema",
+            LinkOwner::Service(_) => "service",
+            LinkOwner::Package(_) => "package",
+        }
+    }
+}
+
+/// Applies `edit` to the links when `If-Match` holds the revision of the last read
+fn edit_links(
+    rdb: &Pool<ConnectionManager<PgConnection>>,
+    cache: Option<&RedisPoolState>,
+    org_id: &str,
+    owner: &LinkOwner,
+    access: (&[String], &[String]),
+    if_match: &IfMatch,
+    edit: impl FnOnce(&mut Vec<QuickLink>) -> Result<(), ApiError>,
+) -> Result<ETagged<Json<Vec<QuickLink>>>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = member_organization(conn, org_id, access.0, access.1)?;
+        ensure_active(conn, &org.slug)?;
+
+        let links = conn.transaction::<_, ApiError, _>(|conn| {
+            let mut links = parse_quick_links(owner.load(conn, &org.slug)?.as_deref());
+            if_match.verify(&etag(&links))?;
+
+            edit(&mut links)?;
+            validate_quick_links(&links)?;
+
+            owner.save(conn, &org.slug, stored_quick_links(&links))?;
+            Ok(links)
+        })?;
+
+        invalidate(cache, &org.slug);
+
+        Ok(ETagged::Body(etag(&links), Json(links)))
+    })
+}
+
+fn link_not_found() -> ApiError {
+    ApiError::NotFound("Quick link not found".to_string())
+}
+
+#[openapi()]
+#[get("/workspace/<org_id>/quick-links?<owner..>")]
+pub async fn get_quick_links(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    owner: QuickLinkOwnerQuery,
+    _claims: Claims,
+    groups: GroupMemberships,
+    groups_owned: GroupOwnerships,
+    if_none_match: IfNoneMatch,
+) -> Result<ETagged<Json<Vec<QuickLink>>>, ApiError> {
+    let owner = LinkOwner::parse(owner)?;
+
+    let links = run_blocking(rdb, |conn| {
+        let org = member_organization(conn, &org_id, &groups.0, &groups_owned.0)?;
+        Ok(parse_quick_links(owner.load(conn, &org.slug)?.as_deref()))
+    })?;
+
+    let revision = etag(&links);
+    if if_none_match.matches(&revision) {
+        return Ok(ETagged::NotModified(revision));
+    }
+    Ok(ETagged::Body(revision, Json(links)))
+}
+
+/// Appends a link, the response holds all the links and their new revision
+#[openapi()]
+#[post("/workspace/<org_id>/quick-links?<owner..>", data = "<link>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn add_quick_link(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    owner: QuickLinkOwnerQuery,
+    link: Json<QuickLink>,
+    _claims: Claims,
+    groups: GroupMemberships,
+    groups_owned: GroupOwnerships,
+    if_match: IfMatch,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<ETagged<Json<Vec<QuickLink>>>, ApiError> {
+    let owner = LinkOwner::parse(owner)?;
+
+    edit_links(
+        rdb,
+        cache.map(|c| c.inner()),
+        &org_id,
+        &owner,
+        (groups.0.as_slice(), groups_owned.0.as_slice()),
+        &if_match,
+        |links| {
+            links.push(link.into_inner());
+            Ok(())
+        },
+    )
+}
+
+/// Replaces the link at `index`, in the order links are returned
+#[openapi()]
+#[put("/workspace/<org_id>/quick-links/<index>?<owner..>", data = "<link>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn update_quick_link(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    index: usize,
+    owner: QuickLinkOwnerQuery,
+    link: Json<QuickLink>,
+    _claims: Claims,
+    groups: GroupMemberships,
+    groups_owned: GroupOwnerships,
+    if_match: IfMatch,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<ETagged<Json<Vec<QuickLink>>>, ApiError> {
+    let owner = LinkOwner::parse(owner)?;
+
+    edit_links(
+        rdb,
+        cache.map(|c| c.inner()),
+        &org_id,
+        &owner,
+        (groups.0.as_slice(), groups_owned.0.as_slice()),
+        &if_match,
+        |links| {
+            let existing = links.get_mut(index).ok_or_else(link_not_found)?;
+            *existing = link.into_inner();
+            Ok(())
+        },
+    )
+}
+
+#[openapi()]
+#[delete("/workspace/<org_id>/quick-links/<index>?<owner..>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn delete_quick_link(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    index: usize,
+    owner: QuickLinkOwnerQuery,
+    _claims: Claims,
+    groups: GroupMemberships,
+    groups_owned: GroupOwnerships,
+    if_match: IfMatch,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<ETagged<Json<Vec<QuickLink>>>, ApiError> {
+    let owner = LinkOwner::parse(owner)?;
+
+    edit_links(
+        rdb,
+        cache.map(|c| c.inner()),
+        &org_id,
+        &owner,
+        (groups.0.as_slice(), groups_owned.0.as_slice()),
+        &if_match,
+        |links| {
+            if index >= links.len() {
+                return Err(link_not_found());
+            }
+            links.remove(index);
+            Ok(())
+        },
+    )
+}
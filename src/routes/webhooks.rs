@@ -0,0 +1,298 @@
+use crate::db::pool::run_blocking;
+use crate::db::webhooks::{self, subscribed_events, EVENT_TYPES};
+use crate::errors::ApiError;
+use crate::fairings::webhooks::{check_target, private_targets_allowed};
+use crate::middlewares::groups_owned::GroupOwnerships;
+use crate::models::request::CreateWebhookRequest;
+use crate::models::response::{
+    Page, RegisteredWebhookResponse, WebhookDeliveryResponse, WebhookResponse,
+};
+use crate::models::schema::schema::{webhook, webhook_delivery};
+use crate::models::schema::{Webhook, WebhookInsertable, Webhook_Delivery};
+use crate::routes::pagination::PageRequest;
+use crate::routes::{ensure_active, owned_organization};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use ginger_shared_rs::rocket_models::MessageResponse;
+use ginger_shared_rs::rocket_utils::Claims;
+use reqwest::Url;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+use uuid::Uuid;
+
+const WEBHOOK_URL_MAX_LENGTH: usize = 2000;
+
+fn to_response(hook: Webhook) -> WebhookResponse {
+    WebhookResponse {
+        id: hook.id,
+        event_types: subscribed_events(&hook),
+        org_id: hook.organization_id,
+        url: hook.url,
+        created_by: hook.created_by,
+        created_at: hook.created_at,
+    }
+}
+
+fn to_delivery_response(delivery: Webhook_Delivery) -> WebhookDeliveryResponse {
+    let status = if delivery.delivered_at.is_some() {
+        "delivered"
+    } else if delivery.failed_at.is_some() {
+        "failed"
+    } else {
+        "pending"
+    };
+
+    WebhookDeliveryResponse {
+        id: delivery.id,
+        event_type: delivery.event_type,
+        payload: serde_json::from_str(&delivery.payload).unwrap_or(serde_json::Value::Null),
+        status: status.to_string(),
+        attempts: delivery.attempts,
+        response_status: delivery.response_status,
+        last_error: delivery.last_error,
+        created_at: delivery.created_at,
+        delivered_at: delivery.delivered_at,
+    }
+}
+
+/// Deliveries carry catalog data, they only go out over TLS. Plain http is let through for
+/// loopback hosts when private targets are allowed, so a local stand-in can receive them
+pub fn validate_url(url: &str) -> Result<String, ApiError> {
+    let invalid = || ApiError::BadRequest("url must be an absolute https URL".to_string());
+
+    if url.len() > WEBHOOK_URL_MAX_LENGTH {
+        return Err(invalid());
+    }
+    let parsed = Url::parse(url.trim()).map_err(|_| invalid())?;
+    let loopback = matches!(parsed.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+
+    match parsed.scheme() {
+        "https" if parsed.host_str().is_some() => Ok(parsed.to_string()),
+        "http" if loopback && private_targets_allowed() => Ok(parsed.to_string()),
+        _ => Err(invalid()),
+    }
+}
+
+fn normalize_event_types(event_types: &[String]) -> Result<Vec<String>, ApiError> {
+    let mut normalized: Vec<String> = event_types
+        .iter()
+        .map(|event_type| event_type.trim().to_string())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+
+    if normalized.is_empty() {
+        return Err(ApiError::BadRequest(
+            "At least one event type is required".to_string(),
+        ));
+    }
+    if let Some(unknown) = normalized
+        .iter()
+        .find(|event_type| !EVENT_TYPES.contains(&event_type.as_str()))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown event type {}, expected one of {}",
+            unknown,
+            EVENT_TYPES.join(", ")
+        )));
+    }
+    Ok(normalized)
+}
+
+fn live_webhook(
+    conn: &mut PgConnection,
+    org_id: &str,
+    webhook_id: i64,
+) -> Result<Webhook, ApiError> {
+    webhook::table
+        .find(webhook_id)
+        .filter(webhook::organization_id.eq(org_id))
+        .filter(webhook::deleted_at.is_null())
+        .first::<Webhook>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::NotFound("Webhook not found".to_string()))
+}
+
+#[openapi()]
+#[post("/workspace/<org_id>/webhooks", data = "<create_request>")]
+pub async fn create_webhook(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    create_request: Json<CreateWebhookRequest>,
+    claims: Claims,
+    groups_owned: GroupOwnerships,
+) -> Result<status::Created<Json<RegisteredWebhookResponse>>, ApiError> {
+    let url = validate_url(&create_request.url)?;
+    check_target(&url).await.map_err(ApiError::BadRequest)?;
+    let event_types = normalize_event_types(&create_request.event_types)?;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        let secret = format!(
+            "whsec_{}{}",
+            Uuid::new_v4().simple(),
+            Uuid::new_v4().simple()
+        );
+
+        let created = diesel::insert_into(webhook::table)
+            .values(&WebhookInsertable {
+                organization_id: org.slug.clone(),
+                url,
+                secret: secret.clone(),
+                event_types_json: serde_json::to_string(&event_types).unwrap(),
+                created_by: Some(claims.sub),
+                created_at: Utc::now(),
+                deleted_at: None,
+            })
+            .get_result::<Webhook>(conn)?;
+
+        let registered = RegisteredWebhookResponse {
+            secret,
+            details: to_response(created),
+        };
+
+        Ok(status::Created::new("/webhooks").body(Json(registered)))
+    })
+}
+
+#[openapi()]
+#[get("/workspace/<org_id>/webhooks?<cursor>&<limit>")]
+pub async fn get_webhooks(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Json<Page<WebhookResponse>>, ApiError> {
+    let page_request = PageRequest::parse(cursor.as_deref(), limit)?;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        let live = || {
+            webhook::table
+                .filter(webhook::organization_id.eq(&org.slug))
+                .filter(webhook::deleted_at.is_null())
+        };
+
+        let mut query = live().into_boxed();
+        if let Some(after_id) = page_request.after_id {
+            query = query.filter(webhook::id.gt(after_id));
+        }
+
+        let hooks = query
+            .order(webhook::id.asc())
+            .limit(page_request.fetch_limit())
+            .load::<Webhook>(conn)?;
+        let total = live().count().get_result::<i64>(conn)?;
+
+        Ok(Json(
+            page_request
+                .page(hooks, total, |hook| (None, hook.id))
+                .map(to_response),
+        ))
+    })
+}
+
+/// Pending deliveries of the webhook are no longer sent, its delivery log is kept
+#[openapi()]
+#[delete("/workspace/<org_id>/webhooks/<webhook_id>")]
+pub async fn delete_webhook(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    webhook_id: i64,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+
+        let updated_rows = diesel::update(
+            webhook::table
+                .find(webhook_id)
+                .filter(webhook::organization_id.eq(&org.slug))
+                .filter(webhook::deleted_at.is_null()),
+        )
+        .set(webhook::deleted_at.eq(Some(Utc::now())))
+        .execute(conn)?;
+
+        if updated_rows == 0 {
+            return Err(ApiError::NotFound("Webhook not found".to_string()));
+        }
+
+        Ok(Json(MessageResponse {
+            message: "Webhook deleted".to_string(),
+        }))
+    })
+}
+
+#[openapi()]
+#[get("/workspace/<org_id>/webhooks/<webhook_id>/deliveries?<cursor>&<limit>")]
+pub async fn get_webhook_deliveries(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    webhook_id: i64,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cursor: Option<String>,
+    limit: Option<i64>,
+) -> Result<Json<Page<WebhookDeliveryResponse>>, ApiError> {
+    let page_request = PageRequest::parse(cursor.as_deref(), limit)?;
+
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        let hook = live_webhook(conn, &org.slug, webhook_id)?;
+
+        // Newest first, ids grow with creation time
+        let mut query = webhook_delivery::table
+            .filter(webhook_delivery::webhook_id.eq(hook.id))
+            .into_boxed();
+        if let Some(after_id) = page_request.after_id {
+            query = query.filter(webhook_delivery::id.lt(after_id));
+        }
+
+        let deliveries = query
+            .order(webhook_delivery::id.desc())
+            .limit(page_request.fetch_limit())
+            .load::<Webhook_Delivery>(conn)?;
+        let total = webhook_delivery::table
+            .filter(webhook_delivery::webhook_id.eq(hook.id))
+            .count()
+            .get_result::<i64>(conn)?;
+
+        Ok(Json(
+            page_request
+                .page(deliveries, total, |delivery| (None, delivery.id))
+                .map(to_delivery_response),
+        ))
+    })
+}
+
+#[openapi()]
+#[post("/workspace/<org_id>/webhooks/<webhook_id>/deliveries/<delivery_id>/redeliver")]
+pub async fn redeliver_webhook_delivery(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    webhook_id: i64,
+    delivery_id: i64,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+) -> Result<Json<MessageResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        let hook = live_webhook(conn, &org.slug, webhook_id)?;
+
+        if webhooks::redeliver(conn, &org.slug, hook.id, delivery_id)? == 0 {
+            return Err(ApiError::NotFound("Delivery not found".to_string()));
+        }
+
+        Ok(Json(MessageResponse {
+            message: "Delivery queued".to_string(),
+        }))
+    })
+}
@@ -0,0 +1,255 @@
+use crate::db::outbox;
+use crate::db::pool::run_blocking;
+use crate::db::redis::{invalidate, RedisPoolState};
+use crate::errors::ApiError;
+use crate::fairings::slug_alias::forget_aliases;
+use crate::middlewares::groups_owned::GroupOwnerships;
+use crate::models::request::{
+    ChangeWorkspaceSlugRequest, RenameWorkspaceRequest, TransferWorkspaceRequest,
+};
+use crate::models::response::WorkspaceIdentityResponse;
+use crate::models::schema::schema::{
+    api_token, dbschema, organization, organization_slug_alias, outbox_event, package, service,
+    snapshots, webhook, webhook_delivery,
+};
+use crate::models::schema::{Organization, Organization_Slug_AliasInsertable};
+use crate::routes::metadata::to_slug;
+use crate::routes::{ensure_active, owned_organization, slug_taken};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use ginger_shared_rs::rocket_utils::Claims;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+
+const WORKSPACE_NAME_MAX_LENGTH: usize = 100;
+
+fn identity(
+    conn: &mut PgConnection,
+    org: Organization,
+) -> Result<Json<WorkspaceIdentityResponse>, ApiError> {
+    let previous_slugs = organization_slug_alias::table
+        .filter(organization_slug_alias::organization_id.eq(org.id))
+        .order(organization_slug_alias::created_at.desc())
+        .select(organization_slug_alias::old_slug)
+        .load::<String>(conn)?;
+
+    Ok(Json(WorkspaceIdentityResponse {
+        slug: org.slug,
+        name: org.name,
+        group_id: org.group_id,
+        previous_slugs,
+    }))
+}
+
+#[openapi()]
+#[put("/manage-workspace/<org_id>/name", data = "<rename_request>")]
+pub async fn rename_workspace(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    rename_request: Json<RenameWorkspaceRequest>,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<WorkspaceIdentityResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        let new_name = rename_request.name.trim();
+        if new_name.is_empty() || new_name.len() > WORKSPACE_NAME_MAX_LENGTH {
+            return Err(ApiError::BadRequest(format!(
+                "Workspace name must be between 1 and {} characters",
+                WORKSPACE_NAME_MAX_LENGTH
+            )));
+        }
+
+        // Renaming leaves the slug alone, links shared with the old name keep working
+        let renamed = conn.transaction::<_, ApiError, _>(|conn| {
+            let renamed = diesel::update(organization::table.find(org.id))
+                .set(organization::name.eq(Some(new_name)))
+                .get_result::<Organization>(conn)?;
+            outbox::enqueue_workspace(conn, &renamed.slug, "renamed")?;
+            Ok(renamed)
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &renamed.slug);
+
+        identity(conn, renamed)
+    })
+}
+
+#[openapi()]
+#[put("/manage-workspace/<org_id>/slug", data = "<slug_request>")]
+pub async fn change_workspace_slug(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    slug_request: Json<ChangeWorkspaceSlugRequest>,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<WorkspaceIdentityResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        let new_slug = to_slug(&slug_request.slug);
+        if new_slug.is_empty() {
+            return Err(ApiError::BadRequest(
+                "Workspace ID must contain letters or digits".to_string(),
+            ));
+        }
+        if new_slug == org.slug {
+            return identity(conn, org);
+        }
+
+        let old_slug = org.slug.clone();
+
+        let updated = conn.transaction::<_, ApiError, _>(|conn| {
+            // Taking back one of its own previous slugs just drops the alias
+            diesel::delete(
+                organization_slug_alias::table
+                    .filter(organization_slug_alias::organization_id.eq(org.id))
+                    .filter(organization_slug_alias::old_slug.eq(&new_slug)),
+            )
+            .execute(conn)?;
+
+            if slug_taken(conn, &new_slug)? {
+                return Err(ApiError::Conflict(
+                    "Workspace ID is already taken".to_string(),
+                ));
+            }
+
+            diesel::insert_into(organization_slug_alias::table)
+                .values(&Organization_Slug_AliasInsertable {
+                    old_slug: old_slug.clone(),
+                    organization_id: org.id,
+                    created_at: Utc::now(),
+                })
+                .execute(conn)?;
+
+            // Children reference the workspace by slug
+            diesel::update(dbschema::table.filter(dbschema::organization_id.eq(&old_slug)))
+                .set(dbschema::organization_id.eq(&new_slug))
+                .execute(conn)?;
+            diesel::update(service::table.filter(service::organization_id.eq(&old_slug)))
+                .set(service::organization_id.eq(&new_slug))
+                .execute(conn)?;
+            diesel::update(package::table.filter(package::organization_id.eq(&old_slug)))
+                .set(package::organization_id.eq(&new_slug))
+                .execute(conn)?;
+            diesel::update(snapshots::table.filter(snapshots::organization_id.eq(&old_slug)))
+                .set(snapshots::organization_id.eq(&new_slug))
+                .execute(conn)?;
+            diesel::update(api_token::table.filter(api_token::organization_id.eq(&old_slug)))
+                .set(api_token::organization_id.eq(&new_slug))
+                .execute(conn)?;
+            diesel::update(outbox_event::table.filter(outbox_event::organization_id.eq(&old_slug)))
+                .set(outbox_event::organization_id.eq(&new_slug))
+                .execute(conn)?;
+            diesel::update(webhook::table.filter(webhook::organization_id.eq(&old_slug)))
+                .set(webhook::organization_id.eq(&new_slug))
+                .execute(conn)?;
+            diesel::update(
+                webhook_delivery::table.filter(webhook_delivery::organization_id.eq(&old_slug)),
+            )
+            .set(webhook_delivery::organization_id.eq(&new_slug))
+            .execute(conn)?;
+
+            let updated = diesel::update(organization::table.find(org.id))
+                .set(organization::slug.eq(&new_slug))
+                .get_result::<Organization>(conn)?;
+            outbox::enqueue_workspace(conn, &new_slug, "slug_changed")?;
+            Ok(updated)
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &old_slug);
+        invalidate(cache.map(|c| c.inner()), &new_slug);
+        forget_aliases();
+
+        identity(conn, updated)
+    })
+}
+
+#[openapi()]
+#[put("/manage-workspace/<org_id>/owner", data = "<transfer_request>")]
+pub async fn transfer_workspace(
+    rdb: &State<Pool<ConnectionManager<PgConnection>>>,
+    org_id: String,
+    transfer_request: Json<TransferWorkspaceRequest>,
+    _claims: Claims,
+    groups_owned: GroupOwnerships,
+    cache: Option<&State<RedisPoolState>>,
+) -> Result<Json<WorkspaceIdentityResponse>, ApiError> {
+    run_blocking(rdb, |conn| {
+        let org = owned_organization(conn, &org_id, &groups_owned.0)?;
+        ensure_active(conn, &org.slug)?;
+
+        let new_group = transfer_request.group_id.trim();
+        if new_group == org.group_id {
+            return identity(conn, org);
+        }
+        if !groups_owned.0.iter().any(|owned| owned == new_group) {
+            return Err(ApiError::Forbidden(
+                "Permission Denied: You can only transfer a workspace to a group you own."
+                    .to_string(),
+            ));
+        }
+        let taken = diesel::select(diesel::dsl::exists(
+            organization::table.filter(organization::group_id.eq(new_group)),
+        ))
+        .get_result::<bool>(conn)?;
+        if taken {
+            return Err(ApiError::Conflict(
+                "The group already owns another workspace".to_string(),
+            ));
+        }
+
+        let old_group = org.group_id.clone();
+
+        let transferred = conn.transaction::<_, ApiError, _>(|conn| {
+            // Items created through the old group's memberships move along with the workspace
+            diesel::update(
+                dbschema::table
+                    .filter(dbschema::organization_id.eq(&org.slug))
+                    .filter(dbschema::group_id.eq(&old_group)),
+            )
+            .set(dbschema::group_id.eq(new_group))
+            .execute(conn)?;
+            diesel::update(
+                service::table
+                    .filter(service::organization_id.eq(&org.slug))
+                    .filter(service::group_id.eq(&old_group)),
+            )
+            .set(service::group_id.eq(new_group))
+            .execute(conn)?;
+            diesel::update(
+                package::table
+                    .filter(package::organization_id.eq(&org.slug))
+                    .filter(package::group_id.eq(&old_group)),
+            )
+            .set(package::group_id.eq(new_group))
+            .execute(conn)?;
+
+            // API tokens authenticate as the owning group, those issued for the old one are revoked
+            diesel::update(
+                api_token::table
+                    .filter(api_token::organization_id.eq(&org.slug))
+                    .filter(api_token::revoked_at.is_null()),
+            )
+            .set(api_token::revoked_at.eq(Some(Utc::now())))
+            .execute(conn)?;
+
+            let transferred = diesel::update(organization::table.find(org.id))
+                .set(organization::group_id.eq(new_group))
+                .get_result::<Organization>(conn)?;
+            outbox::enqueue_workspace(conn, &transferred.slug, "transferred")?;
+            Ok(transferred)
+        })?;
+
+        invalidate(cache.map(|c| c.inner()), &transferred.slug);
+
+        identity(conn, transferred)
+    })
+}
@@ -0,0 +1,156 @@
+use opentelemetry::global;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{config, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use rocket::tokio::task_local;
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::time::Duration;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+const SERVICE_NAME: &str = "metadata-service";
+
+// Longer strings are replaced by their length when a payload is logged
+const MAX_LOGGED_STRING: usize = 256;
+
+const SECRET_MARKERS: [&str; 5] = ["token", "secret", "password", "authorization", "api_key"];
+
+task_local! {
+    static DB_TIME: Cell<Duration>;
+}
+
+/// Installs the global subscriber. Logs are JSON lines filtered with `RUST_LOG` (`LOG_FORMAT=pretty`
+/// for local development), spans are exported over OTLP when `OTEL_EXPORTER_OTLP_ENDPOINT` is set
+pub fn init() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    // The provider is installed even without an exporter so trace ids exist and get propagated
+    let mut provider =
+        TracerProvider::builder().with_config(config().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", SERVICE_NAME),
+        ])));
+    let mut exporter_error = None;
+    if let Ok(endpoint) = env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        match opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(endpoint)
+            .build_span_exporter()
+        {
+            Ok(exporter) => provider = provider.with_batch_exporter(exporter, runtime::Tokio),
+            Err(error) => exporter_error = Some(error),
+        }
+    }
+    let provider = provider.build();
+    let tracer = provider.tracer(SERVICE_NAME);
+    global::set_tracer_provider(provider);
+
+    let registry = tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(tracing_opentelemetry::layer().with_tracer(tracer));
+    // Fails when a subscriber is already set, e.g. by an earlier test
+    let installed = if env::var("LOG_FORMAT").as_deref() == Ok("pretty") {
+        registry.with(fmt::layer()).try_init()
+    } else {
+        registry.with(fmt::layer().json()).try_init()
+    };
+
+    if let (Ok(()), Some(error)) = (installed, exporter_error) {
+        tracing::warn!(%error, "OTLP exporter disabled");
+    }
+}
+
+/// Flushes the spans still buffered by the batch exporter
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// W3C `traceparent` (and `tracestate`) of the current span, as outgoing headers
+pub fn trace_headers() -> HashMap<String, String> {
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&Span::current().context(), &mut carrier)
+    });
+    carrier
+}
+
+/// HTTP client for the generated IAM and Notification clients, every call it makes carries the
+/// trace context of the request it was created in
+pub fn propagating_client() -> reqwest::Client {
+    let mut headers = HeaderMap::new();
+    for (name, value) in trace_headers() {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Runs `work` while summing the time spent in the database, see `add_db_time`
+pub async fn with_db_timer<T>(work: impl Future<Output = T>) -> (T, Duration) {
+    DB_TIME
+        .scope(Cell::new(Duration::ZERO), async {
+            let output = work.await;
+            (output, DB_TIME.with(Cell::get))
+        })
+        .await
+}
+
+/// Adds to the DB time of the current request, a no-op outside of one
+pub fn add_db_time(elapsed: Duration) {
+    let _ = DB_TIME.try_with(|total| total.set(total.get() + elapsed));
+}
+
+fn is_secret(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+fn redact_value(value: Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = if is_secret(&key) {
+                        Value::String("[redacted]".to_string())
+                    } else {
+                        redact_value(value)
+                    };
+                    (key, value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact_value).collect()),
+        Value::String(text) if text.len() > MAX_LOGGED_STRING => {
+            Value::String(format!("[{} bytes]", text.len()))
+        }
+        value => value,
+    }
+}
+
+/// Payload as it may be logged, with secrets masked and large strings (specs, diagrams) elided
+pub fn redacted(payload: &impl Serialize) -> String {
+    serde_json::to_value(payload)
+        .map(redact_value)
+        .map(|value| value.to_string())
+        .unwrap_or_else(|_| "[unserializable]".to_string())
+}
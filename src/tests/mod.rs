@@ -17,3 +17,1129 @@ fn hello_world() {
         .unwrap()
     );
 }
+
+#[test]
+fn slug_keeps_digits() {
+    use crate::routes::metadata::to_slug;
+
+    assert_eq!(to_slug("Team 42"), "team-42");
+    assert_ne!(to_slug("Team 42"), to_slug("Team"));
+    assert_eq!(to_slug("  Release -- 2.0  "), "release-2-0");
+}
+
+#[test]
+fn slug_transliterates_unicode() {
+    use crate::routes::metadata::to_slug;
+
+    assert_eq!(to_slug("Équipe Ünïcode"), "equipe-unicode");
+    assert_eq!(to_slug("Straße"), "strasse");
+    assert_eq!(to_slug("!!!"), "");
+}
+
+#[test]
+fn page_limit_is_validated() {
+    use crate::routes::pagination::PageRequest;
+
+    assert_eq!(PageRequest::parse(None, None).unwrap().limit, 20);
+    assert!(PageRequest::parse(None, Some(0)).is_err());
+    assert!(PageRequest::parse(None, Some(101)).is_err());
+    assert!(PageRequest::parse(Some("not-a-cursor"), Some(10)).is_err());
+}
+
+#[test]
+fn page_cursor_continues_after_last_item() {
+    use crate::routes::pagination::PageRequest;
+
+    let first = PageRequest::parse(None, Some(2)).unwrap();
+    let page = first.page(vec![1i64, 2, 3], 3, |item| (None, *item));
+    assert_eq!(page.items, vec![1, 2]);
+    assert_eq!(page.total, 3);
+
+    let next = PageRequest::parse(page.next_cursor.as_deref(), Some(2)).unwrap();
+    assert_eq!(next.after_id, Some(2));
+
+    let last = next.page(vec![3i64], 3, |item| (None, *item));
+    assert_eq!(last.next_cursor, None);
+}
+
+#[test]
+fn sort_accepts_known_columns() {
+    use crate::routes::pagination::{Sort, SortKey};
+
+    assert_eq!(Sort::parse(None).unwrap().key, SortKey::Id);
+    let sort = Sort::parse(Some("-updated_at")).unwrap();
+    assert_eq!(sort.key, SortKey::UpdatedAt);
+    assert!(sort.descending);
+    assert!(Sort::parse(Some("spec")).is_err());
+}
+
+#[test]
+fn projection_keeps_requested_fields() {
+    use crate::models::response::{Page, SnapshotsResponse};
+    use crate::routes::projection::project;
+
+    let page = || Page {
+        items: vec![SnapshotsResponse {
+            version: "1.2.0".to_string(),
+            created_at: chrono::Utc::now(),
+        }],
+        next_cursor: None,
+        total: 1,
+    };
+
+    let projected = serde_json::to_value(project(page(), Some("version")).unwrap()).unwrap();
+    assert_eq!(
+        projected["items"][0],
+        serde_json::json!({"version": "1.2.0"})
+    );
+    assert!(project(page(), Some("version,spec")).is_err());
+    assert!(project(page(), Some(" , ")).is_err());
+}
+
+#[test]
+fn services_are_assembled_with_their_envs() {
+    use crate::models::schema::{Service, Service_Envs};
+    use crate::routes::metadata::assemble_services;
+    use chrono::Utc;
+
+    const SERVICES: i64 = 5000;
+    let now = Utc::now();
+
+    let services: Vec<Service> = (1..=SERVICES)
+        .map(|id| Service {
+            identifier: format!("service-{}", id),
+            group_id: None,
+            db_schema_id: None,
+            tables_json: Some("[\"users\"]".to_string()),
+            dependencies_json: None,
+            service_type: "rpc-endpoint".to_string(),
+            lang: Some("rust".to_string()),
+            description: None,
+            organization_id: Some("acme".to_string()),
+            repo_origin: None,
+            cache_schema_id: None,
+            quick_links: None,
+            message_queue_schema_id: None,
+            updated_at: now,
+            deleted_at: None,
+            id,
+        })
+        .collect();
+    // Loaded in id order, not grouped by service
+    let envs: Vec<Service_Envs> = ["dev", "stage", "prod"]
+        .iter()
+        .flat_map(|env| (1..=SERVICES).map(move |parent_id| (env, parent_id)))
+        .enumerate()
+        .map(|(index, (env, parent_id))| Service_Envs {
+            parent_id,
+            spec: String::new(),
+            env: env.to_string(),
+            base_url: format!("https://{}.service-{}.internal", env, parent_id),
+            updated_at: Some(now),
+            version: "1.0.0".to_string(),
+            pipeline_status: None,
+            base_url_ws: None,
+            pipeline_changed_at: None,
+            last_passed_at: None,
+            deleted_at: None,
+            id: index as i64 + 1,
+        })
+        .collect();
+
+    let assembled = assemble_services(services, envs).unwrap();
+
+    assert_eq!(assembled.len(), SERVICES as usize);
+    let last = assembled.last().unwrap();
+    assert_eq!(last.identifier, "service-5000");
+    let keys: Vec<&str> = last.envs.iter().map(|e| e.env_key.as_str()).collect();
+    assert_eq!(keys, vec!["dev", "stage", "prod"]);
+    assert_eq!(last.envs[2].base_url, "https://prod.service-5000.internal");
+    assert_eq!(last.tables, vec!["users"]);
+}
+
+// Needs DATABASE_URL, run with `cargo test -- --ignored services_listing_benchmark --nocapture`
+#[test]
+#[ignore]
+fn services_listing_benchmark() {
+    use crate::models::request::ServiceListQuery;
+    use crate::models::schema::schema::service::dsl::*;
+    use crate::models::schema::schema::service_envs;
+    use crate::models::schema::{ServiceInsertable, Service_EnvsInsertable};
+    use crate::routes::metadata::load_services_page;
+    use crate::routes::pagination::{PageRequest, Sort};
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use diesel::sql_types::BigInt;
+    use std::time::Instant;
+
+    const SERVICES: usize = 3000;
+
+    #[derive(QueryableByName)]
+    struct Scans {
+        #[diesel(sql_type = BigInt)]
+        scans: i64,
+    }
+
+    // Scans of `service_envs` so far in this transaction, one per query reading it
+    fn env_scans(conn: &mut PgConnection) -> i64 {
+        diesel::sql_query(
+            "SELECT COALESCE(seq_scan, 0) + COALESCE(idx_scan, 0) AS scans \
+             FROM pg_stat_xact_user_tables WHERE relname = 'service_envs'",
+        )
+        .get_result::<Scans>(conn)
+        .unwrap()
+        .scans
+    }
+
+    let mut conn = PgConnection::establish(&std::env::var("DATABASE_URL").unwrap()).unwrap();
+    conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+        let org = format!("bench-{}", uuid::Uuid::new_v4());
+        let now = Utc::now();
+        let services: Vec<ServiceInsertable> = (0..SERVICES)
+            .map(|index| ServiceInsertable {
+                identifier: format!("{}-service-{}", org, index),
+                group_id: None,
+                db_schema_id: None,
+                tables_json: Some("[\"users\"]".to_string()),
+                dependencies_json: None,
+                service_type: "rpc-endpoint".to_string(),
+                lang: Some("rust".to_string()),
+                description: None,
+                organization_id: Some(org.clone()),
+                repo_origin: None,
+                cache_schema_id: None,
+                quick_links: None,
+                message_queue_schema_id: None,
+                updated_at: now,
+            })
+            .collect();
+        let ids: Vec<i64> = diesel::insert_into(service)
+            .values(&services)
+            .returning(id)
+            .get_results(conn)?;
+        let envs: Vec<Service_EnvsInsertable> = ids
+            .iter()
+            .flat_map(|parent| {
+                ["dev", "stage", "prod"].map(|env| Service_EnvsInsertable {
+                    parent_id: *parent,
+                    spec: String::new(),
+                    env: env.to_string(),
+                    base_url: format!("https://{}.service-{}.internal", env, parent),
+                    updated_at: Some(now),
+                    version: "1.0.0".to_string(),
+                    pipeline_status: None,
+                    base_url_ws: None,
+                    pipeline_changed_at: None,
+                    last_passed_at: None,
+                })
+            })
+            .collect();
+        for chunk in envs.chunks(2000) {
+            diesel::insert_into(service_envs::table)
+                .values(chunk)
+                .execute(conn)?;
+        }
+
+        let filters = ServiceListQuery {
+            service_type: None,
+            lang: None,
+            env: None,
+            pipeline_status: None,
+            db_schema_id: None,
+            dependency: None,
+            sort: None,
+            fields: None,
+        };
+        let sort = Sort::parse(None).unwrap();
+
+        // A single service and a full page cost the same queries
+        let before = env_scans(conn);
+        let page_request = PageRequest::parse(None, Some(1)).unwrap();
+        load_services_page(conn, &org, &page_request, sort, &filters).unwrap();
+        let single = env_scans(conn) - before;
+
+        let started = Instant::now();
+        let mut cursor = None;
+        let mut listed = 0;
+        loop {
+            let before = env_scans(conn);
+            let page_request = PageRequest::parse(cursor.as_deref(), Some(100)).unwrap();
+            let page = load_services_page(conn, &org, &page_request, sort, &filters).unwrap();
+            assert_eq!(env_scans(conn) - before, single);
+            assert!(page.items.iter().all(|s| s.envs.len() == 3));
+            listed += page.items.len();
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(listed, SERVICES);
+        println!(
+            "Listed {} services with their envs in {:?}",
+            SERVICES,
+            started.elapsed()
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn dbschema_without_env_branch_has_no_version() {
+    use crate::models::schema::Dbschema;
+    use crate::routes::metadata::assemble_dbschema;
+    use chrono::Utc;
+
+    let db_schema = Dbschema {
+        name: "Accounts".to_string(),
+        description: None,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        data: None,
+        group_id: None,
+        identifier: Some("accounts".to_string()),
+        organization_id: Some("acme".to_string()),
+        repo_origin: None,
+        db_type: "rdbms".to_string(),
+        quick_links: None,
+        deleted_at: None,
+        id: 1,
+    };
+
+    let response = assemble_dbschema(
+        db_schema,
+        None,
+        Some("[{\"data\": {\"name\": \"users\"}}]".to_string()),
+    );
+    assert_eq!(response.version, None);
+    assert_eq!(response.pipeline_status, None);
+    assert_eq!(response.tables, vec!["users"]);
+}
+
+#[test]
+fn readiness_degrades_on_optional_dependencies() {
+    use crate::models::response::DependencyStatusResponse;
+    use crate::routes::health::readiness;
+
+    let dependency = |name: &str, status: &str, required: bool| DependencyStatusResponse {
+        name: name.to_string(),
+        status: status.to_string(),
+        required,
+        latency_ms: Some(1),
+        error: None,
+    };
+
+    let healthy = vec![
+        dependency("postgres", "up", true),
+        dependency("redis", "not_configured", false),
+    ];
+    assert_eq!(readiness(&healthy), (Status::Ok, "ready"));
+
+    let degraded = vec![
+        dependency("postgres", "up", true),
+        dependency("mongo", "down", false),
+    ];
+    assert_eq!(readiness(&degraded), (Status::Ok, "degraded"));
+
+    let unavailable = vec![
+        dependency("postgres", "down", true),
+        dependency("mongo", "up", false),
+    ];
+    assert_eq!(
+        readiness(&unavailable),
+        (Status::ServiceUnavailable, "unavailable")
+    );
+}
+
+#[test]
+fn logged_payloads_are_redacted() {
+    use crate::telemetry::redacted;
+
+    let payload = serde_json::json!({
+        "identifier": "billing",
+        "api_token": "secret-value",
+        "spec": "x".repeat(1000),
+        "envs": [{"Authorization": "Bearer abc", "env": "prod"}],
+    });
+    let logged: serde_json::Value = serde_json::from_str(&redacted(&payload)).unwrap();
+
+    assert_eq!(logged["identifier"], "billing");
+    assert_eq!(logged["api_token"], "[redacted]");
+    assert_eq!(logged["spec"], "[1000 bytes]");
+    assert_eq!(logged["envs"][0]["Authorization"], "[redacted]");
+    assert_eq!(logged["envs"][0]["env"], "prod");
+}
+
+#[test]
+fn responses_carry_the_request_id() {
+    use crate::fairings::request_trace::REQUEST_ID_HEADER;
+    use rocket::http::Header;
+
+    let client = Client::tracked(rocket()).expect("valid rocket instance");
+
+    let response = client
+        .get("/")
+        .header(Header::new(REQUEST_ID_HEADER, "edge-1234"))
+        .dispatch();
+    assert_eq!(
+        response.headers().get_one(REQUEST_ID_HEADER),
+        Some("edge-1234")
+    );
+
+    let response = client.get("/").dispatch();
+    assert!(response.headers().get_one(REQUEST_ID_HEADER).is_some());
+}
+
+#[test]
+fn pipeline_timestamps_track_failures_and_passes() {
+    use crate::db::pipeline::PipelineTimestamps;
+    use chrono::{Duration, Utc};
+
+    let start = Utc::now();
+    let later = start + Duration::minutes(30);
+
+    let failing = PipelineTimestamps::default().after(Some("running"), "failed", start);
+    assert_eq!(failing.changed_at, Some(start));
+    assert_eq!(failing.last_passed_at, None);
+
+    // Repeated failures keep the time the pipeline started failing
+    let still_failing = failing.after(Some("failed"), "failed", later);
+    assert_eq!(still_failing.changed_at, Some(start));
+
+    let passing = still_failing.after(Some("failed"), "passing", later);
+    assert_eq!(passing.changed_at, Some(later));
+    assert_eq!(passing.last_passed_at, Some(later));
+}
+
+#[test]
+fn catalog_metrics_report_failing_pipelines() {
+    use crate::db::pipeline::{CatalogPipeline, PipelineTimestamps};
+    use crate::metrics::metrics;
+    use chrono::{Duration, Utc};
+
+    let now = Utc::now();
+    let pipeline = |identifier: &str, status: &str, changed_at| CatalogPipeline {
+        kind: "service",
+        org_id: "acme".to_string(),
+        identifier: identifier.to_string(),
+        env: "prod".to_string(),
+        status: Some(status.to_string()),
+        timestamps: PipelineTimestamps {
+            changed_at: Some(changed_at),
+            last_passed_at: None,
+        },
+    };
+    let metrics = metrics();
+    metrics.refresh(
+        &[
+            pipeline("billing", "failed", now - Duration::hours(2)),
+            pipeline("accounts", "passing", now),
+        ],
+        now,
+    );
+
+    assert_eq!(
+        metrics
+            .entities
+            .with_label_values(&["service", "acme", "prod"])
+            .get(),
+        2
+    );
+    assert_eq!(
+        metrics
+            .pipelines
+            .with_label_values(&["service", "prod", "failed"])
+            .get(),
+        1
+    );
+    assert_eq!(
+        metrics
+            .failing_seconds
+            .with_label_values(&["service", "acme", "billing", "prod"])
+            .get(),
+        7200.0
+    );
+
+    // Once fixed it no longer counts as failed
+    metrics.refresh(&[pipeline("billing", "passing", now)], now);
+    assert_eq!(
+        metrics
+            .pipelines
+            .with_label_values(&["service", "prod", "failed"])
+            .get(),
+        0
+    );
+}
+
+#[test]
+fn outbox_retries_back_off_up_to_an_hour() {
+    use crate::db::outbox::retry_delay;
+    use chrono::Duration;
+
+    assert_eq!(retry_delay(1), Duration::seconds(5));
+    assert_eq!(retry_delay(2), Duration::seconds(10));
+    assert_eq!(retry_delay(4), Duration::seconds(40));
+    assert_eq!(retry_delay(30), Duration::hours(1));
+}
+
+#[test]
+fn failed_attempts_give_up_at_the_limit() {
+    use crate::db::outbox::FailedAttempt;
+    use chrono::{Duration, Utc};
+
+    let now = Utc::now();
+    let retried = FailedAttempt::new(0, 8, &"x".repeat(5000), now);
+    assert_eq!(retried.attempts, 1);
+    assert_eq!(retried.error.len(), 2000);
+    assert_eq!(retried.next_attempt_at, now + Duration::seconds(5));
+    assert_eq!(retried.given_up_at, None);
+
+    let given_up = FailedAttempt::new(7, 8, "HTTP 500", now);
+    assert_eq!(given_up.attempts, 8);
+    assert_eq!(given_up.given_up_at, Some(now));
+}
+
+#[test]
+fn webhook_urls_must_use_https() {
+    use crate::routes::webhooks::validate_url;
+
+    assert!(validate_url("https://chat.example.com/hooks/42").is_ok());
+    assert!(validate_url("http://localhost:9000/hook").is_ok());
+    assert!(validate_url("http://127.0.0.1:9000/hook").is_ok());
+    assert!(validate_url("http://chat.example.com/hooks/42").is_err());
+    assert!(validate_url("ftp://chat.example.com/hooks").is_err());
+    assert!(validate_url("not a url").is_err());
+}
+
+#[test]
+fn webhooks_cannot_target_internal_addresses() {
+    use crate::fairings::webhooks::{is_public_address, resolve_target};
+    use std::net::IpAddr;
+
+    let public = |ip: &str| is_public_address(ip.parse::<IpAddr>().unwrap());
+    assert!(public("93.184.216.34"));
+    assert!(public("2606:2800:220:1:248:1893:25c8:1946"));
+    for internal in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "::1",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:169.254.169.254",
+    ] {
+        assert!(!public(internal), "{} is internal", internal);
+    }
+
+    let runtime = rocket::tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    assert!(runtime
+        .block_on(resolve_target("169.254.169.254", 443, false))
+        .is_err());
+    assert!(runtime.block_on(resolve_target("::1", 443, false)).is_err());
+    assert!(runtime
+        .block_on(resolve_target("127.0.0.1", 9000, true))
+        .is_ok());
+}
+
+// Accepts a single request on a local port and answers it with `status`
+fn webhook_stand_in(status: &'static str) -> (String, std::sync::mpsc::Receiver<String>) {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (sender, received) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            let read = stream.read(&mut buffer).unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().ok())?
+                    })
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    break;
+                }
+            }
+        }
+        let response = format!(
+            "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            status
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+        sender
+            .send(String::from_utf8_lossy(&request).to_string())
+            .unwrap();
+    });
+
+    (url, received)
+}
+
+type WebhookFixture = (
+    crate::models::schema::Webhook,
+    crate::models::schema::Webhook_Delivery,
+);
+
+fn webhook_fixture(url: String) -> WebhookFixture {
+    use crate::models::schema::{Webhook, Webhook_Delivery};
+    use chrono::Utc;
+
+    let hook = Webhook {
+        organization_id: "acme".to_string(),
+        url,
+        secret: "whsec_test".to_string(),
+        event_types_json: r#"["pipeline.failed"]"#.to_string(),
+        created_by: None,
+        created_at: Utc::now(),
+        deleted_at: None,
+        id: 1,
+    };
+    let delivery = Webhook_Delivery {
+        webhook_id: 1,
+        organization_id: "acme".to_string(),
+        event_type: "pipeline.failed".to_string(),
+        payload: r#"{"event":"pipeline.failed","data":{"identifier":"billing"}}"#.to_string(),
+        attempts: 0,
+        next_attempt_at: Utc::now(),
+        response_status: None,
+        last_error: None,
+        created_at: Utc::now(),
+        delivered_at: None,
+        failed_at: None,
+        id: 7,
+    };
+    (hook, delivery)
+}
+
+#[test]
+fn webhook_deliveries_are_signed() {
+    use crate::fairings::webhooks::{deliver, signature};
+
+    let (url, received) = webhook_stand_in("204 No Content");
+    let (hook, delivery) = webhook_fixture(url);
+
+    let runtime = rocket::tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let outcome = runtime.block_on(deliver(&reqwest::Client::new(), &hook, &delivery));
+    assert_eq!(outcome, Ok(204));
+
+    let request = received.recv().unwrap();
+    let (head, body) = request.split_once("\r\n\r\n").unwrap();
+    let head = head.to_lowercase();
+    assert_eq!(body, delivery.payload);
+    assert!(head.contains("x-webhook-event: pipeline.failed"));
+    assert!(head.contains("x-webhook-delivery: 7"));
+
+    let signed = head
+        .lines()
+        .find_map(|line| line.strip_prefix("x-webhook-signature: "))
+        .expect("signature header");
+    let timestamp: i64 = signed
+        .strip_prefix("t=")
+        .and_then(|rest| rest.split(',').next())
+        .and_then(|t| t.parse().ok())
+        .unwrap();
+    assert_eq!(signed, signature("whsec_test", timestamp, body));
+    assert_ne!(signed, signature("another secret", timestamp, body));
+}
+
+#[test]
+fn failed_webhook_deliveries_keep_the_status() {
+    use crate::fairings::webhooks::deliver;
+
+    let (url, _received) = webhook_stand_in("503 Service Unavailable");
+    let (hook, delivery) = webhook_fixture(url);
+
+    let runtime = rocket::tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let outcome = runtime.block_on(deliver(&reqwest::Client::new(), &hook, &delivery));
+    assert!(matches!(outcome, Err((Some(503), _))));
+}
+
+#[test]
+fn repo_manifests_are_ingestible() {
+    use crate::models::manifest::{MetadataManifest, ReleaserManifest, ServicesManifest};
+
+    let metadata: MetadataManifest = toml::from_str(include_str!("../../metadata.toml")).unwrap();
+    let services: ServicesManifest = toml::from_str(include_str!("../../services.toml")).unwrap();
+    let releaser: ReleaserManifest = toml::from_str(include_str!("../../releaser.toml")).unwrap();
+
+    assert_eq!(metadata.links.len(), 3);
+    assert_eq!(releaser.version.to_string(), "0.6.0-nightly.0");
+    assert_eq!(
+        services.dependencies(),
+        vec![
+            "@ginger-society/IAMService",
+            "@ginger-society/NotificationService",
+            "@ginger-society/iam-frontend-users",
+        ]
+    );
+    assert_eq!(
+        services.urls.get("prod").map(String::as_str),
+        Some("https://api.gingersociety.org/metadata")
+    );
+}
+
+#[test]
+fn services_toml_is_rendered_from_dependencies() {
+    use crate::models::manifest::ServicesManifest;
+    use crate::models::schema::{Service, Service_Envs};
+    use crate::routes::manifests::render_services_manifest;
+    use chrono::Utc;
+
+    let now = Utc::now();
+    let service = |id: i64, identifier: &str, service_type: &str| Service {
+        identifier: identifier.to_string(),
+        group_id: None,
+        db_schema_id: None,
+        tables_json: None,
+        dependencies_json: None,
+        service_type: service_type.to_string(),
+        lang: Some("Rust".to_string()),
+        description: None,
+        organization_id: Some("acme".to_string()),
+        repo_origin: None,
+        cache_schema_id: None,
+        quick_links: None,
+        message_queue_schema_id: None,
+        updated_at: now,
+        deleted_at: None,
+        id,
+    };
+    let service_env =
+        |parent_id: i64, env: &str, base_url: &str, base_url_ws: Option<&str>| Service_Envs {
+            parent_id,
+            spec: String::new(),
+            env: env.to_string(),
+            base_url: base_url.to_string(),
+            updated_at: Some(now),
+            version: "1.0.0".to_string(),
+            pipeline_status: None,
+            base_url_ws: base_url_ws.map(str::to_string),
+            pipeline_changed_at: None,
+            last_passed_at: None,
+            deleted_at: None,
+            id: 0,
+        };
+
+    let metadata = service(1, "@acme/metadata", "RPCEndpoint");
+    let envs = vec![
+        service_env(1, "dev", "http://localhost:8081/metadata", None),
+        service_env(1, "prod", "https://api.acme.dev/metadata", None),
+    ];
+    let dependencies = vec![
+        (
+            service(2, "@acme/notifications", "RPCEndpoint"),
+            vec![
+                service_env(
+                    2,
+                    "dev",
+                    "http://localhost:3030",
+                    Some("ws://localhost:3030/ws"),
+                ),
+                service_env(
+                    2,
+                    "prod",
+                    "https://api.acme.dev",
+                    Some("wss://api.acme.dev/ws"),
+                ),
+            ],
+        ),
+        (
+            service(3, "@acme/users-portal", "Portal"),
+            vec![service_env(3, "prod", "https://users.acme.dev", None)],
+        ),
+    ];
+
+    let manifest = render_services_manifest(&metadata, &envs, &dependencies, None);
+    assert_eq!(
+        manifest.services["@acme/notifications"]["prod"],
+        "https://api.acme.dev"
+    );
+    assert_eq!(
+        manifest.ws_refs["@acme/notifications"]["dev"],
+        "ws://localhost:3030/ws"
+    );
+    assert_eq!(
+        manifest.portals_refs["@acme/users-portal"]["prod"],
+        "https://users.acme.dev"
+    );
+    assert!(!manifest.services.contains_key("@acme/users-portal"));
+    assert!(manifest.urls_ws.is_empty());
+
+    // What is rendered reads back as the same dependencies
+    let rendered: ServicesManifest = toml::from_str(&toml::to_string(&manifest).unwrap()).unwrap();
+    assert_eq!(
+        rendered.dependencies(),
+        vec!["@acme/notifications", "@acme/users-portal"]
+    );
+    assert_eq!(rendered.urls["dev"], "http://localhost:8081/metadata");
+
+    let prod_only = render_services_manifest(&metadata, &envs, &dependencies, Some("prod"));
+    assert_eq!(prod_only.urls.len(), 1);
+    assert!(!prod_only.services["@acme/notifications"].contains_key("dev"));
+}
+
+#[test]
+fn quick_links_are_validated() {
+    use crate::models::quick_link::QuickLink;
+    use crate::routes::quick_links::validate_quick_links;
+
+    let link = |icon: &str, link: &str| QuickLink {
+        icon: icon.to_string(),
+        internal: false,
+        label: "Pipelines".to_string(),
+        link: link.to_string(),
+    };
+
+    assert!(validate_quick_links(&[link("FaGithub", "https://github.com/acme/repo")]).is_ok());
+    assert!(validate_quick_links(&[link("FaNotAnIcon", "https://github.com/acme/repo")]).is_err());
+    assert!(validate_quick_links(&[link("FaGithub", "github.com/acme/repo")]).is_err());
+    assert!(validate_quick_links(&[link("FaGithub", "javascript:alert(1)")]).is_err());
+    assert!(validate_quick_links(&vec![link("FaLink", "https://acme.dev"); 21]).is_err());
+}
+
+#[test]
+fn legacy_quick_links_are_migrated() {
+    use crate::models::quick_link::{normalize_stored, parse_quick_links};
+    use crate::models::request::UpdateDbschemaRequest;
+
+    let canonical =
+        r#"[{"icon":"FaGithub","internal":false,"label":"Repo","link":"https://github.com/acme"}]"#;
+    // Encoded twice by the client, without `internal` and with an entry that is not a link
+    let legacy = serde_json::to_string(
+        r#"[{"icon":"FaGithub","label":"Repo","link":"https://github.com/acme"},"oops"]"#,
+    )
+    .unwrap();
+
+    assert_eq!(normalize_stored(&legacy), Some(Some(canonical.to_string())));
+    assert_eq!(
+        normalize_stored(canonical),
+        Some(Some(canonical.to_string()))
+    );
+    assert_eq!(normalize_stored("[]"), Some(None));
+    assert_eq!(normalize_stored("not json"), None);
+    assert!(parse_quick_links(Some("not json")).is_empty());
+
+    // Requests may still send the links as a string
+    let request: UpdateDbschemaRequest = serde_json::from_value(serde_json::json!({
+        "name": "Accounts",
+        "organisation_id": "acme",
+        "repo_origin": "https://github.com/acme",
+        "version": "1.0.0",
+        "quick_links": canonical,
+    }))
+    .unwrap();
+    assert_eq!(request.quick_links.unwrap()[0].label, "Repo");
+}
+
+#[test]
+fn canvas_layout_is_typed_and_pruned() {
+    use crate::models::canvas::{parse_layout, stored_layout, CanvasGroup};
+    use crate::routes::canvas::validate_layout;
+    use std::collections::BTreeSet;
+
+    // Both shapes the portal used to store
+    let legacy = parse_layout(Some(
+        r#"{"iam":{"x":10,"y":20},"billing":{"x":-5.5,"y":0}}"#,
+    ));
+    assert_eq!(legacy.blocks["iam"].x, 10.0);
+    assert_eq!(legacy.blocks.len(), 2);
+    let nodes = parse_layout(Some(
+        r#"[{"id":"iam","position":{"x":1,"y":2}},{"id":"x"}]"#,
+    ));
+    assert_eq!(nodes.blocks.len(), 1);
+    assert!(parse_layout(Some("not json")).blocks.is_empty());
+    assert_eq!(stored_layout(&parse_layout(None)), None);
+
+    let mut layout = legacy.clone();
+    layout.groups.push(CanvasGroup {
+        id: "core".to_string(),
+        label: "Core".to_string(),
+        x: 0.0,
+        y: 0.0,
+        width: 400.0,
+        height: 300.0,
+        collapsed: false,
+        blocks: vec!["iam".to_string(), "billing".to_string()],
+    });
+    let stored = stored_layout(&layout).unwrap();
+    assert_eq!(parse_layout(Some(&stored)), layout);
+    assert!(validate_layout(&layout).is_ok());
+
+    layout.prune(&BTreeSet::from(["iam".to_string()]));
+    assert_eq!(layout.blocks.keys().collect::<Vec<_>>(), ["iam"]);
+    assert_eq!(layout.groups[0].blocks, ["iam"]);
+
+    let mut invalid = layout.clone();
+    invalid.blocks.get_mut("iam").unwrap().x = f64::NAN;
+    assert!(validate_layout(&invalid).is_err());
+    let mut invalid = layout.clone();
+    invalid.groups.push(invalid.groups[0].clone());
+    assert!(validate_layout(&invalid).is_err());
+    let mut invalid = layout;
+    invalid.groups[0].width = 0.0;
+    assert!(validate_layout(&invalid).is_err());
+}
+
+#[test]
+fn canvas_is_laid_out_in_dependency_layers() {
+    use crate::models::canvas::{BlockLayout, CanvasLayout};
+    use crate::routes::canvas::{auto_layout, CanvasGraph};
+
+    let edge = |from: &str, to: &str| (from.to_string(), to.to_string());
+    let graph = CanvasGraph {
+        blocks: [
+            "gateway",
+            "billing",
+            "loop-a",
+            "loop-b",
+            "billing-db",
+            "sdk",
+        ]
+        .map(String::from)
+        .to_vec(),
+        edges: vec![
+            edge("gateway", "billing"),
+            edge("gateway", "sdk"),
+            edge("billing", "billing-db"),
+            edge("billing-db", "sdk"),
+            edge("loop-a", "loop-b"),
+            edge("loop-b", "loop-a"),
+        ],
+    };
+
+    let layout = auto_layout(&graph, &CanvasLayout::default());
+    assert_eq!(layout.blocks.len(), graph.blocks.len());
+    let y = |id: &str| layout.blocks[id].y;
+    assert!(y("gateway") < y("billing"));
+    assert!(y("billing") < y("billing-db"));
+    assert!(y("billing-db") < y("sdk"));
+    assert_ne!(y("loop-a"), y("loop-b"));
+
+    let positions: Vec<_> = layout.blocks.values().map(|b| (b.x, b.y)).collect();
+    for (i, a) in positions.iter().enumerate() {
+        assert!(positions[i + 1..].iter().all(|b| b != a));
+    }
+
+    // A pinned block keeps its place and the others make room for it
+    let pinned_block = BlockLayout {
+        x: layout.blocks["gateway"].x,
+        y: layout.blocks["gateway"].y,
+        width: Some(2000.0),
+        height: Some(100.0),
+        collapsed: true,
+    };
+    let mut pinned = CanvasLayout::default();
+    pinned
+        .blocks
+        .insert("loop-a".to_string(), pinned_block.clone());
+    let layout = auto_layout(&graph, &pinned);
+    assert_eq!(layout.blocks["loop-a"], pinned_block);
+    assert!(layout
+        .blocks
+        .iter()
+        .filter(|(id, _)| id.as_str() != "loop-a")
+        .all(|(_, b)| b.x >= pinned_block.x + 2000.0
+            || b.x + 280.0 <= pinned_block.x
+            || b.y >= pinned_block.y + 100.0));
+}
+
+#[test]
+fn api_tokens_are_bound_to_their_workspace_and_scopes() {
+    use crate::errors::ApiError;
+    use crate::middlewares::api_token_claims::{
+        check_token_access, SCOPE_PIPELINE, SCOPE_READ, SCOPE_WRITE,
+    };
+    use crate::routes::api_tokens::normalize_scopes;
+
+    let scopes = vec![SCOPE_WRITE.to_string()];
+    assert!(check_token_access("acme-group", &scopes, false, "acme-group", SCOPE_WRITE).is_ok());
+    assert!(check_token_access("acme-group", &scopes, false, "acme-group", SCOPE_READ).is_ok());
+    assert!(
+        check_token_access("acme-group", &scopes, false, "acme-group", SCOPE_PIPELINE).is_err()
+    );
+
+    // A token of workspace A publishing a service into workspace B
+    let denied = check_token_access("acme-group", &scopes, false, "globex-group", SCOPE_WRITE);
+    assert!(matches!(denied, Err(ApiError::Forbidden(_))));
+    let read_only = vec![SCOPE_READ.to_string()];
+    let denied = check_token_access("acme-group", &read_only, false, "acme-group", SCOPE_WRITE);
+    assert!(matches!(denied, Err(ApiError::Forbidden(_))));
+
+    assert_eq!(
+        normalize_scopes(&[" metadata:write".to_string(), "metadata:write".to_string()]).unwrap(),
+        [SCOPE_WRITE]
+    );
+    assert!(normalize_scopes(&["admin".to_string()]).is_err());
+    assert!(normalize_scopes(&[]).is_err());
+}
+
+#[test]
+fn forged_tokens_are_rate_limited_by_client_ip() {
+    use crate::fairings::rate_limit::{bucket_for, RateLimitConfig};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    let token = |secret: &[u8]| {
+        let claims = serde_json::json!({
+            "sub": "acme-group",
+            "exp": chrono::Utc::now().timestamp() + 3600,
+        });
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap()
+    };
+    let config = RateLimitConfig::default();
+    let secret = Some(b"server-secret".as_slice());
+    let path = "/metadata/services-and-envs/acme";
+
+    let signed = format!("Bearer {}", token(b"server-secret"));
+    let (key, _) = bucket_for(path, "10.0.0.1", Some(&signed), None, secret, &config);
+    assert_eq!(key, "api:acme-group");
+
+    let forged = format!("Bearer {}", token(b"guessed-secret"));
+    let (key, limit) = bucket_for(path, "10.0.0.1", Some(&forged), None, secret, &config);
+    assert_eq!(key, "public:10.0.0.1");
+    assert_eq!(limit.requests, config.public.requests);
+    let (key, _) = bucket_for(path, "10.0.0.1", None, Some(&forged), secret, &config);
+    assert_eq!(key, "public:10.0.0.1");
+
+    // Nothing can be verified without the secret
+    let (key, _) = bucket_for(path, "10.0.0.1", Some(&signed), None, None, &config);
+    assert_eq!(key, "public:10.0.0.1");
+}
+
+#[test]
+fn identifiers_of_other_workspaces_are_not_taken_over() {
+    use crate::errors::ApiError;
+    use crate::routes::metadata::check_identifier_owner;
+
+    assert!(check_identifier_owner("Service", None, "acme").is_ok());
+    assert!(check_identifier_owner("Service", Some(Some("acme".to_string())), "acme").is_ok());
+    // Registered before services were tied to a workspace
+    assert!(check_identifier_owner("Package", Some(None), "acme").is_ok());
+
+    let taken = check_identifier_owner("Service", Some(Some("globex".to_string())), "acme");
+    assert!(matches!(taken, Err(ApiError::Conflict(_))));
+}
+
+#[test]
+fn inline_documents_never_read_as_blob_references() {
+    use crate::db::blobs::{
+        classify, content_hash, resolve_document, store_document, stored_hash, StoredDocument,
+    };
+
+    let runtime = rocket::tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let round_trip = |content: &str| {
+        runtime.block_on(async {
+            let stored = store_document(None, Some(content.to_string()), 100)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(stored_hash(&stored), content_hash(content));
+            resolve_document(None, Some(stored)).await.unwrap().unwrap()
+        })
+    };
+
+    let lookalike = format!("blob:sha256:{}", content_hash("spec"));
+    assert_eq!(round_trip(&lookalike), lookalike);
+    assert_eq!(round_trip("inline:spec"), "inline:spec");
+    assert_eq!(round_trip("openapi: 3.0.0"), "openapi: 3.0.0");
+
+    assert_eq!(classify("blob:sha256:abc"), StoredDocument::Blob("abc"));
+    assert_eq!(classify("openapi"), StoredDocument::Inline("openapi"));
+}
+
+#[test]
+fn deleted_items_are_restorable_within_retention() {
+    use crate::errors::ApiError;
+    use crate::routes::deletions::{restorable, schema_identifier};
+    use chrono::{Duration, Utc};
+
+    let cutoff = Utc::now() - Duration::days(30);
+    let recent = cutoff + Duration::days(1);
+
+    assert_eq!(restorable(Some(Some(recent)), cutoff).ok(), Some(recent));
+    assert!(matches!(
+        restorable(Some(Some(cutoff - Duration::days(1))), cutoff),
+        Err(ApiError::NotFound(_))
+    ));
+    // Live rows and rows of another workspace are not found either
+    assert!(matches!(
+        restorable(Some(None), cutoff),
+        Err(ApiError::NotFound(_))
+    ));
+    assert!(matches!(
+        restorable(None, cutoff),
+        Err(ApiError::NotFound(_))
+    ));
+
+    assert_eq!(
+        schema_identifier(Some("orders-db".to_string()), 7),
+        "orders-db"
+    );
+    assert_eq!(schema_identifier(None, 7), "7");
+}
+
+#[test]
+fn legacy_api_tokens_keep_their_workspace_access() {
+    use crate::errors::ApiError;
+    use crate::middlewares::api_token_claims::{
+        check_token_access, legacy_tokens_allowed, token_jti, SCOPE_PIPELINE, SCOPE_WRITE,
+    };
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    let key = EncodingKey::from_secret(b"secret");
+    let legacy = encode(
+        &Header::default(),
+        &json!({"sub": "acme-group", "exp": 1}),
+        &key,
+    )
+    .unwrap();
+    let minted = encode(
+        &Header::default(),
+        &json!({"sub": "acme-group", "exp": 1, "jti": "tok-1"}),
+        &key,
+    )
+    .unwrap();
+    assert_eq!(token_jti(&legacy), None);
+    assert_eq!(token_jti(&minted).as_deref(), Some("tok-1"));
+
+    // Allowed until API_TOKENS_ALLOW_LEGACY=false, without scopes but still within the workspace
+    assert!(legacy_tokens_allowed());
+    assert!(check_token_access("acme-group", &[], true, "acme-group", SCOPE_WRITE).is_ok());
+    assert!(check_token_access("acme-group", &[], true, "acme-group", SCOPE_PIPELINE).is_ok());
+    let denied = check_token_access("acme-group", &[], true, "globex-group", SCOPE_WRITE);
+    assert!(matches!(denied, Err(ApiError::Forbidden(_))));
+    assert!(check_token_access("acme-group", &[], false, "acme-group", SCOPE_WRITE).is_err());
+}